@@ -0,0 +1,308 @@
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use twox_hash::XxHash3_64;
+
+use crate::page::PageId;
+use crate::tuple::{Tuple, TupleValue};
+use crate::util::side_file::{self, Reader};
+
+const FILE_EXTENSION: &str = "bloom";
+// Filters of ranges written before the first vacuum are sized for this many rows a page.
+const DEFAULT_ROWS_PER_PAGE: usize = 64;
+
+/// Bloom filters of chosen columns of a heap file, one per range of
+/// `pages_per_range` pages, to skip pages that certainly lack a value.
+///
+/// A filter is sized for the rows its range had when it was built, so the
+/// false positive rate holds until many more rows are written to the range.
+/// A vacuum builds the filters again, sized for the rows the ranges have then.
+/// Deleted values stay in a filter until then as well.
+#[derive(Debug)]
+pub struct BloomFilters {
+    path: PathBuf,
+    columns: Vec<usize>,
+    pages_per_range: u64,
+    false_positive_rate: f64,
+    hash_count: u32,
+    page_count: u64,
+    // Filter of range `i` and column `j` is `filters[i * columns.len() + j]`.
+    filters: Vec<BloomFilter>,
+    // Changed since the last flush, the stored filters are removed then.
+    dirty: bool,
+}
+
+impl BloomFilters {
+    /// Opens the filters stored next to the heap file `filename`. There are
+    /// none if they were not built yet, or with other columns, ranges or rate.
+    pub fn open(
+        path: &str,
+        filename: &str,
+        columns: &[usize],
+        pages_per_range: u64,
+        false_positive_rate: f64,
+    ) -> Result<BloomFilters, Error> {
+        assert!(pages_per_range > 0, "A range has at least one page");
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "A false positive rate is between 0 and 1"
+        );
+
+        let path = Path::new(path).join(format!("{}.{}", filename, FILE_EXTENSION));
+
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err),
+        };
+
+        let mut filters = BloomFilters {
+            path,
+            columns: columns.to_vec(),
+            pages_per_range,
+            false_positive_rate,
+            hash_count: hash_count(false_positive_rate),
+            page_count: 0,
+            filters: vec![],
+            dirty: false,
+        };
+
+        if !data.is_empty() {
+            let mut reader = Reader::new(&data, "Bloom filter file");
+
+            if reader.u64()? == pages_per_range
+                && reader.u64()? == false_positive_rate.to_bits()
+                && reader.u16()? as usize == columns.len()
+                && columns
+                    .iter()
+                    .all(|&column| matches!(reader.u16(), Ok(c) if c as usize == column))
+            {
+                let page_count = reader.u64()?;
+                let range_count = page_count.div_ceil(pages_per_range) as usize;
+
+                for _ in 0..range_count * columns.len() {
+                    let len = reader.u32()? as usize;
+                    // A filter has at least one word, bits are taken modulo its size.
+                    if len == 0 {
+                        return Err(reader.corrupted());
+                    }
+                    let words = (0..len)
+                        .map(|_| reader.u64())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    filters
+                        .filters
+                        .push(BloomFilter::from_words(words, filters.hash_count));
+                }
+                filters.page_count = page_count;
+            }
+        }
+
+        Ok(filters)
+    }
+
+    /// Number of pages the filters cover, pages after them are not summarized.
+    pub fn page_count(&self) -> u64 {
+        self.page_count
+    }
+
+    pub fn pages_per_range(&self) -> u64 {
+        self.pages_per_range
+    }
+
+    pub fn hash_count(&self) -> u32 {
+        self.hash_count
+    }
+
+    /// Size in bits of the filter of a column for the range holding `page_id`.
+    pub fn filter_bits(&self, page_id: PageId, column: usize) -> Option<usize> {
+        let index = self.filter_index(page_id, column)?;
+
+        self.filters.get(index).map(BloomFilter::bits)
+    }
+
+    /// Adds a row whose values of the filtered columns are `values`, in the
+    /// order of `columns`, to the filters of the range of `page_id`.
+    pub fn insert(&mut self, page_id: PageId, values: &[TupleValue]) {
+        let start = self.add_page(page_id) * self.columns.len();
+
+        for (column, value) in values.iter().enumerate() {
+            self.set_bits(start + column, value);
+        }
+    }
+
+    pub fn insert_tuple(&mut self, page_id: PageId, tuple: &Tuple) {
+        let start = self.add_page(page_id) * self.columns.len();
+
+        for index in 0..self.columns.len() {
+            self.set_bits(start + index, &tuple.values[self.columns[index]]);
+        }
+    }
+
+    /// Covers `page_id` without adding a row to it.
+    pub fn cover(&mut self, page_id: PageId) {
+        self.add_page(page_id);
+    }
+
+    /// Empties the filters of the range of `page_id` and sizes them for
+    /// `row_count` rows, which are added by `insert` afterwards.
+    pub fn reset_range(&mut self, page_id: PageId, row_count: usize) {
+        let start = self.add_page(page_id) * self.columns.len();
+        let filter = BloomFilter::new(row_count, self.false_positive_rate);
+
+        self.filters[start..start + self.columns.len()].fill(filter);
+    }
+
+    /// Whether a page may hold a row whose `column` equals `value`, as in SQL.
+    /// Pages not covered and columns without a filter always may.
+    pub fn may_contain(&self, page_id: PageId, column: usize, value: &TupleValue) -> bool {
+        if page_id >= self.page_count {
+            return true;
+        }
+        if value.is_null() {
+            return false;
+        }
+
+        let Some(index) = self.filter_index(page_id, column) else {
+            return true;
+        };
+
+        self.filters[index].may_contain(hash_value(value))
+    }
+
+    /// Removes the stored filters before the first change after an open or a
+    /// flush, filters that miss rows written since are built again on open.
+    pub fn invalidate(&mut self) -> Result<(), Error> {
+        if !self.dirty {
+            side_file::remove(&self.path)?;
+            self.dirty = true;
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let mut data = vec![];
+        data.extend_from_slice(&self.pages_per_range.to_be_bytes());
+        data.extend_from_slice(&self.false_positive_rate.to_bits().to_be_bytes());
+        data.extend_from_slice(&(self.columns.len() as u16).to_be_bytes());
+        for &column in &self.columns {
+            data.extend_from_slice(&(column as u16).to_be_bytes());
+        }
+        data.extend_from_slice(&self.page_count.to_be_bytes());
+
+        for filter in &self.filters {
+            data.extend_from_slice(&(filter.words.len() as u32).to_be_bytes());
+            for word in &filter.words {
+                data.extend_from_slice(&word.to_be_bytes());
+            }
+        }
+
+        side_file::write(&self.path, &data)?;
+        self.dirty = false;
+
+        Ok(())
+    }
+
+    fn filter_index(&self, page_id: PageId, column: usize) -> Option<usize> {
+        let index = self.columns.iter().position(|&c| c == column)?;
+
+        Some((page_id / self.pages_per_range) as usize * self.columns.len() + index)
+    }
+
+    // Index of the range of the page, new ranges get filters of the default size.
+    fn add_page(&mut self, page_id: PageId) -> usize {
+        self.page_count = self.page_count.max(page_id + 1);
+
+        let filter_count =
+            self.page_count.div_ceil(self.pages_per_range) as usize * self.columns.len();
+        if self.filters.len() < filter_count {
+            let filter = BloomFilter::new(
+                self.pages_per_range as usize * DEFAULT_ROWS_PER_PAGE,
+                self.false_positive_rate,
+            );
+            self.filters.resize(filter_count, filter);
+        }
+
+        (page_id / self.pages_per_range) as usize
+    }
+
+    fn set_bits(&mut self, filter: usize, value: &TupleValue) {
+        // Equality never holds for a null, it is not added.
+        if value.is_null() {
+            return;
+        }
+
+        self.filters[filter].insert(hash_value(value));
+    }
+}
+
+/// Bloom filter of 64 bit hashes, e.g. XxHash3 hashes of keys.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BloomFilter {
+    words: Vec<u64>,
+    hash_count: u32,
+}
+
+impl BloomFilter {
+    /// Empty filter sized for `row_count` rows at the false positive rate,
+    /// `-ln(p) / ln(2)^2` bits a row.
+    pub fn new(row_count: usize, false_positive_rate: f64) -> BloomFilter {
+        let bits_per_row = -false_positive_rate.ln() / (2f64.ln() * 2f64.ln());
+        let words = ((row_count.max(1) as f64 * bits_per_row).ceil() as usize).div_ceil(64);
+
+        BloomFilter {
+            words: vec![0; words],
+            hash_count: hash_count(false_positive_rate),
+        }
+    }
+
+    pub fn from_words(words: Vec<u64>, hash_count: u32) -> BloomFilter {
+        BloomFilter { words, hash_count }
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    pub fn hash_count(&self) -> u32 {
+        self.hash_count
+    }
+
+    pub fn bits(&self) -> usize {
+        self.words.len() * 64
+    }
+
+    pub fn insert(&mut self, hash: u64) {
+        for bit in self.bit_indexes(hash) {
+            self.words[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn may_contain(&self, hash: u64) -> bool {
+        self.bit_indexes(hash)
+            .all(|bit| self.words[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    // Bits of a hash, derived from it by double hashing.
+    fn bit_indexes(&self, hash: u64) -> impl Iterator<Item = usize> + use<> {
+        let bits = self.bits() as u64;
+        let (first, second) = (hash, hash.rotate_left(32) | 1);
+
+        (0..self.hash_count as u64)
+            .map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % bits) as usize)
+    }
+}
+
+/// The best count of hashes for a filter of the best size, it depends only on the rate.
+pub fn hash_count(false_positive_rate: f64) -> u32 {
+    (-false_positive_rate.log2()).round().clamp(1.0, 32.0) as u32
+}
+
+// Values equal in SQL hash the same, whatever their type.
+fn hash_value(value: &TupleValue) -> u64 {
+    let mut hasher = XxHash3_64::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
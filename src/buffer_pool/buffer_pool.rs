@@ -1,11 +1,13 @@
 #![cfg_attr(test, allow(dead_code))]
 
-use parking_lot::{MappedRwLockReadGuard, MappedRwLockWriteGuard};
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::{MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock};
 
 use crate::{
     buffer_pool::page_hash_map::{BufferPoolPageHashMap, InsertPageError, InsertPageResult},
-    page::{Page, PageId},
-    persist::Reader,
+    page::{Page, PageKey, RelationId},
+    persist::relation::RelationFile,
 };
 
 pub enum BufferPoolPage<'a> {
@@ -32,37 +34,96 @@ impl<'a> ReadPageGuard<'a> {
 
     pub fn get(&'a self) -> &'a Page {
         match &self.buffer_pool_page {
-            BufferPoolPage::PageFromPool(page) => &*page,
-            BufferPoolPage::PageFromDisk(page) => &*page,
+            BufferPoolPage::PageFromPool(page) => page,
+            BufferPoolPage::PageFromDisk(page) => page,
         }
     }
 }
 
+pub struct WritePageGuard<'a> {
+    page: MappedRwLockWriteGuard<'a, Page>,
+}
+
+impl<'a> WritePageGuard<'a> {
+    pub fn get(&self) -> &Page {
+        &self.page
+    }
+
+    pub fn get_mut(&mut self) -> &mut Page {
+        &mut self.page
+    }
+}
+
+/// Caches pages of many relations, a page is identified by its `PageKey`.
+/// Every relation must use the page size of the pool.
+///
+/// Pages are written through: a modified page goes to disk via `write_page`
+/// while its frame is still locked, so frames are never dirty on eviction.
 pub struct BufferPool<'a> {
     page_map: BufferPoolPageHashMap<'a>,
-    reader: Reader,
+    page_size: usize,
+    relations: RwLock<HashMap<RelationId, Arc<RelationFile>>>,
 }
 
 #[derive(Debug)]
 pub enum GetPageError<'a> {
     FailedToInsert(InsertPageError<'a>),
     FailedToReadFromDisk,
+    FailedToWriteToDisk(std::io::Error),
+    CorruptedPage(PageKey),
+    UnknownRelation(RelationId),
+}
+
+#[derive(Debug)]
+pub enum AddRelationError {
+    RelationExists(RelationId),
+    PageSizeMismatch(usize),
 }
 
 impl<'a> BufferPool<'a> {
-    pub fn new(size: usize, reader: Reader) -> BufferPool<'a> {
+    pub fn new(size: usize, page_size: usize) -> BufferPool<'a> {
         BufferPool {
-            page_map: BufferPoolPageHashMap::new(size),
-            reader,
+            page_map: BufferPoolPageHashMap::with_page_size(size, page_size),
+            page_size,
+            relations: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn add_relation(
+        &self,
+        relation_id: RelationId,
+        file: RelationFile,
+    ) -> Result<(), AddRelationError> {
+        if file.page_size() != self.page_size {
+            return Err(AddRelationError::PageSizeMismatch(file.page_size()));
+        }
+
+        let mut relations = self.relations.write();
+        if relations.contains_key(&relation_id) {
+            return Err(AddRelationError::RelationExists(relation_id));
         }
+
+        relations.insert(relation_id, Arc::new(file));
+
+        Ok(())
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    pub fn page_count(&self, relation_id: RelationId) -> Result<u64, GetPageError<'a>> {
+        Ok(self.relation(relation_id)?.page_count())
     }
 
-    pub fn get(&'a self, page_id: PageId) -> Result<ReadPageGuard<'a>, GetPageError<'a>> {
-        if let Some(read_guard) = self.page_map.read_page(&page_id) {
+    pub fn get(&'a self, key: PageKey) -> Result<ReadPageGuard<'a>, GetPageError<'a>> {
+        let relation = self.relation(key.relation_id)?;
+
+        if let Some(read_guard) = self.page_map.read_page(&key) {
             return Ok(ReadPageGuard::new_page_from_pool(read_guard));
         }
 
-        let insert_result = self.page_map.insert_page(&page_id);
+        let insert_result = self.page_map.insert_page(&key);
         let Ok(insert_result) = insert_result else {
             return Err(GetPageError::FailedToInsert(insert_result.err().unwrap()));
         };
@@ -70,14 +131,154 @@ impl<'a> BufferPool<'a> {
         match insert_result {
             InsertPageResult::ExistingPage(guard) => Ok(ReadPageGuard::new_page_from_pool(guard)),
             InsertPageResult::NewPage(mut write_guard) => {
-                let Ok(_) = self.reader.read_page(page_id, &mut *write_guard) else {
+                let Ok(_) = relation.read_page(key.page_id, &mut write_guard) else {
                     return Err(GetPageError::FailedToReadFromDisk);
                 };
-                write_guard.id = page_id;
-                write_guard.refresh_metadata();
+                // A frame gets its key only once the page is read and valid.
+                if write_guard.refresh_metadata().is_err() {
+                    return Err(GetPageError::CorruptedPage(key));
+                }
+                write_guard.id = key.page_id;
+                write_guard.relation_id = key.relation_id;
 
                 Ok(ReadPageGuard::new_page_from_disk(write_guard))
             }
         }
     }
+
+    pub fn get_mut(&'a self, key: PageKey) -> Result<WritePageGuard<'a>, GetPageError<'a>> {
+        let relation = self.relation(key.relation_id)?;
+
+        loop {
+            if let Some(page) = self.page_map.write_page(&key) {
+                return Ok(WritePageGuard { page });
+            }
+
+            let insert_result = self.page_map.insert_page(&key);
+            let Ok(insert_result) = insert_result else {
+                return Err(GetPageError::FailedToInsert(insert_result.err().unwrap()));
+            };
+
+            // An existing page was inserted by another thread meanwhile, it is
+            // locked for writing on the next iteration.
+            if let InsertPageResult::NewPage(mut page) = insert_result {
+                let Ok(_) = relation.read_page(key.page_id, &mut page) else {
+                    return Err(GetPageError::FailedToReadFromDisk);
+                };
+                if page.refresh_metadata().is_err() {
+                    return Err(GetPageError::CorruptedPage(key));
+                }
+                page.id = key.page_id;
+                page.relation_id = key.relation_id;
+
+                return Ok(WritePageGuard { page });
+            }
+        }
+    }
+
+    /// Puts an empty page to the pool without reading it from disk.
+    pub fn new_page(&'a self, key: PageKey) -> Result<WritePageGuard<'a>, GetPageError<'a>> {
+        self.relation(key.relation_id)?;
+
+        loop {
+            let mut page = match self.page_map.write_page(&key) {
+                Some(page) => page,
+                None => match self.page_map.insert_page(&key) {
+                    Ok(InsertPageResult::NewPage(page)) => page,
+                    Ok(InsertPageResult::ExistingPage(_)) => continue,
+                    Err(err) => return Err(GetPageError::FailedToInsert(err)),
+                },
+            };
+
+            page.reset(key);
+
+            return Ok(WritePageGuard { page });
+        }
+    }
+
+    pub fn write_page(&self, page: &WritePageGuard) -> Result<(), GetPageError<'a>> {
+        self.relation(page.get().relation_id)?
+            .write_page(page.get())
+            .map_err(GetPageError::FailedToWriteToDisk)
+    }
+
+    /// Loads pages that are not in the pool yet with one batched read per relation.
+    ///
+    /// Pages are read straight into their frames, which stay locked until the
+    /// batch of their relation is read. Keys are locked in sorted order, so
+    /// prefetches of overlapping batches don't deadlock. A batch needs as many
+    /// free or unlocked frames as it has missing pages.
+    pub fn prefetch(&'a self, keys: &[PageKey]) -> Result<(), GetPageError<'a>> {
+        let mut keys = keys.to_vec();
+        keys.sort_unstable();
+        keys.dedup();
+
+        let batches = keys
+            .chunk_by(|a, b| a.relation_id == b.relation_id)
+            .map(|keys| Ok((self.relation(keys[0].relation_id)?, keys)))
+            .collect::<Result<Vec<_>, GetPageError<'a>>>()?;
+
+        for (relation, keys) in batches {
+            let mut frames = vec![];
+            let mut result = Ok(());
+
+            for key in keys {
+                if self.page_map.read_page(key).is_some() {
+                    continue;
+                }
+
+                match self.page_map.insert_page(key) {
+                    Ok(InsertPageResult::NewPage(mut frame)) => {
+                        let previous_key = frame.key();
+                        frame.id = key.page_id;
+                        frame.relation_id = key.relation_id;
+                        frames.push((frame, previous_key));
+                    }
+                    Ok(InsertPageResult::ExistingPage(_)) => {}
+                    Err(err) => {
+                        // The frames taken so far are still read, they are in the map already.
+                        result = Err(GetPageError::FailedToInsert(err));
+                        break;
+                    }
+                }
+            }
+
+            let mut pages = frames
+                .iter_mut()
+                .map(|(frame, _)| &mut **frame)
+                .collect::<Vec<_>>();
+            if relation.read_pages(&mut pages).is_err() {
+                // As in `get`, a frame that was not read keeps its previous
+                // key, so no lookup of the new key finds the stale bytes.
+                for (frame, previous_key) in &mut frames {
+                    frame.id = previous_key.page_id;
+                    frame.relation_id = previous_key.relation_id;
+                }
+
+                return Err(GetPageError::FailedToReadFromDisk);
+            }
+
+            for (frame, previous_key) in &mut frames {
+                if frame.refresh_metadata().is_err() {
+                    let key = frame.key();
+                    frame.id = previous_key.page_id;
+                    frame.relation_id = previous_key.relation_id;
+
+                    result = result.and(Err(GetPageError::CorruptedPage(key)));
+                }
+            }
+
+            result?;
+        }
+
+        Ok(())
+    }
+
+    fn relation(&self, relation_id: RelationId) -> Result<Arc<RelationFile>, GetPageError<'a>> {
+        self.relations
+            .read()
+            .get(&relation_id)
+            .cloned()
+            .ok_or(GetPageError::UnknownRelation(relation_id))
+    }
 }
@@ -12,15 +12,12 @@ pub struct Clock {
 
 impl Clock {
     pub fn new(size: usize) -> Self {
-        let inidicators_length = (2 * size + BITMAP_CELL_SIZE - 1) / BITMAP_CELL_SIZE;
+        let inidicators_length = (2 * size).div_ceil(BITMAP_CELL_SIZE);
 
         Self {
             size,
             clock: AtomicUsize::new(0),
-            read_indicator: (0..inidicators_length)
-                .into_iter()
-                .map(|_| AtomicU8::new(0))
-                .collect(),
+            read_indicator: (0..inidicators_length).map(|_| AtomicU8::new(0)).collect(),
         }
     }
 
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod buffer_pool;
-pub mod page_hash_map;
 pub mod clock;
+pub mod page_hash_map;
@@ -2,27 +2,30 @@
 
 use crate::{
     buffer_pool::clock::Clock,
-    page::{Page, PageId},
+    page::{Page, PageKey, SIZE},
     util::free_list::{AllocatedPage, ConcurrentFreeList},
 };
 use parking_lot::{
     MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard,
     RwLockUpgradableReadGuard, RwLockWriteGuard,
 };
+use std::sync::atomic::{AtomicU64, Ordering};
 use twox_hash::XxHash3_64;
 
+// Tags of slots that hold no page, a tag of a page is never one of them.
+const EMPTY: u64 = 0;
+const THUMBSTONE: u64 = 1;
+
 #[derive(Debug)]
 pub struct Entry<'a> {
     allocated_page: Option<AllocatedPage<'a>>,
 }
 
 impl<'a> Entry<'a> {
-    fn page_id(&self) -> Option<&PageId> {
-        let Some(allocated_page) = self.allocated_page.as_ref() else {
-            return None;
-        };
+    fn page_key(&self) -> Option<PageKey> {
+        let allocated_page = self.allocated_page.as_ref()?;
 
-        Some(&allocated_page.page.id)
+        Some(allocated_page.page.key())
     }
 
     fn is_thumbstone(&self) -> bool {
@@ -46,37 +49,45 @@ pub enum InsertPageError<'a> {
     FailedToInsert,
 }
 
+/// Open addressing map from page keys to frames, every slot has its own lock.
+///
+/// Every slot also has a tag, the hash of the key it holds. Lookups skip the
+/// slots of other keys by their tags without locking them, so a thread may
+/// keep pages locked while it looks up other pages.
 #[derive(Debug)]
 pub struct BufferPoolPageHashMap<'a> {
     size: usize,
     free_list: ConcurrentFreeList<'a>,
     clock: Clock,
     pub page_keys: Vec<RwLock<Option<Entry<'a>>>>,
+    tags: Vec<AtomicU64>,
 }
 
 impl<'a> BufferPoolPageHashMap<'a> {
     pub fn new(size: usize) -> Self {
+        Self::with_page_size(size, SIZE)
+    }
+
+    pub fn with_page_size(size: usize, page_size: usize) -> Self {
         Self {
             size,
-            free_list: ConcurrentFreeList::new((0..size).collect()),
+            free_list: ConcurrentFreeList::with_page_size((0..size).collect(), page_size),
             clock: Clock::new(size * 2),
-            page_keys: (0..size * 2)
-                .into_iter()
-                .map(|_| RwLock::new(None))
-                .collect(),
+            page_keys: (0..size * 2).map(|_| RwLock::new(None)).collect(),
+            tags: (0..size * 2).map(|_| AtomicU64::new(EMPTY)).collect(),
         }
     }
 
     pub fn insert_page(
         &'a self,
-        page_id: &PageId,
+        key: &PageKey,
     ) -> Result<InsertPageResult<'a>, InsertPageError<'a>> {
         let allocate_result = self.try_allocate_page();
         let Ok(allocated_page) = allocate_result else {
             return Err(InsertPageError::NoFreeSlot(allocate_result.err().unwrap()));
         };
 
-        let insert_result = self.try_insert_page(page_id);
+        let insert_result = self.try_insert_page(key);
 
         match insert_result {
             Ok(InsertPageResultInternal::NewPage(mut guard)) => {
@@ -114,12 +125,16 @@ impl<'a> BufferPoolPageHashMap<'a> {
                         .find_victim_key()
                         .or(Err("Cannot find victim key"))?;
 
-                    let mut guard = self.page_keys[victim_key_index].write();
+                    // A locked page is in use, another victim is tried.
+                    let Some(mut guard) = self.page_keys[victim_key_index].try_write() else {
+                        continue;
+                    };
                     let page_key = guard.as_mut().ok_or("Cannot get lock")?;
                     let Some(allocated_page) = page_key.allocated_page.take() else {
                         continue;
                     };
 
+                    self.tags[victim_key_index].store(THUMBSTONE, Ordering::Release);
                     self.clock.track_delete(&victim_key_index);
 
                     return Ok(allocated_page);
@@ -130,30 +145,43 @@ impl<'a> BufferPoolPageHashMap<'a> {
         Err("Cannot find a slot after several retries")
     }
 
-    fn try_insert_page(&'a self, page_id: &PageId) -> Result<InsertPageResultInternal<'a>, ()> {
-        let hash = XxHash3_64::oneshot(&page_id.to_be_bytes()) as usize;
-        let key = hash % self.size;
+    fn try_insert_page(&'a self, key: &PageKey) -> Result<InsertPageResultInternal<'a>, ()> {
+        let hash = XxHash3_64::oneshot(&key.to_bytes());
+        let start = hash as usize % self.size;
 
         let keys_size = self.size * 2;
-        let mut k = key;
+        let mut k = start;
 
         loop {
             let k_idx = k % keys_size;
+
+            // Empty slots and thumbstones are locked, a new page may be inserted there.
+            let slot_tag = self.tags[k_idx].load(Ordering::Acquire);
+            if slot_tag != EMPTY && slot_tag != THUMBSTONE && slot_tag != tag(hash) {
+                k += 1;
+
+                if k == start + keys_size {
+                    return Err(());
+                }
+
+                continue;
+            }
+
             let key_read_guard = self.page_keys[k_idx].upgradable_read();
             let page_key = &*key_read_guard;
 
-            if matches!(page_key, Some(page_key) if !page_key.is_thumbstone() && page_key.page_id().unwrap() != page_id)
+            if matches!(page_key, Some(page_key) if !page_key.is_thumbstone() && page_key.page_key().unwrap() != *key)
             {
                 k += 1;
 
-                if k == key + keys_size {
+                if k == start + keys_size {
                     return Err(());
                 }
 
                 continue;
             }
 
-            if matches!(page_key, Some(page_key) if !page_key.is_thumbstone() && page_key.page_id().unwrap() == page_id)
+            if matches!(page_key, Some(page_key) if !page_key.is_thumbstone() && page_key.page_key().unwrap() == *key)
             {
                 self.clock.track_read(&k_idx);
 
@@ -166,27 +194,92 @@ impl<'a> BufferPoolPageHashMap<'a> {
                 continue;
             };
 
+            self.tags[k_idx].store(tag(hash), Ordering::Release);
             self.clock.track_insert(&k_idx);
 
             return Ok(InsertPageResultInternal::NewPage(write_lock));
         }
     }
 
-    pub fn read_page(&self, page_id: &PageId) -> Option<MappedRwLockReadGuard<Page>> {
-        let hash = XxHash3_64::oneshot(&page_id.to_be_bytes()) as usize;
-        let key = hash % self.size;
+    pub fn write_page(&self, key: &PageKey) -> Option<MappedRwLockWriteGuard<'_, Page>> {
+        let hash = XxHash3_64::oneshot(&key.to_bytes());
+        let start = hash as usize % self.size;
+
+        let mut k = start;
+        let keys_size = self.size * 2;
+
+        loop {
+            let k_idx = k % keys_size;
+
+            match self.tags[k_idx].load(Ordering::Acquire) {
+                EMPTY => break None,
+                slot_tag if slot_tag != tag(hash) => {
+                    k += 1;
+
+                    if k == start + keys_size {
+                        break None;
+                    }
+
+                    continue;
+                }
+                _ => {}
+            }
+
+            let key_read_guard = self.page_keys[k_idx].upgradable_read();
+
+            match &*key_read_guard {
+                Some(page_key)
+                    if !page_key.is_thumbstone() && page_key.page_key().unwrap() == *key =>
+                {
+                    self.clock.track_read(&k_idx);
+
+                    let key_write_guard = RwLockUpgradableReadGuard::upgrade(key_read_guard);
+
+                    break Some(RwLockWriteGuard::map(key_write_guard, |x| {
+                        x.as_mut().unwrap().allocated_page.as_mut().unwrap().page
+                    }));
+                }
+                Some(_) => {
+                    k += 1;
+
+                    if k == start + keys_size {
+                        break None;
+                    }
+                }
+                None => break None,
+            };
+        }
+    }
+
+    pub fn read_page(&self, key: &PageKey) -> Option<MappedRwLockReadGuard<'_, Page>> {
+        let hash = XxHash3_64::oneshot(&key.to_bytes());
+        let start = hash as usize % self.size;
 
-        let mut k = key;
+        let mut k = start;
         let keys_size = self.size * 2;
 
         loop {
             let k_idx = k % keys_size;
 
+            match self.tags[k_idx].load(Ordering::Acquire) {
+                EMPTY => break None,
+                slot_tag if slot_tag != tag(hash) => {
+                    k += 1;
+
+                    if k == start + keys_size {
+                        break None;
+                    }
+
+                    continue;
+                }
+                _ => {}
+            }
+
             let key_read_guard = self.page_keys[k_idx].read();
 
             match &*key_read_guard {
                 Some(page_key)
-                    if !page_key.is_thumbstone() && page_key.page_id().unwrap() == page_id =>
+                    if !page_key.is_thumbstone() && page_key.page_key().unwrap() == *key =>
                 {
                     self.clock.track_read(&k_idx);
 
@@ -196,11 +289,11 @@ impl<'a> BufferPoolPageHashMap<'a> {
                 }
                 Some(page_key)
                     if page_key.is_thumbstone()
-                        || !page_key.is_thumbstone() && page_key.page_id().unwrap() != page_id =>
+                        || !page_key.is_thumbstone() && page_key.page_key().unwrap() != *key =>
                 {
                     k += 1;
 
-                    if k == key + keys_size {
+                    if k == start + keys_size {
                         break None;
                     }
                 }
@@ -209,3 +302,7 @@ impl<'a> BufferPoolPageHashMap<'a> {
         }
     }
 }
+
+fn tag(hash: u64) -> u64 {
+    hash.max(THUMBSTONE + 1)
+}
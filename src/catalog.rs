@@ -0,0 +1,539 @@
+use std::sync::LazyLock;
+
+use parking_lot::Mutex;
+
+use crate::{
+    buffer_pool::buffer_pool::BufferPool,
+    heap_file::{HeapFile, HeapFileError},
+    page::RelationId,
+    schema::{Column, DataType, Schema},
+    tuple::{Tuple, TupleValue},
+};
+
+pub const TABLES_RELATION_ID: RelationId = 0;
+pub const COLUMNS_RELATION_ID: RelationId = 1;
+pub const INDEXES_RELATION_ID: RelationId = 2;
+// Ids below are reserved for the catalog itself.
+pub const FIRST_USER_RELATION_ID: RelationId = 16;
+
+const TABLES: &str = "catalog_tables";
+const COLUMNS: &str = "catalog_columns";
+const INDEXES: &str = "catalog_indexes";
+
+static TABLES_SCHEMA: LazyLock<Schema> = LazyLock::new(|| {
+    Schema::parse(&[
+        ("id", "integer not null"),
+        ("name", "varchar not null"),
+        ("filename", "varchar not null"),
+        ("engine", "varchar not null"),
+    ])
+    .unwrap()
+});
+// Tables rows of catalogs written before tables had an engine.
+static LEGACY_TABLES_SCHEMA: LazyLock<Schema> = LazyLock::new(|| {
+    Schema::parse(&[
+        ("id", "integer not null"),
+        ("name", "varchar not null"),
+        ("filename", "varchar not null"),
+    ])
+    .unwrap()
+});
+// Nullable is 0 or 1.
+static COLUMNS_SCHEMA: LazyLock<Schema> = LazyLock::new(|| {
+    Schema::parse(&[
+        ("table_id", "integer not null"),
+        ("name", "varchar not null"),
+        ("type", "varchar not null"),
+        ("ordinal", "integer not null"),
+        ("nullable", "integer not null"),
+    ])
+    .unwrap()
+});
+// Index columns are ordinals joined with ",", unique is 0 or 1.
+static INDEXES_SCHEMA: LazyLock<Schema> = LazyLock::new(|| {
+    Schema::parse(&[
+        ("id", "integer not null"),
+        ("table_id", "integer not null"),
+        ("name", "varchar not null"),
+        ("filename", "varchar not null"),
+        ("columns", "varchar not null"),
+        ("unique", "integer not null"),
+    ])
+    .unwrap()
+});
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableInfo {
+    pub id: RelationId,
+    pub name: String,
+    pub filename: String,
+    pub schema: Schema,
+    pub engine: Engine,
+}
+
+/// How the rows of a table are stored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Engine {
+    /// Unordered rows in slotted pages, updated in place.
+    #[default]
+    Heap,
+    /// Rows sorted by the `key` columns in a log-structured merge tree, for
+    /// tables that are mostly appended to.
+    Lsm { key: Vec<usize> },
+}
+
+impl Engine {
+    /// LSM engine keyed by the named columns of the schema.
+    pub fn lsm<'a>(schema: &Schema, key: &[&str]) -> Result<Engine, CatalogError<'a>> {
+        if key.is_empty() {
+            return Err(CatalogError::EmptyKey);
+        }
+
+        let mut ordinals = Vec::with_capacity(key.len());
+        for column in key {
+            let Some(ordinal) = schema.index_of(column) else {
+                return Err(CatalogError::ColumnNotFound(column.to_string()));
+            };
+            ordinals.push(ordinal);
+        }
+
+        Ok(Engine::Lsm { key: ordinals })
+    }
+
+    // Stored as "heap", or "lsm:" followed by key ordinals joined with ",".
+    fn name(&self) -> String {
+        match self {
+            Engine::Heap => "heap".to_owned(),
+            Engine::Lsm { key } => {
+                let key = key.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+
+                format!("lsm:{}", key.join(","))
+            }
+        }
+    }
+
+    fn parse<'a>(name: &str) -> Result<Engine, CatalogError<'a>> {
+        if name == "heap" {
+            return Ok(Engine::Heap);
+        }
+
+        let key = name
+            .strip_prefix("lsm:")
+            .ok_or(CatalogError::CorruptedRow("Unknown table engine"))?
+            .split(',')
+            .map(|c| c.parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .or(Err(CatalogError::CorruptedRow("Invalid table engine key")))?;
+
+        Ok(Engine::Lsm { key })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexInfo {
+    pub id: RelationId,
+    pub table_id: RelationId,
+    pub name: String,
+    pub filename: String,
+    /// Ordinals of the indexed columns of the table, in key order.
+    pub columns: Vec<usize>,
+    pub unique: bool,
+}
+
+#[derive(Debug)]
+pub enum CatalogError<'a> {
+    HeapFile(HeapFileError<'a>),
+    TableExists(String),
+    TableNotFound(String),
+    IndexExists(String),
+    ColumnNotFound(String),
+    /// An LSM table is keyed by at least one column.
+    EmptyKey,
+    CorruptedRow(&'static str),
+}
+
+impl<'a> From<HeapFileError<'a>> for CatalogError<'a> {
+    fn from(err: HeapFileError<'a>) -> CatalogError<'a> {
+        CatalogError::HeapFile(err)
+    }
+}
+
+/// Tables, columns and indexes of a database, stored in heap files next to the data.
+///
+/// Catalog tables describe themselves: they are written to the catalog the
+/// first time it is opened. Tables and indexes share one sequence of relation
+/// ids, which are also their ids in the buffer pool.
+pub struct Catalog<'a> {
+    path: String,
+    tables: HeapFile<'a>,
+    columns: HeapFile<'a>,
+    indexes: HeapFile<'a>,
+    next_relation_id: Mutex<RelationId>,
+}
+
+impl<'a> Catalog<'a> {
+    pub fn open(pool: &'a BufferPool<'a>, path: &str) -> Result<Catalog<'a>, CatalogError<'a>> {
+        let columns = HeapFile::open(pool, COLUMNS_RELATION_ID, path, COLUMNS, &COLUMNS_SCHEMA)?;
+
+        // The catalog's own columns tell which format its tables rows have.
+        let tables = if column_count(&columns, TABLES_RELATION_ID)? == LEGACY_TABLES_SCHEMA.len() {
+            let tables = HeapFile::open(
+                pool,
+                TABLES_RELATION_ID,
+                path,
+                TABLES,
+                &LEGACY_TABLES_SCHEMA,
+            )?;
+            upgrade_tables(tables, &columns)?
+        } else {
+            HeapFile::open(pool, TABLES_RELATION_ID, path, TABLES, &TABLES_SCHEMA)?
+        };
+
+        let catalog = Catalog {
+            path: path.to_owned(),
+            tables,
+            columns,
+            indexes: HeapFile::open(pool, INDEXES_RELATION_ID, path, INDEXES, &INDEXES_SCHEMA)?,
+            next_relation_id: Mutex::new(FIRST_USER_RELATION_ID),
+        };
+        catalog.bootstrap()?;
+
+        Ok(catalog)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Writes the catalog tables to the catalog on first open, and finds the
+    /// next free relation id.
+    fn bootstrap(&self) -> Result<(), CatalogError<'a>> {
+        let mut next_relation_id = self.next_relation_id.lock();
+
+        if self.tables.page_count() == 0 {
+            for (id, name, schema) in [
+                (TABLES_RELATION_ID, TABLES, &*TABLES_SCHEMA),
+                (COLUMNS_RELATION_ID, COLUMNS, &*COLUMNS_SCHEMA),
+                (INDEXES_RELATION_ID, INDEXES, &*INDEXES_SCHEMA),
+            ] {
+                self.insert_table(id, name, name, schema, Engine::Heap)?;
+            }
+        }
+
+        let table_ids = self.tables()?.into_iter().map(|t| t.id);
+        let index_ids = self.all_indexes()?.into_iter().map(|i| i.id);
+
+        *next_relation_id = table_ids
+            .chain(index_ids)
+            .map(|id| id + 1)
+            .fold(FIRST_USER_RELATION_ID, RelationId::max);
+
+        Ok(())
+    }
+
+    /// Registers a heap table, its data goes to the returned `filename`.
+    pub fn create_table(&self, name: &str, schema: &Schema) -> Result<TableInfo, CatalogError<'a>> {
+        self.create_table_with_engine(name, schema, Engine::Heap)
+    }
+
+    /// Registers a table stored by the engine. Files of an LSM table are
+    /// named after the returned `filename`.
+    pub fn create_table_with_engine(
+        &self,
+        name: &str,
+        schema: &Schema,
+        engine: Engine,
+    ) -> Result<TableInfo, CatalogError<'a>> {
+        let mut next_relation_id = self.next_relation_id.lock();
+
+        if self.table(name)?.is_some() {
+            return Err(CatalogError::TableExists(name.to_owned()));
+        }
+        if let Engine::Lsm { key } = &engine {
+            if key.is_empty() {
+                return Err(CatalogError::EmptyKey);
+            }
+            if let Some(column) = key.iter().find(|&&column| column >= schema.len()) {
+                return Err(CatalogError::ColumnNotFound(column.to_string()));
+            }
+        }
+
+        let id = *next_relation_id;
+        let filename = format!("table_{}.data", id);
+        let table = self.insert_table(id, name, &filename, schema, engine)?;
+        *next_relation_id += 1;
+
+        Ok(table)
+    }
+
+    pub fn create_index(
+        &self,
+        name: &str,
+        table_name: &str,
+        columns: &[&str],
+        unique: bool,
+    ) -> Result<IndexInfo, CatalogError<'a>> {
+        self.create_index_with(name, table_name, columns, unique, |_| Ok(()))
+    }
+
+    /// Registers an index once `build` has written its file, an index that
+    /// fails to build is never listed. Its relation id is used up either way,
+    /// the pool may hold it.
+    pub fn create_index_with<E: From<CatalogError<'a>>>(
+        &self,
+        name: &str,
+        table_name: &str,
+        columns: &[&str],
+        unique: bool,
+        build: impl FnOnce(&IndexInfo) -> Result<(), E>,
+    ) -> Result<IndexInfo, E> {
+        let mut next_relation_id = self.next_relation_id.lock();
+
+        let Some(table) = self.table(table_name)? else {
+            return Err(CatalogError::TableNotFound(table_name.to_owned()).into());
+        };
+
+        if self.all_indexes()?.iter().any(|i| i.name == name) {
+            return Err(CatalogError::IndexExists(name.to_owned()).into());
+        }
+
+        let mut ordinals = Vec::with_capacity(columns.len());
+        for column in columns {
+            let Some(ordinal) = table.schema.index_of(column) else {
+                return Err(CatalogError::ColumnNotFound(column.to_string()).into());
+            };
+            ordinals.push(ordinal);
+        }
+
+        let index = IndexInfo {
+            id: *next_relation_id,
+            table_id: table.id,
+            name: name.to_owned(),
+            filename: format!("index_{}.data", *next_relation_id),
+            columns: ordinals,
+            unique,
+        };
+
+        let columns = index
+            .columns
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let built = build(&index);
+        *next_relation_id += 1;
+        built?;
+
+        self.indexes
+            .insert(&Tuple {
+                schema: &INDEXES_SCHEMA,
+                values: vec![
+                    TupleValue::Integer(index.id as i32),
+                    TupleValue::Integer(index.table_id as i32),
+                    TupleValue::Varchar(index.name.clone()),
+                    TupleValue::Varchar(index.filename.clone()),
+                    TupleValue::Varchar(columns),
+                    TupleValue::Integer(index.unique as i32),
+                ],
+            })
+            .map_err(CatalogError::from)?;
+
+        Ok(index)
+    }
+
+    pub fn table(&self, name: &str) -> Result<Option<TableInfo>, CatalogError<'a>> {
+        Ok(self.tables()?.into_iter().find(|t| t.name == name))
+    }
+
+    pub fn tables(&self) -> Result<Vec<TableInfo>, CatalogError<'a>> {
+        let mut columns = vec![];
+        for row in self.columns.scan() {
+            let (_, row) = row?;
+
+            let table_id = integer(&row, 0)? as RelationId;
+            let Ok(data_type) = DataType::parse(varchar(&row, 2)?) else {
+                return Err(CatalogError::CorruptedRow("Unknown column type"));
+            };
+            let mut column = Column::new(varchar(&row, 1)?, data_type);
+            column.nullable = integer(&row, 4)? != 0;
+
+            columns.push((table_id, integer(&row, 3)?, column));
+        }
+
+        let mut tables = vec![];
+        for row in self.tables.scan() {
+            let (_, row) = row?;
+
+            let id = integer(&row, 0)? as RelationId;
+            let mut table_columns = columns
+                .iter()
+                .filter(|(table_id, _, _)| *table_id == id)
+                .collect::<Vec<_>>();
+            table_columns.sort_by_key(|(_, ordinal, _)| *ordinal);
+
+            let table_columns = table_columns
+                .into_iter()
+                .map(|(_, _, column)| column.clone())
+                .collect();
+            let Ok(schema) = Schema::new(table_columns) else {
+                return Err(CatalogError::CorruptedRow("Duplicate column"));
+            };
+
+            tables.push(TableInfo {
+                id,
+                name: varchar(&row, 1)?.to_owned(),
+                filename: varchar(&row, 2)?.to_owned(),
+                schema,
+                engine: Engine::parse(varchar(&row, 3)?)?,
+            });
+        }
+
+        Ok(tables)
+    }
+
+    pub fn indexes(&self, table_id: RelationId) -> Result<Vec<IndexInfo>, CatalogError<'a>> {
+        Ok(self
+            .all_indexes()?
+            .into_iter()
+            .filter(|i| i.table_id == table_id)
+            .collect())
+    }
+
+    pub fn flush(&self) -> Result<(), CatalogError<'a>> {
+        self.tables.flush()?;
+        self.columns.flush()?;
+        self.indexes.flush()?;
+
+        Ok(())
+    }
+
+    fn all_indexes(&self) -> Result<Vec<IndexInfo>, CatalogError<'a>> {
+        let mut indexes = vec![];
+
+        for row in self.indexes.scan() {
+            let (_, row) = row?;
+
+            let columns = varchar(&row, 4)?
+                .split(',')
+                .map(|c| c.parse::<usize>())
+                .collect::<Result<Vec<_>, _>>()
+                .or(Err(CatalogError::CorruptedRow("Invalid index columns")))?;
+
+            indexes.push(IndexInfo {
+                id: integer(&row, 0)? as RelationId,
+                table_id: integer(&row, 1)? as RelationId,
+                name: varchar(&row, 2)?.to_owned(),
+                filename: varchar(&row, 3)?.to_owned(),
+                columns,
+                unique: integer(&row, 5)? != 0,
+            });
+        }
+
+        Ok(indexes)
+    }
+
+    fn insert_table(
+        &self,
+        id: RelationId,
+        name: &str,
+        filename: &str,
+        schema: &Schema,
+        engine: Engine,
+    ) -> Result<TableInfo, CatalogError<'a>> {
+        // Columns go first, a table without its columns is never visible.
+        for (ordinal, column) in schema.columns().iter().enumerate() {
+            insert_column(&self.columns, id, ordinal, column)?;
+        }
+
+        self.tables.insert(&Tuple {
+            schema: &TABLES_SCHEMA,
+            values: vec![
+                TupleValue::Integer(id as i32),
+                TupleValue::Varchar(name.to_owned()),
+                TupleValue::Varchar(filename.to_owned()),
+                TupleValue::Varchar(engine.name()),
+            ],
+        })?;
+
+        Ok(TableInfo {
+            id,
+            name: name.to_owned(),
+            filename: filename.to_owned(),
+            schema: schema.clone(),
+            engine,
+        })
+    }
+}
+
+fn insert_column<'a>(
+    columns: &HeapFile<'a>,
+    table_id: RelationId,
+    ordinal: usize,
+    column: &Column,
+) -> Result<(), CatalogError<'a>> {
+    columns.insert(&Tuple {
+        schema: &COLUMNS_SCHEMA,
+        values: vec![
+            TupleValue::Integer(table_id as i32),
+            TupleValue::Varchar(column.name.clone()),
+            TupleValue::Varchar(column.data_type.name()),
+            TupleValue::Integer(ordinal as i32),
+            TupleValue::Integer(column.nullable as i32),
+        ],
+    })?;
+
+    Ok(())
+}
+
+fn column_count<'a>(
+    columns: &HeapFile<'a>,
+    table_id: RelationId,
+) -> Result<usize, CatalogError<'a>> {
+    let mut count = 0;
+    for row in columns.scan() {
+        let (_, row) = row?;
+        if integer(&row, 0)? as RelationId == table_id {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Gives every table of a legacy catalog the heap engine, and registers the
+/// engine column once the rows have it.
+fn upgrade_tables<'a>(
+    tables: HeapFile<'a>,
+    columns: &HeapFile<'a>,
+) -> Result<HeapFile<'a>, CatalogError<'a>> {
+    let tables = tables.migrate(&TABLES_SCHEMA, |mut row| {
+        row.schema = &TABLES_SCHEMA;
+        row.values.push(TupleValue::Varchar(Engine::Heap.name()));
+        row
+    })?;
+
+    let ordinal = LEGACY_TABLES_SCHEMA.len();
+    insert_column(
+        columns,
+        TABLES_RELATION_ID,
+        ordinal,
+        &TABLES_SCHEMA.columns()[ordinal],
+    )?;
+
+    Ok(tables)
+}
+
+fn integer<'a>(row: &Tuple, column: usize) -> Result<i32, CatalogError<'a>> {
+    match row.values.get(column) {
+        Some(TupleValue::Integer(value)) => Ok(*value),
+        _ => Err(CatalogError::CorruptedRow("Integer column expected")),
+    }
+}
+
+fn varchar<'a, 't>(row: &'t Tuple, column: usize) -> Result<&'t str, CatalogError<'a>> {
+    match row.values.get(column) {
+        Some(TupleValue::Varchar(value)) => Ok(value),
+        _ => Err(CatalogError::CorruptedRow("Varchar column expected")),
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::page::{Page, PageId};
+
+const FILE_EXTENSION: &str = "fsm";
+// Free space of a page is stored as one byte, a category of `page_size / 256` bytes.
+const CATEGORIES: usize = 256;
+
+/// Approximate free space of every page of a heap file.
+///
+/// Categories are rounded down, so a page returned by `find_page` always has
+/// at least the requested number of bytes. A max tree over the categories
+/// answers a lookup without looking at every page.
+#[derive(Debug)]
+pub struct FreeSpaceMap {
+    path: PathBuf,
+    page_size: usize,
+    page_count: usize,
+    // Leaves start at `capacity`, node `i` has children `2 * i` and `2 * i + 1`.
+    tree: Vec<u8>,
+    capacity: usize,
+}
+
+impl FreeSpaceMap {
+    /// Opens the map stored next to the heap file `filename`, an empty map if there is none yet.
+    pub fn open(path: &str, filename: &str, page_size: usize) -> Result<FreeSpaceMap, Error> {
+        let path = Path::new(path).join(format!("{}.{}", filename, FILE_EXTENSION));
+
+        let categories = match fs::read(&path) {
+            Ok(categories) => categories,
+            Err(err) if err.kind() == ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err),
+        };
+
+        let mut map = FreeSpaceMap {
+            path,
+            page_size,
+            page_count: 0,
+            tree: vec![0; 2],
+            capacity: 1,
+        };
+
+        for (page_id, category) in categories.into_iter().enumerate() {
+            map.set_category(page_id, category);
+        }
+
+        Ok(map)
+    }
+
+    pub fn page_count(&self) -> u64 {
+        self.page_count as u64
+    }
+
+    pub fn update(&mut self, page_id: PageId, free_bytes: usize) {
+        let category = (free_bytes * CATEGORIES / self.page_size).min(CATEGORIES - 1);
+
+        self.set_category(page_id as usize, category as u8);
+    }
+
+    pub fn update_page(&mut self, page: &Page) {
+        self.update(page.id, page.free_space);
+    }
+
+    /// Lower bound of the free bytes of a page.
+    pub fn free_space(&self, page_id: PageId) -> usize {
+        if page_id as usize >= self.page_count {
+            return 0;
+        }
+
+        self.tree[self.capacity + page_id as usize] as usize * self.page_size / CATEGORIES
+    }
+
+    /// A page with at least `min_bytes` free bytes, the one with the lowest id.
+    pub fn find_page(&self, min_bytes: usize) -> Option<PageId> {
+        let category = (min_bytes * CATEGORIES).div_ceil(self.page_size);
+        if self.page_count == 0 || category >= CATEGORIES || self.tree[1] < category as u8 {
+            return None;
+        }
+
+        let mut node = 1;
+        while node < self.capacity {
+            node *= 2;
+
+            if self.tree[node] < category as u8 {
+                node += 1;
+            }
+        }
+
+        Some((node - self.capacity) as PageId)
+    }
+
+    pub fn flush(&self) -> Result<(), Error> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+
+        file.write_all(&self.tree[self.capacity..self.capacity + self.page_count])?;
+        file.sync_data()
+    }
+
+    fn set_category(&mut self, page_id: usize, category: u8) {
+        if page_id >= self.capacity {
+            self.grow(page_id + 1);
+        }
+
+        self.page_count = self.page_count.max(page_id + 1);
+
+        let mut node = self.capacity + page_id;
+        self.tree[node] = category;
+
+        while node > 1 {
+            node /= 2;
+            self.tree[node] = self.tree[2 * node].max(self.tree[2 * node + 1]);
+        }
+    }
+
+    fn grow(&mut self, min_capacity: usize) {
+        let capacity = min_capacity.next_power_of_two();
+        let mut tree = vec![0; 2 * capacity];
+
+        tree[capacity..capacity + self.page_count]
+            .copy_from_slice(&self.tree[self.capacity..self.capacity + self.page_count]);
+
+        for node in (1..capacity).rev() {
+            tree[node] = tree[2 * node].max(tree[2 * node + 1]);
+        }
+
+        self.tree = tree;
+        self.capacity = capacity;
+    }
+}
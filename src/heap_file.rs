@@ -0,0 +1,497 @@
+use std::ops::{Bound, Range};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::Mutex;
+
+use crate::{
+    bloom_filter::BloomFilters,
+    buffer_pool::buffer_pool::{AddRelationError, BufferPool, GetPageError, ReadPageGuard},
+    free_space_map::FreeSpaceMap,
+    page::{
+        HEADER_SIZE, Page, PageId, PageKey, ReadTupleError, RelationId, SLOT_SIZE, SlotId,
+        UpdateTupleError,
+    },
+    persist::{IoBackend, relation::RelationFile},
+    schema::Schema,
+    tuple::{Tuple, TupleDecodeError, TupleToDataError, TupleValue},
+    zone_map::{ZoneMap, in_bounds},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RowId {
+    pub page_id: PageId,
+    pub slot_id: SlotId,
+}
+
+impl RowId {
+    pub fn new(page_id: PageId, slot_id: SlotId) -> RowId {
+        RowId { page_id, slot_id }
+    }
+}
+
+#[derive(Debug)]
+pub enum HeapFileError<'a> {
+    Io(std::io::Error),
+    Page(GetPageError<'a>),
+    Relation(AddRelationError),
+    TupleToDataError(TupleToDataError),
+    TupleDecodeError(RowId, TupleDecodeError),
+    TupleNotFound(RowId),
+    TupleTooBig(usize),
+}
+
+impl<'a> From<GetPageError<'a>> for HeapFileError<'a> {
+    fn from(err: GetPageError<'a>) -> HeapFileError<'a> {
+        HeapFileError::Page(err)
+    }
+}
+
+impl From<AddRelationError> for HeapFileError<'_> {
+    fn from(err: AddRelationError) -> Self {
+        HeapFileError::Relation(err)
+    }
+}
+
+impl From<TupleToDataError> for HeapFileError<'_> {
+    fn from(err: TupleToDataError) -> Self {
+        HeapFileError::TupleToDataError(err)
+    }
+}
+
+impl From<std::io::Error> for HeapFileError<'_> {
+    fn from(err: std::io::Error) -> Self {
+        HeapFileError::Io(err)
+    }
+}
+
+pub type ScanRow<'a> = Result<(RowId, Tuple<'a>), HeapFileError<'a>>;
+
+type LockedRows<'a> = (Vec<ReadPageGuard<'a>>, Vec<(PageId, Tuple<'a>)>);
+
+/// Unordered set of tuples of one table stored in slotted pages.
+///
+/// Tuples are addressed by `RowId`. A row keeps its id until it is deleted,
+/// or until an update doesn't fit into its page and moves the row.
+pub struct HeapFile<'a> {
+    pool: &'a BufferPool<'a>,
+    relation_id: RelationId,
+    free_space_map: Mutex<FreeSpaceMap>,
+    next_page_id: AtomicU64,
+    schema: &'a Schema,
+    zone_map: Option<Mutex<ZoneMap>>,
+    bloom_filters: Option<Mutex<BloomFilters>>,
+}
+
+impl<'a> HeapFile<'a> {
+    /// Opens the heap file, or creates it with pages of the pool page size,
+    /// and registers it in the pool as `relation_id`.
+    pub fn open(
+        pool: &'a BufferPool<'a>,
+        relation_id: RelationId,
+        path: &str,
+        filename: &str,
+        schema: &'a Schema,
+    ) -> Result<HeapFile<'a>, HeapFileError<'a>> {
+        let relation_file = RelationFile::open(path, filename, pool.page_size(), IoBackend::Pread)?;
+        let page_count = relation_file.page_count();
+
+        let mut free_space_map = FreeSpaceMap::open(path, filename, pool.page_size())?;
+
+        // The map is flushed lazily, pages written after the last flush are
+        // read once to bring it up to date.
+        if free_space_map.page_count() != page_count {
+            let mut page = Page::with_size(0, pool.page_size());
+
+            for page_id in 0..page_count {
+                let Ok(_) = relation_file.read_page(page_id, &mut page) else {
+                    return Err(HeapFileError::Page(GetPageError::FailedToReadFromDisk));
+                };
+                page.id = page_id;
+                if page.refresh_metadata().is_err() {
+                    let key = PageKey::new(relation_id, page_id);
+                    return Err(HeapFileError::Page(GetPageError::CorruptedPage(key)));
+                }
+
+                free_space_map.update_page(&page);
+            }
+        }
+
+        pool.add_relation(relation_id, relation_file)?;
+
+        Ok(HeapFile {
+            pool,
+            relation_id,
+            free_space_map: Mutex::new(free_space_map),
+            next_page_id: AtomicU64::new(page_count),
+            schema,
+            zone_map: None,
+            bloom_filters: None,
+        })
+    }
+
+    /// Keeps a zone map of `columns`, one summary per `pages_per_range`
+    /// pages. The map is built from the pages if it doesn't cover all of them.
+    pub fn with_zone_map(
+        mut self,
+        path: &str,
+        filename: &str,
+        columns: &[usize],
+        pages_per_range: u64,
+    ) -> Result<HeapFile<'a>, HeapFileError<'a>> {
+        let zone_map = ZoneMap::open(path, filename, self.schema, columns, pages_per_range)?;
+        let stale = zone_map.page_count() != self.page_count();
+        self.zone_map = Some(Mutex::new(zone_map));
+
+        if stale {
+            self.vacuum()?;
+        }
+
+        Ok(self)
+    }
+
+    /// Keeps bloom filters of `columns` with the given false positive rate,
+    /// one per `pages_per_range` pages. They are built from the pages if they
+    /// don't cover all of them.
+    pub fn with_bloom_filters(
+        mut self,
+        path: &str,
+        filename: &str,
+        columns: &[usize],
+        pages_per_range: u64,
+        false_positive_rate: f64,
+    ) -> Result<HeapFile<'a>, HeapFileError<'a>> {
+        let bloom_filters = BloomFilters::open(
+            path,
+            filename,
+            columns,
+            pages_per_range,
+            false_positive_rate,
+        )?;
+        let stale = bloom_filters.page_count() != self.page_count();
+        self.bloom_filters = Some(Mutex::new(bloom_filters));
+
+        if stale {
+            self.vacuum()?;
+        }
+
+        Ok(self)
+    }
+
+    /// Rewrites every row with `convert` and reads rows as `schema` from then
+    /// on, for a table whose stored format changed.
+    pub fn migrate(
+        mut self,
+        schema: &'a Schema,
+        convert: impl Fn(Tuple<'a>) -> Tuple<'a>,
+    ) -> Result<HeapFile<'a>, HeapFileError<'a>> {
+        // Rows are read up front, a row moved by its update is not seen twice.
+        let rows = self.scan().collect::<Result<Vec<_>, _>>()?;
+        for (row_id, row) in rows {
+            self.update(row_id, &convert(row))?;
+        }
+        self.schema = schema;
+
+        Ok(self)
+    }
+
+    pub fn page_count(&self) -> u64 {
+        self.next_page_id.load(Ordering::Acquire)
+    }
+
+    pub fn insert(&self, tuple: &Tuple) -> Result<RowId, HeapFileError<'a>> {
+        let required_space = tuple.to_data()?.len() + SLOT_SIZE;
+        if required_space > self.pool.page_size() - HEADER_SIZE {
+            return Err(HeapFileError::TupleTooBig(required_space));
+        }
+
+        loop {
+            let page_id = self.free_space_map.lock().find_page(required_space);
+
+            let mut page = match page_id {
+                Some(page_id) => self.pool.get_mut(self.page_key(page_id))?,
+                None => self
+                    .pool
+                    .new_page(self.page_key(self.next_page_id.fetch_add(1, Ordering::AcqRel)))?,
+            };
+
+            // The map is approximate and may be outdated by concurrent inserts.
+            if !page.get().has_space(tuple)? {
+                self.free_space_map.lock().update_page(page.get());
+                continue;
+            }
+
+            // Summaries are widened first, a scan never skips a written row.
+            self.summarize_row(page.get().id, tuple)?;
+
+            let slot = page.get_mut().write(tuple)?;
+            self.pool.write_page(&page)?;
+            self.free_space_map.lock().update_page(page.get());
+
+            return Ok(RowId::new(page.get().id, slot.id));
+        }
+    }
+
+    pub fn get(&self, row_id: RowId) -> Result<Tuple<'a>, HeapFileError<'a>> {
+        if row_id.page_id >= self.page_count() {
+            return Err(HeapFileError::TupleNotFound(row_id));
+        }
+
+        let page = self.pool.get(self.page_key(row_id.page_id))?;
+
+        match page.get().read(row_id.slot_id, self.schema) {
+            Ok(tuple) => Ok(tuple),
+            Err(ReadTupleError::TupleNotFound) => Err(HeapFileError::TupleNotFound(row_id)),
+            Err(ReadTupleError::TupleDecodeError(err)) => {
+                Err(HeapFileError::TupleDecodeError(row_id, err))
+            }
+        }
+    }
+
+    /// Updates the row in place if the page has room for it. Otherwise the row
+    /// moves to another page and gets a new id, which is returned.
+    pub fn update(&self, row_id: RowId, tuple: &Tuple) -> Result<RowId, HeapFileError<'a>> {
+        if row_id.page_id >= self.page_count() {
+            return Err(HeapFileError::TupleNotFound(row_id));
+        }
+
+        {
+            let mut page = self.pool.get_mut(self.page_key(row_id.page_id))?;
+
+            self.summarize_row(row_id.page_id, tuple)?;
+
+            match page.get_mut().update(row_id.slot_id, tuple) {
+                Ok(_) => {
+                    self.pool.write_page(&page)?;
+                    self.free_space_map.lock().update_page(page.get());
+
+                    return Ok(row_id);
+                }
+                Err(UpdateTupleError::NotEnoughSpace) => {}
+                Err(UpdateTupleError::TupleNotFound) => {
+                    return Err(HeapFileError::TupleNotFound(row_id));
+                }
+                Err(UpdateTupleError::TupleToDataError(err)) => return Err(err.into()),
+            }
+        }
+
+        let new_row_id = self.insert(tuple)?;
+        self.delete(row_id)?;
+
+        Ok(new_row_id)
+    }
+
+    pub fn delete(&self, row_id: RowId) -> Result<(), HeapFileError<'a>> {
+        if row_id.page_id >= self.page_count() {
+            return Err(HeapFileError::TupleNotFound(row_id));
+        }
+
+        let mut page = self.pool.get_mut(self.page_key(row_id.page_id))?;
+
+        if page.get_mut().delete(row_id.slot_id).is_err() {
+            return Err(HeapFileError::TupleNotFound(row_id));
+        }
+        page.get_mut().compact();
+
+        self.pool.write_page(&page)?;
+        self.free_space_map.lock().update_page(page.get());
+
+        Ok(())
+    }
+
+    /// All live rows, page by page. Rows of a page are decoded before they are
+    /// returned, so no frame stays locked between iterations. A row that cannot
+    /// be decoded is returned as an error and the scan goes on.
+    pub fn scan(&self) -> impl Iterator<Item = ScanRow<'a>> {
+        self.scan_written(|_| true)
+    }
+
+    /// Live rows whose `column` is between `start` and `end`, compared as in
+    /// SQL. Pages the zone map rules out are not read.
+    pub fn scan_range<'s>(
+        &'s self,
+        column: usize,
+        start: Bound<&'s TupleValue>,
+        end: Bound<&'s TupleValue>,
+    ) -> impl Iterator<Item = ScanRow<'a>> + 's {
+        self.scan_pages(column, start, end, |_| true)
+    }
+
+    /// Live rows whose `column` equals `value`, as in SQL. Pages the zone
+    /// map or the bloom filters rule out are not read.
+    pub fn scan_equal<'s>(
+        &'s self,
+        column: usize,
+        value: &'s TupleValue,
+    ) -> impl Iterator<Item = ScanRow<'a>> + 's {
+        let bounds = (Bound::Included(value), Bound::Included(value));
+
+        self.scan_pages(column, bounds.0, bounds.1, move |page_id| {
+            match &self.bloom_filters {
+                Some(bloom_filters) => bloom_filters.lock().may_contain(page_id, column, value),
+                None => true,
+            }
+        })
+    }
+
+    /// Builds the zone map and the bloom filters again from the pages. They
+    /// only grow on writes, this drops deleted values and sizes the filters
+    /// for the rows the pages hold now.
+    pub fn vacuum(&self) -> Result<(), HeapFileError<'a>> {
+        let page_count = self.pool.page_count(self.relation_id)?;
+
+        if let Some(zone_map) = &self.zone_map {
+            let pages_per_range = zone_map.lock().pages_per_range();
+
+            for first in (0..page_count).step_by(pages_per_range as usize) {
+                let pages = first..(first + pages_per_range).min(page_count);
+                let (_guards, rows) = self.lock_range(pages.clone())?;
+
+                let mut zone_map = zone_map.lock();
+                zone_map.reset_range(first);
+                pages.for_each(|page_id| zone_map.cover(page_id));
+                for (page_id, tuple) in &rows {
+                    zone_map.update_tuple(*page_id, tuple);
+                }
+            }
+        }
+
+        if let Some(bloom_filters) = &self.bloom_filters {
+            let pages_per_range = bloom_filters.lock().pages_per_range();
+
+            for first in (0..page_count).step_by(pages_per_range as usize) {
+                let pages = first..(first + pages_per_range).min(page_count);
+                let (_guards, rows) = self.lock_range(pages.clone())?;
+
+                let mut bloom_filters = bloom_filters.lock();
+                bloom_filters.reset_range(first, rows.len());
+                pages.for_each(|page_id| bloom_filters.cover(page_id));
+                for (page_id, tuple) in &rows {
+                    bloom_filters.insert_tuple(*page_id, tuple);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&self) -> Result<(), HeapFileError<'a>> {
+        self.free_space_map.lock().flush()?;
+        if let Some(zone_map) = &self.zone_map {
+            zone_map.lock().flush()?;
+        }
+        if let Some(bloom_filters) = &self.bloom_filters {
+            bloom_filters.lock().flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Rows of the pages `may_read` accepts. The file length is read once per
+    /// scan instead of once per page.
+    fn scan_written<'s>(
+        &'s self,
+        may_read: impl Fn(PageId) -> bool + 's,
+    ) -> impl Iterator<Item = ScanRow<'a>> + 's {
+        // Pages allocated by concurrent inserts but not written yet are skipped.
+        let (pages, error) = match self.pool.page_count(self.relation_id) {
+            Ok(written) => (0..self.page_count().min(written), None),
+            Err(err) => (0..0, Some(Err(err.into()))),
+        };
+
+        error
+            .into_iter()
+            .chain(
+                pages
+                    .filter(move |&page_id| may_read(page_id))
+                    .flat_map(move |page_id| match self.read_rows(page_id) {
+                        Ok(rows) => rows,
+                        Err(err) => vec![Err(err)],
+                    }),
+            )
+    }
+
+    fn read_rows(&self, page_id: PageId) -> Result<Vec<ScanRow<'a>>, HeapFileError<'a>> {
+        let page = self.pool.get(self.page_key(page_id))?;
+
+        Ok(page
+            .get()
+            .read_iterator_raw_with_slots()
+            .map(|(slot_id, data)| {
+                let row_id = RowId::new(page_id, slot_id);
+
+                match Tuple::read(self.schema, data) {
+                    Ok(tuple) => Ok((row_id, tuple)),
+                    Err(err) => Err(HeapFileError::TupleDecodeError(row_id, err)),
+                }
+            })
+            .collect())
+    }
+
+    fn scan_pages<'s>(
+        &'s self,
+        column: usize,
+        start: Bound<&'s TupleValue>,
+        end: Bound<&'s TupleValue>,
+        may_match: impl Fn(PageId) -> bool + 's,
+    ) -> impl Iterator<Item = ScanRow<'a>> + 's {
+        self.scan_written(move |page_id| {
+            let in_zone = match &self.zone_map {
+                Some(zone_map) => zone_map.lock().may_match(page_id, column, start, end),
+                None => true,
+            };
+
+            in_zone && may_match(page_id)
+        })
+        .filter(move |row| match row {
+            Ok((_, tuple)) => in_bounds(&tuple.values[column], start, end),
+            Err(_) => true,
+        })
+    }
+
+    fn summarize_row(&self, page_id: PageId, tuple: &Tuple) -> Result<(), HeapFileError<'a>> {
+        if let Some(zone_map) = &self.zone_map {
+            let mut zone_map = zone_map.lock();
+            zone_map.invalidate()?;
+            zone_map.update_tuple(page_id, tuple);
+        }
+        if let Some(bloom_filters) = &self.bloom_filters {
+            let mut bloom_filters = bloom_filters.lock();
+            bloom_filters.invalidate()?;
+            bloom_filters.insert_tuple(page_id, tuple);
+        }
+
+        Ok(())
+    }
+
+    // Rows of the pages, which stay read locked until the guards are dropped.
+    // Writers summarize a row while they hold its page, so summaries built
+    // from the rows miss no row written meanwhile.
+    fn lock_range(&self, pages: Range<PageId>) -> Result<LockedRows<'a>, HeapFileError<'a>> {
+        let mut guards = Vec::with_capacity(pages.clone().count());
+        let mut rows = vec![];
+
+        for page_id in pages {
+            let page = self.pool.get(self.page_key(page_id))?;
+
+            for (slot_id, data) in page.get().read_iterator_raw_with_slots() {
+                match Tuple::read(self.schema, data) {
+                    Ok(tuple) => rows.push((page_id, tuple)),
+                    Err(err) => {
+                        return Err(HeapFileError::TupleDecodeError(
+                            RowId::new(page_id, slot_id),
+                            err,
+                        ));
+                    }
+                }
+            }
+
+            guards.push(page);
+        }
+
+        Ok((guards, rows))
+    }
+
+    fn page_key(&self, page_id: PageId) -> PageKey {
+        PageKey::new(self.relation_id, page_id)
+    }
+}
@@ -0,0 +1,792 @@
+use std::ops::Bound;
+
+use crate::{
+    buffer_pool::buffer_pool::{
+        AddRelationError, BufferPool, GetPageError, ReadPageGuard, WritePageGuard,
+    },
+    heap_file::RowId,
+    page::{Page, PageId, PageKey, RelationId, SlotId},
+    persist::{IoBackend, relation::RelationFile},
+};
+
+use super::{KIND_OFFSET, PageAllocator};
+
+// Nodes keep the header of an empty slotted page, so the pool sees them as
+// pages without tuples. The node header follows it: kind, number of entries
+// and a link, the next leaf of a leaf or the leftmost child of an internal node.
+const COUNT_OFFSET: usize = KIND_OFFSET + 1;
+const LINK_OFFSET: usize = COUNT_OFFSET + 2;
+const NODE_HEADER_SIZE: usize = LINK_OFFSET + 8;
+
+const LEAF: u8 = 1;
+const INTERNAL: u8 = 2;
+const META: u8 = 3;
+
+// The meta page stores the root id in place of the link. It is never a
+// sibling, so its id also marks the last leaf.
+const META_PAGE_ID: PageId = 0;
+const NO_PAGE: PageId = 0;
+
+const ROW_ID_SIZE: usize = 10;
+const CHILD_SIZE: usize = 8;
+const KEY_LENGTH_SIZE: usize = 2;
+
+// Nodes of a built tree are filled to 9/10 of a page, so the first inserts don't split them.
+const BUILD_FILL_NUMERATOR: usize = 9;
+const BUILD_FILL_DENOMINATOR: usize = 10;
+
+type Entry = (Vec<u8>, RowId);
+
+#[derive(Debug)]
+pub enum BTreeError<'a> {
+    Io(std::io::Error),
+    Page(GetPageError<'a>),
+    Relation(AddRelationError),
+    KeyTooLong(usize),
+    DuplicateEntry,
+    NotEmpty,
+    Corrupted(PageId),
+}
+
+impl<'a> From<GetPageError<'a>> for BTreeError<'a> {
+    fn from(err: GetPageError<'a>) -> BTreeError<'a> {
+        BTreeError::Page(err)
+    }
+}
+
+impl From<AddRelationError> for BTreeError<'_> {
+    fn from(err: AddRelationError) -> Self {
+        BTreeError::Relation(err)
+    }
+}
+
+impl From<std::io::Error> for BTreeError<'_> {
+    fn from(err: std::io::Error) -> Self {
+        BTreeError::Io(err)
+    }
+}
+
+/// B+tree of byte keys stored in pages of the buffer pool, e.g. keys of
+/// `tuple::encode_key`. Every entry points to a row of a heap file, a key
+/// may point to many rows. Entries are ordered by key, then by row id.
+///
+/// Writers lock nodes from the root down and release the locked ancestors as
+/// soon as a node is safe, i.e. cannot split on insert or underflow on delete.
+/// Siblings are always locked from left to right, the order of range scans.
+/// An underfull node is merged with a sibling when both fit into one page,
+/// entries are not redistributed.
+///
+/// Pages of merged nodes are reused by later splits. Free pages are found by
+/// reading every page on open.
+pub struct BTree<'a> {
+    pool: &'a BufferPool<'a>,
+    relation_id: RelationId,
+    pages: PageAllocator,
+}
+
+impl<'a> BTree<'a> {
+    /// Opens the tree, or creates an empty one, and registers it in the pool as `relation_id`.
+    pub fn open(
+        pool: &'a BufferPool<'a>,
+        relation_id: RelationId,
+        path: &str,
+        filename: &str,
+    ) -> Result<BTree<'a>, BTreeError<'a>> {
+        let relation_file = RelationFile::open(path, filename, pool.page_size(), IoBackend::Pread)?;
+        let page_count = relation_file.page_count();
+
+        let pages = PageAllocator::open(&relation_file, pool.page_size(), 2)?;
+        pool.add_relation(relation_id, relation_file)?;
+
+        let tree = BTree {
+            pool,
+            relation_id,
+            pages,
+        };
+
+        if page_count == 0 {
+            let mut meta = pool.new_page(tree.page_key(META_PAGE_ID))?;
+            write_root(meta.get_mut(), 1);
+            pool.write_page(&meta)?;
+
+            let mut root = pool.new_page(tree.page_key(1))?;
+            tree.write_node(&mut root, &Node::leaf(vec![]))?;
+        }
+
+        Ok(tree)
+    }
+
+    pub fn relation_id(&self) -> RelationId {
+        self.relation_id
+    }
+
+    pub fn page_count(&self) -> u64 {
+        self.pages.page_count()
+    }
+
+    /// Longest key that fits, a node holds at least four entries.
+    pub fn max_key_length(&self) -> usize {
+        self.max_entry_size() - KEY_LENGTH_SIZE - ROW_ID_SIZE - CHILD_SIZE
+    }
+
+    pub fn insert(&self, key: &[u8], row_id: RowId) -> Result<(), BTreeError<'a>> {
+        if key.len() > self.max_key_length() {
+            return Err(BTreeError::KeyTooLong(key.len()));
+        }
+
+        let entry = (key.to_vec(), row_id);
+        let (mut meta, mut path) = self.lock_path(&entry, |node, _| {
+            node.size() + self.max_entry_size() <= self.pool.page_size()
+        })?;
+
+        let LockedNode {
+            mut guard,
+            mut node,
+            mut index,
+        } = path.pop().unwrap();
+
+        let Err(position) = node.entries.binary_search(&entry) else {
+            return Err(BTreeError::DuplicateEntry);
+        };
+        node.entries.insert(position, entry);
+
+        loop {
+            if node.size() <= self.pool.page_size() {
+                return self.write_node(&mut guard, &node);
+            }
+
+            let (separator, mut right) = node.split();
+            let right_id = self.pages.allocate();
+            if node.is_leaf {
+                right.link = node.link;
+                node.link = right_id;
+            }
+
+            let mut right_guard = self.pool.new_page(self.page_key(right_id))?;
+            self.write_node(&mut right_guard, &right)?;
+            self.write_node(&mut guard, &node)?;
+
+            let Some(parent) = path.pop() else {
+                let mut meta = meta
+                    .take()
+                    .expect("The meta page is locked while the root may split");
+
+                let root_id = self.pages.allocate();
+                let root = Node::internal(vec![separator], vec![guard.get().id, right_id]);
+
+                let mut root_guard = self.pool.new_page(self.page_key(root_id))?;
+                self.write_node(&mut root_guard, &root)?;
+
+                write_root(meta.get_mut(), root_id);
+                self.pool.write_page(&meta)?;
+
+                return Ok(());
+            };
+
+            guard = parent.guard;
+            node = parent.node;
+            node.entries.insert(index, separator);
+            node.children.insert(index + 1, right_id);
+            index = parent.index;
+        }
+    }
+
+    /// Removes the entry, returns whether it existed.
+    pub fn delete(&self, key: &[u8], row_id: RowId) -> Result<bool, BTreeError<'a>> {
+        let entry = (key.to_vec(), row_id);
+        let (meta, mut path) = self.lock_path(&entry, |node, is_root| {
+            if is_root {
+                node.is_leaf || node.entries.len() > 1
+            } else {
+                node.entries_size() >= self.min_entries_size() + self.max_entry_size()
+            }
+        })?;
+
+        let mut leaf = path.pop().unwrap();
+
+        let Ok(position) = leaf.node.entries.binary_search(&entry) else {
+            return Ok(false);
+        };
+        leaf.node.entries.remove(position);
+
+        self.merge_underfull(meta, path, leaf)?;
+
+        Ok(true)
+    }
+
+    // Writes the changed node and merges it with a sibling when it is
+    // underfull, up to the first ancestor that stays full enough.
+    fn merge_underfull(
+        &self,
+        mut meta: Option<WritePageGuard<'a>>,
+        mut path: Vec<LockedNode<'a>>,
+        changed: LockedNode<'a>,
+    ) -> Result<(), BTreeError<'a>> {
+        let LockedNode {
+            mut guard,
+            mut node,
+            mut index,
+        } = changed;
+
+        loop {
+            let Some(parent) = path.last_mut() else {
+                // The root is locked with the meta page only when it may lose its last separator.
+                if let Some(mut meta) = meta
+                    .take()
+                    .filter(|_| node.entries.is_empty() && !node.is_leaf)
+                {
+                    write_root(meta.get_mut(), node.children[0]);
+                    self.pool.write_page(&meta)?;
+
+                    self.pages.free(self.pool, &mut guard)?;
+
+                    return Ok(());
+                }
+
+                return self.write_node(&mut guard, &node);
+            };
+
+            self.write_node(&mut guard, &node)?;
+            if node.entries_size() >= self.min_entries_size() {
+                return Ok(());
+            }
+
+            // Siblings are locked from left to right, so the node is released
+            // and locked again with its sibling. Writers cannot reach it
+            // meanwhile, its parent stays locked.
+            let left_index = if index + 1 < parent.node.children.len() {
+                index
+            } else if index > 0 {
+                index - 1
+            } else {
+                return Ok(());
+            };
+            drop(guard);
+
+            let mut left_guard = self
+                .pool
+                .get_mut(self.page_key(parent.node.children[left_index]))?;
+            let mut left = Node::read(left_guard.get())?;
+            let mut right_guard = self
+                .pool
+                .get_mut(self.page_key(parent.node.children[left_index + 1]))?;
+            let right = Node::read(right_guard.get())?;
+
+            let separator = &parent.node.entries[left_index];
+            let merged_size = left.size() + right.entries_size() + separator_size(&left, separator);
+            if merged_size > self.pool.page_size() {
+                return Ok(());
+            }
+
+            left.merge(separator.clone(), right);
+            self.write_node(&mut left_guard, &left)?;
+            self.pages.free(self.pool, &mut right_guard)?;
+
+            drop(left_guard);
+            drop(right_guard);
+
+            let parent = path.pop().unwrap();
+            guard = parent.guard;
+            node = parent.node;
+            node.entries.remove(left_index);
+            node.children.remove(left_index + 1);
+            index = parent.index;
+        }
+    }
+
+    /// Fills an empty tree with the entries bottom up: leaves are written from
+    /// left to right, then each level of internal nodes above them. It is much
+    /// faster than inserting entries one by one. Readers and writers wait
+    /// until the tree is built.
+    pub fn build(&self, mut entries: Vec<Entry>) -> Result<(), BTreeError<'a>> {
+        if let Some((key, _)) = entries
+            .iter()
+            .find(|(key, _)| key.len() > self.max_key_length())
+        {
+            return Err(BTreeError::KeyTooLong(key.len()));
+        }
+
+        entries.sort_unstable();
+        if entries.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(BTreeError::DuplicateEntry);
+        }
+
+        let mut meta = self.pool.get_mut(self.page_key(META_PAGE_ID))?;
+        let root_id = read_root(meta.get())?;
+        let mut root = self.pool.get_mut(self.page_key(root_id))?;
+
+        let node = Node::read(root.get())?;
+        if !node.is_leaf || !node.entries.is_empty() {
+            return Err(BTreeError::NotEmpty);
+        }
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        // The empty root becomes the first leaf.
+        let leaves = self.pack(entries, |entry| {
+            KEY_LENGTH_SIZE + entry.0.len() + ROW_ID_SIZE
+        });
+        let leaf_ids = std::iter::once(root_id)
+            .chain((1..leaves.len()).map(|_| self.pages.allocate()))
+            .collect::<Vec<_>>();
+
+        // First entries of the nodes of a level with their pages, the children of the level above.
+        let mut level = Vec::with_capacity(leaves.len());
+        for (i, entries) in leaves.into_iter().enumerate() {
+            level.push((entries[0].clone(), leaf_ids[i]));
+
+            let mut leaf = Node::leaf(entries);
+            leaf.link = leaf_ids.get(i + 1).copied().unwrap_or(NO_PAGE);
+
+            if i == 0 {
+                self.write_node(&mut root, &leaf)?;
+            } else {
+                let mut guard = self.pool.new_page(self.page_key(leaf_ids[i]))?;
+                self.write_node(&mut guard, &leaf)?;
+            }
+        }
+        drop(root);
+
+        while level.len() > 1 {
+            let nodes = self.pack(level, |(entry, _)| {
+                KEY_LENGTH_SIZE + entry.0.len() + ROW_ID_SIZE + CHILD_SIZE
+            });
+
+            level = Vec::with_capacity(nodes.len());
+            for children in nodes {
+                let first = children[0].0.clone();
+                let (mut entries, children): (Vec<_>, Vec<_>) = children.into_iter().unzip();
+                // The first entry of the leftmost subtree is the separator in the level above.
+                entries.remove(0);
+
+                let page_id = self.pages.allocate();
+                let mut guard = self.pool.new_page(self.page_key(page_id))?;
+                self.write_node(&mut guard, &Node::internal(entries, children))?;
+
+                level.push((first, page_id));
+            }
+        }
+
+        write_root(meta.get_mut(), level[0].1);
+        self.pool.write_page(&meta)?;
+
+        Ok(())
+    }
+
+    /// Rows of all entries with the key.
+    pub fn get(&self, key: &[u8]) -> Result<Vec<RowId>, BTreeError<'a>> {
+        self.range(Bound::Included(key), Bound::Included(key))?
+            .map(|entry| entry.map(|(_, row_id)| row_id))
+            .collect()
+    }
+
+    /// Entries with keys between the bounds in key order. The iterator keeps
+    /// the current leaf locked for reading, so the tree must not be modified
+    /// by the thread that iterates.
+    pub fn range(
+        &self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> Result<BTreeRange<'a>, BTreeError<'a>> {
+        let is_before_start = |entry: &Entry| match start {
+            Bound::Included(start) => entry.0.as_slice() < start,
+            Bound::Excluded(start) => entry.0.as_slice() <= start,
+            Bound::Unbounded => false,
+        };
+
+        let meta = self.pool.get(self.page_key(META_PAGE_ID))?;
+        let mut page_id = read_root(meta.get())?;
+        let mut parent = meta;
+
+        loop {
+            let guard = self.pool.get(self.page_key(page_id))?;
+            drop(parent);
+
+            let node = Node::read(guard.get())?;
+            let position = node.entries.partition_point(is_before_start);
+
+            if node.is_leaf {
+                return Ok(BTreeRange {
+                    pool: self.pool,
+                    relation_id: self.relation_id,
+                    leaf: Some(guard),
+                    node,
+                    position,
+                    end: end.map(<[u8]>::to_vec),
+                });
+            }
+
+            page_id = node.children[position];
+            parent = guard;
+        }
+    }
+
+    // Locks the nodes on the way to the leaf of the entry, the meta page and
+    // the nodes above the last safe one are released.
+    fn lock_path(
+        &self,
+        entry: &Entry,
+        is_safe: impl Fn(&Node, bool) -> bool,
+    ) -> Result<(Option<WritePageGuard<'a>>, Vec<LockedNode<'a>>), BTreeError<'a>> {
+        let meta = self.pool.get_mut(self.page_key(META_PAGE_ID))?;
+        let root_id = read_root(meta.get())?;
+
+        let mut meta = Some(meta);
+        let mut path: Vec<LockedNode> = vec![];
+        let (mut page_id, mut index) = (root_id, 0);
+
+        loop {
+            let guard = self.pool.get_mut(self.page_key(page_id))?;
+            let node = Node::read(guard.get())?;
+
+            if is_safe(&node, page_id == root_id) {
+                meta = None;
+                path.clear();
+            }
+
+            let child = (!node.is_leaf).then(|| node.entries.partition_point(|e| e <= entry));
+            path.push(LockedNode { guard, node, index });
+
+            let Some(child) = child else {
+                return Ok((meta, path));
+            };
+            page_id = path.last().unwrap().node.children[child];
+            index = child;
+        }
+    }
+
+    fn write_node(
+        &self,
+        guard: &mut WritePageGuard<'a>,
+        node: &Node,
+    ) -> Result<(), BTreeError<'a>> {
+        node.write(guard.get_mut());
+        self.pool.write_page(guard)?;
+
+        Ok(())
+    }
+
+    // Splits items into nodes filled up to the build fill. The last node may
+    // be underfull, then it takes half of the items of the one before it.
+    fn pack<T>(&self, items: Vec<T>, size: impl Fn(&T) -> usize) -> Vec<Vec<T>> {
+        let capacity = (self.pool.page_size() - NODE_HEADER_SIZE) * BUILD_FILL_NUMERATOR
+            / BUILD_FILL_DENOMINATOR;
+
+        let mut nodes = vec![];
+        let (mut node, mut node_size) = (vec![], 0);
+        for item in items {
+            if node_size + size(&item) > capacity {
+                nodes.push(std::mem::take(&mut node));
+                node_size = 0;
+            }
+
+            node_size += size(&item);
+            node.push(item);
+        }
+
+        if node_size < self.min_entries_size() && !nodes.is_empty() {
+            let mut items = nodes.pop().unwrap();
+            items.append(&mut node);
+
+            let half = items.iter().map(&size).sum::<usize>() / 2;
+            let mut left_size = 0;
+            let middle = items
+                .iter()
+                .position(|item| {
+                    left_size += size(item);
+                    left_size > half
+                })
+                .unwrap_or(items.len() - 1)
+                .max(1);
+
+            node = items.split_off(middle);
+            nodes.push(items);
+        }
+        nodes.push(node);
+
+        nodes
+    }
+
+    fn max_entry_size(&self) -> usize {
+        (self.pool.page_size() - NODE_HEADER_SIZE) / 4
+    }
+
+    // Nodes below this size are merged with a sibling.
+    fn min_entries_size(&self) -> usize {
+        (self.pool.page_size() - NODE_HEADER_SIZE) / 4
+    }
+
+    fn page_key(&self, page_id: PageId) -> PageKey {
+        PageKey::new(self.relation_id, page_id)
+    }
+}
+
+/// Entries of a range scan. A leaf is locked before the previous one is
+/// released, so a concurrent merge cannot free the next leaf under the scan.
+pub struct BTreeRange<'a> {
+    pool: &'a BufferPool<'a>,
+    relation_id: RelationId,
+    leaf: Option<ReadPageGuard<'a>>,
+    node: Node,
+    position: usize,
+    end: Bound<Vec<u8>>,
+}
+
+impl<'a> Iterator for BTreeRange<'a> {
+    type Item = Result<(Vec<u8>, RowId), BTreeError<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.leaf.as_ref()?;
+
+        while self.position == self.node.entries.len() {
+            if self.node.link == NO_PAGE {
+                self.leaf = None;
+                return None;
+            }
+
+            let next = match self
+                .pool
+                .get(PageKey::new(self.relation_id, self.node.link))
+            {
+                Ok(next) => next,
+                Err(err) => {
+                    self.leaf = None;
+                    return Some(Err(err.into()));
+                }
+            };
+
+            match Node::read(next.get()) {
+                Ok(node) => self.node = node,
+                Err(err) => {
+                    self.leaf = None;
+                    return Some(Err(err));
+                }
+            }
+            self.leaf = Some(next);
+            self.position = 0;
+        }
+
+        let (key, row_id) = &self.node.entries[self.position];
+        let in_range = match &self.end {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+
+        if !in_range {
+            self.leaf = None;
+            return None;
+        }
+
+        self.position += 1;
+
+        Some(Ok((key.clone(), *row_id)))
+    }
+}
+
+struct LockedNode<'a> {
+    guard: WritePageGuard<'a>,
+    node: Node,
+    // Position of the node among the children of its parent.
+    index: usize,
+}
+
+// Decoded node, it is encoded back into its page after a change.
+#[derive(Debug)]
+struct Node {
+    is_leaf: bool,
+    // Separators of an internal node are the first entries of the subtrees
+    // on their right.
+    entries: Vec<Entry>,
+    // One more child than entries, empty for a leaf.
+    children: Vec<PageId>,
+    // Next leaf of a leaf.
+    link: PageId,
+}
+
+impl Node {
+    fn leaf(entries: Vec<Entry>) -> Node {
+        Node {
+            is_leaf: true,
+            entries,
+            children: vec![],
+            link: NO_PAGE,
+        }
+    }
+
+    fn internal(entries: Vec<Entry>, children: Vec<PageId>) -> Node {
+        Node {
+            is_leaf: false,
+            entries,
+            children,
+            link: NO_PAGE,
+        }
+    }
+
+    fn read<'a>(page: &Page) -> Result<Node, BTreeError<'a>> {
+        let corrupted = || BTreeError::Corrupted(page.id);
+        let data = &page.data;
+
+        let is_leaf = match data[KIND_OFFSET] {
+            LEAF => true,
+            INTERNAL => false,
+            _ => return Err(corrupted()),
+        };
+        let count = u16::from_be_bytes([data[COUNT_OFFSET], data[COUNT_OFFSET + 1]]) as usize;
+        let link = read_u64(data, LINK_OFFSET);
+
+        let mut node = if is_leaf {
+            Node::leaf(Vec::with_capacity(count))
+        } else {
+            Node::internal(Vec::with_capacity(count), vec![link])
+        };
+        if is_leaf {
+            node.link = link;
+        }
+
+        let mut offset = NODE_HEADER_SIZE;
+        for _ in 0..count {
+            let key_start = offset + KEY_LENGTH_SIZE;
+            let Some(length) = data.get(offset..key_start) else {
+                return Err(corrupted());
+            };
+
+            let key_end = key_start + u16::from_be_bytes([length[0], length[1]]) as usize;
+            let end = key_end + ROW_ID_SIZE + if is_leaf { 0 } else { CHILD_SIZE };
+            if end > data.len() {
+                return Err(corrupted());
+            }
+
+            let row_id = RowId::new(
+                read_u64(data, key_end),
+                SlotId::from_be_bytes([data[key_end + 8], data[key_end + 9]]),
+            );
+            node.entries
+                .push((data[key_start..key_end].to_vec(), row_id));
+
+            if !is_leaf {
+                node.children.push(read_u64(data, key_end + ROW_ID_SIZE));
+            }
+
+            offset = end;
+        }
+
+        Ok(node)
+    }
+
+    fn write(&self, page: &mut Page) {
+        let data = &mut page.data;
+
+        data[KIND_OFFSET] = if self.is_leaf { LEAF } else { INTERNAL };
+        data[COUNT_OFFSET..LINK_OFFSET].copy_from_slice(&(self.entries.len() as u16).to_be_bytes());
+        let link = if self.is_leaf {
+            self.link
+        } else {
+            self.children[0]
+        };
+        data[LINK_OFFSET..NODE_HEADER_SIZE].copy_from_slice(&link.to_be_bytes());
+
+        let mut offset = NODE_HEADER_SIZE;
+        for (i, (key, row_id)) in self.entries.iter().enumerate() {
+            let mut write = |bytes: &[u8]| {
+                data[offset..offset + bytes.len()].copy_from_slice(bytes);
+                offset += bytes.len();
+            };
+
+            write(&(key.len() as u16).to_be_bytes());
+            write(key);
+            write(&row_id.page_id.to_be_bytes());
+            write(&row_id.slot_id.to_be_bytes());
+            if !self.is_leaf {
+                write(&self.children[i + 1].to_be_bytes());
+            }
+        }
+
+        data[offset..].fill(0);
+    }
+
+    fn size(&self) -> usize {
+        NODE_HEADER_SIZE + self.entries_size()
+    }
+
+    fn entries_size(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|entry| self.entry_size(entry))
+            .sum()
+    }
+
+    fn entry_size(&self, entry: &Entry) -> usize {
+        let child_size = if self.is_leaf { 0 } else { CHILD_SIZE };
+
+        KEY_LENGTH_SIZE + entry.0.len() + ROW_ID_SIZE + child_size
+    }
+
+    // Moves the upper half of the entries by size to a new right node and
+    // returns the separator of the two nodes.
+    fn split(&mut self) -> (Entry, Node) {
+        let half = self.entries_size() / 2;
+
+        let mut size = 0;
+        let middle = self
+            .entries
+            .iter()
+            .position(|entry| {
+                size += self.entry_size(entry);
+                size >= half
+            })
+            .unwrap()
+            .clamp(1, self.entries.len() - 1);
+
+        let mut entries = self.entries.split_off(middle);
+
+        if self.is_leaf {
+            (entries[0].clone(), Node::leaf(entries))
+        } else {
+            // The separator moves up, its child becomes the leftmost child of the right node.
+            let separator = entries.remove(0);
+            let children = self.children.split_off(middle + 1);
+
+            (separator, Node::internal(entries, children))
+        }
+    }
+
+    // Appends the right sibling, the separator comes down from the parent of internal nodes.
+    fn merge(&mut self, separator: Entry, right: Node) {
+        if self.is_leaf {
+            self.link = right.link;
+        } else {
+            self.entries.push(separator);
+            self.children.extend(right.children);
+        }
+
+        self.entries.extend(right.entries);
+    }
+}
+
+// Size the separator takes when it comes down into `left` on merge.
+fn separator_size(left: &Node, separator: &Entry) -> usize {
+    if left.is_leaf {
+        0
+    } else {
+        left.entry_size(separator)
+    }
+}
+
+fn read_root<'a>(meta: &Page) -> Result<PageId, BTreeError<'a>> {
+    if meta.data[KIND_OFFSET] != META {
+        return Err(BTreeError::Corrupted(meta.id));
+    }
+
+    Ok(read_u64(&meta.data, LINK_OFFSET))
+}
+
+fn write_root(meta: &mut Page, root: PageId) {
+    meta.data[KIND_OFFSET] = META;
+    meta.data[LINK_OFFSET..NODE_HEADER_SIZE].copy_from_slice(&root.to_be_bytes());
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(data[offset..offset + 8].try_into().unwrap())
+}
@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use crate::{
+    buffer_pool::buffer_pool::BufferPool,
+    heap_file::RowId,
+    index::btree::{BTree, BTreeError},
+    page::RelationId,
+};
+
+// Longer terms are cut, a key must fit into a tree node.
+const MAX_TERM_LENGTH: usize = 64;
+// Terms are made of alphanumeric characters only, so it never ends a term.
+const SEPARATOR: u8 = 0;
+
+/// Lowercase alphanumeric words of the text with their positions, e.g.
+/// "John.Doe@mail.com" is "john", "doe", "mail" and "com".
+pub fn tokenize(text: &str) -> impl Iterator<Item = (u32, String)> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .enumerate()
+        .map(|(position, word)| {
+            let mut term = word.to_lowercase();
+            if term.len() > MAX_TERM_LENGTH {
+                let end = (0..=MAX_TERM_LENGTH)
+                    .rev()
+                    .find(|&end| term.is_char_boundary(end))
+                    .unwrap();
+                term.truncate(end);
+            }
+
+            (position as u32, term)
+        })
+}
+
+/// Full-text query of terms as `tokenize` makes them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Term(String),
+    /// Terms next to each other, in order.
+    Phrase(Vec<String>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+impl Query {
+    /// Parses words that all must match, `OR` between groups of them and
+    /// `"quoted words"` that match as a phrase, e.g. `ann "mail com" OR bob`.
+    /// A word of many terms, like an email, is a phrase too.
+    pub fn parse(text: &str) -> Query {
+        let mut groups = vec![];
+        let mut group = vec![];
+
+        for (i, part) in text.split('"').enumerate() {
+            // Odd parts are inside quotes.
+            if i % 2 == 1 {
+                group.push(Query::phrase(part));
+                continue;
+            }
+
+            for word in part.split_whitespace() {
+                if word == "OR" {
+                    groups.push(Query::And(std::mem::take(&mut group)));
+                } else {
+                    group.push(Query::phrase(word));
+                }
+            }
+        }
+        groups.push(Query::And(group));
+
+        Query::Or(groups).simplify()
+    }
+
+    fn phrase(text: &str) -> Query {
+        let mut terms = tokenize(text).map(|(_, term)| term).collect::<Vec<_>>();
+
+        match terms.len() {
+            0 => Query::And(vec![]),
+            1 => Query::Term(terms.remove(0)),
+            _ => Query::Phrase(terms),
+        }
+    }
+
+    // Drops empty groups and groups of one query.
+    fn simplify(self) -> Query {
+        let (queries, is_and) = match self {
+            Query::And(queries) => (queries, true),
+            Query::Or(queries) => (queries, false),
+            query => return query,
+        };
+
+        let mut queries = queries
+            .into_iter()
+            .map(Query::simplify)
+            .filter(|query| *query != Query::And(vec![]))
+            .collect::<Vec<_>>();
+
+        match (queries.len(), is_and) {
+            (1, _) => queries.remove(0),
+            (_, true) => Query::And(queries),
+            (_, false) if queries.is_empty() => Query::And(queries),
+            (_, false) => Query::Or(queries),
+        }
+    }
+}
+
+/// Inverted index of the text of a column: every term points to the rows
+/// that have it, with its positions in them for phrase queries.
+///
+/// Posting lists are entries of a B+tree on buffer pool pages, keyed by the
+/// term and a position. The index is updated with the text of a row when the
+/// row is inserted or deleted, an update is a delete of the old text and an
+/// insert of the new one.
+pub struct FullTextIndex<'a> {
+    tree: BTree<'a>,
+}
+
+impl<'a> FullTextIndex<'a> {
+    pub fn open(
+        pool: &'a BufferPool<'a>,
+        relation_id: RelationId,
+        path: &str,
+        filename: &str,
+    ) -> Result<FullTextIndex<'a>, BTreeError<'a>> {
+        Ok(FullTextIndex {
+            tree: BTree::open(pool, relation_id, path, filename)?,
+        })
+    }
+
+    pub fn relation_id(&self) -> RelationId {
+        self.tree.relation_id()
+    }
+
+    pub fn insert(&self, row_id: RowId, text: &str) -> Result<(), BTreeError<'a>> {
+        for (position, term) in tokenize(text) {
+            match self.tree.insert(&posting_key(&term, position), row_id) {
+                Ok(()) | Err(BTreeError::DuplicateEntry) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the terms of `text`, the text the row was inserted with.
+    pub fn delete(&self, row_id: RowId, text: &str) -> Result<(), BTreeError<'a>> {
+        for (position, term) in tokenize(text) {
+            self.tree.delete(&posting_key(&term, position), row_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Rows that match the query, in row id order.
+    pub fn search(&self, query: &Query) -> Result<Vec<RowId>, BTreeError<'a>> {
+        match query {
+            Query::Term(term) => Ok(self.postings(term)?.into_keys().collect()),
+            Query::Phrase(terms) => self.search_phrase(terms),
+            Query::And(queries) => {
+                let mut queries = queries.iter();
+                let Some(first) = queries.next() else {
+                    return Ok(vec![]);
+                };
+
+                let mut rows = self.search(first)?;
+                for query in queries {
+                    if rows.is_empty() {
+                        break;
+                    }
+
+                    let other = self.search(query)?;
+                    rows.retain(|row_id| other.binary_search(row_id).is_ok());
+                }
+
+                Ok(rows)
+            }
+            Query::Or(queries) => {
+                let mut rows = vec![];
+                for query in queries {
+                    rows.extend(self.search(query)?);
+                }
+                rows.sort_unstable();
+                rows.dedup();
+
+                Ok(rows)
+            }
+        }
+    }
+
+    fn search_phrase(&self, terms: &[String]) -> Result<Vec<RowId>, BTreeError<'a>> {
+        let Some((first, rest)) = terms.split_first() else {
+            return Ok(vec![]);
+        };
+
+        // Positions the phrase may start at in each row.
+        let mut starts = self.postings(first)?;
+        for (offset, term) in rest.iter().enumerate() {
+            if starts.is_empty() {
+                break;
+            }
+
+            let postings = self.postings(term)?;
+            starts.retain(|row_id, positions| {
+                let Some(next) = postings.get(row_id) else {
+                    return false;
+                };
+                positions.retain(|position| next.contains(&(position + offset as u32 + 1)));
+
+                !positions.is_empty()
+            });
+        }
+
+        Ok(starts.into_keys().collect())
+    }
+
+    // Rows of the term with its positions in them.
+    fn postings(&self, term: &str) -> Result<BTreeMap<RowId, Vec<u32>>, BTreeError<'a>> {
+        let mut start = term.as_bytes().to_vec();
+        start.push(SEPARATOR);
+        let mut end = term.as_bytes().to_vec();
+        end.push(SEPARATOR + 1);
+
+        let mut postings = BTreeMap::<RowId, Vec<u32>>::new();
+        for entry in self
+            .tree
+            .range(Bound::Included(&start), Bound::Excluded(&end))?
+        {
+            let (key, row_id) = entry?;
+
+            let position = u32::from_be_bytes(key[key.len() - 4..].try_into().unwrap());
+            postings.entry(row_id).or_default().push(position);
+        }
+
+        Ok(postings)
+    }
+}
+
+fn posting_key(term: &str, position: u32) -> Vec<u8> {
+    let mut key = Vec::with_capacity(term.len() + 5);
+    key.extend_from_slice(term.as_bytes());
+    key.push(SEPARATOR);
+    key.extend_from_slice(&position.to_be_bytes());
+    key
+}
@@ -0,0 +1,673 @@
+use twox_hash::XxHash3_64;
+
+use crate::{
+    buffer_pool::buffer_pool::{AddRelationError, BufferPool, GetPageError, WritePageGuard},
+    heap_file::RowId,
+    page::{Page, PageId, PageKey, RelationId, SlotId},
+    persist::{IoBackend, relation::RelationFile},
+};
+
+use super::{KIND_OFFSET, PageAllocator};
+
+// Pages keep the header of an empty slotted page. The header of the index
+// follows it: kind, depth, count and link.
+//
+// - meta page: global depth and the number of directory pages, their ids follow.
+// - directory page: ids of the buckets of consecutive hash suffixes.
+// - bucket page: local depth, number of entries and the next overflow page.
+const DEPTH_OFFSET: usize = KIND_OFFSET + 1;
+const COUNT_OFFSET: usize = DEPTH_OFFSET + 1;
+const LINK_OFFSET: usize = COUNT_OFFSET + 2;
+const PAGE_HEADER_SIZE: usize = LINK_OFFSET + 8;
+
+const META: u8 = 1;
+const DIRECTORY: u8 = 2;
+const BUCKET: u8 = 3;
+
+const META_PAGE_ID: PageId = 0;
+// The meta page is never an overflow page, its id ends a chain of buckets.
+const NO_PAGE: PageId = 0;
+
+const PAGE_ID_SIZE: usize = 8;
+const ROW_ID_SIZE: usize = 10;
+const KEY_LENGTH_SIZE: usize = 2;
+
+type Entry = (Vec<u8>, RowId);
+
+#[derive(Debug)]
+pub enum HashIndexError<'a> {
+    Io(std::io::Error),
+    Page(GetPageError<'a>),
+    Relation(AddRelationError),
+    KeyTooLong(usize),
+    DuplicateEntry,
+    Corrupted(PageId),
+}
+
+impl<'a> From<GetPageError<'a>> for HashIndexError<'a> {
+    fn from(err: GetPageError<'a>) -> HashIndexError<'a> {
+        HashIndexError::Page(err)
+    }
+}
+
+impl From<AddRelationError> for HashIndexError<'_> {
+    fn from(err: AddRelationError) -> Self {
+        HashIndexError::Relation(err)
+    }
+}
+
+impl From<std::io::Error> for HashIndexError<'_> {
+    fn from(err: std::io::Error) -> Self {
+        HashIndexError::Io(err)
+    }
+}
+
+/// Extendible hash index of byte keys, for equality lookups only. Like
+/// `BTree` every entry points to a row and a key may point to many rows.
+///
+/// The lowest `global depth` bits of the XxHash3 hash of a key pick an entry
+/// of the directory, which points to a bucket. A full bucket is split in two
+/// by one more bit of the hash, the directory doubles when the bucket used all
+/// bits of it. Entries that a split cannot separate, e.g. entries of one key,
+/// go to overflow pages chained to the bucket. Buckets are never merged.
+///
+/// Lookups and deletes lock the meta page, the directory page and the bucket
+/// for a moment each. An insert into a full bucket locks the meta page for
+/// writing, so splits are serialized and the directory cannot change under them.
+pub struct HashIndex<'a> {
+    pool: &'a BufferPool<'a>,
+    relation_id: RelationId,
+    pages: PageAllocator,
+}
+
+impl<'a> HashIndex<'a> {
+    /// Opens the index, or creates an empty one, and registers it in the pool as `relation_id`.
+    pub fn open(
+        pool: &'a BufferPool<'a>,
+        relation_id: RelationId,
+        path: &str,
+        filename: &str,
+    ) -> Result<HashIndex<'a>, HashIndexError<'a>> {
+        let relation_file = RelationFile::open(path, filename, pool.page_size(), IoBackend::Pread)?;
+        let page_count = relation_file.page_count();
+
+        // Pages of split chains are freed, they are found again on open.
+        let pages = PageAllocator::open(&relation_file, pool.page_size(), 3)?;
+        pool.add_relation(relation_id, relation_file)?;
+
+        let index = HashIndex {
+            pool,
+            relation_id,
+            pages,
+        };
+
+        if page_count == 0 {
+            let mut meta = pool.new_page(index.page_key(META_PAGE_ID))?;
+            Header {
+                global_depth: 0,
+                directory: vec![1],
+            }
+            .write(meta.get_mut());
+            pool.write_page(&meta)?;
+
+            let mut directory = pool.new_page(index.page_key(1))?;
+            directory.get_mut().data[KIND_OFFSET] = DIRECTORY;
+            write_u64(&mut directory.get_mut().data, PAGE_HEADER_SIZE, 2);
+            pool.write_page(&directory)?;
+
+            let mut bucket = pool.new_page(index.page_key(2))?;
+            index.write_bucket(&mut bucket, &Bucket::new(0))?;
+        }
+
+        Ok(index)
+    }
+
+    pub fn page_count(&self) -> u64 {
+        self.pages.page_count()
+    }
+
+    /// Number of hash bits the directory uses.
+    pub fn global_depth(&self) -> Result<u8, HashIndexError<'a>> {
+        let meta = self.pool.get(self.page_key(META_PAGE_ID))?;
+
+        Ok(Header::read(meta.get())?.global_depth)
+    }
+
+    /// Longest key that fits, a bucket page holds at least four entries.
+    pub fn max_key_length(&self) -> usize {
+        (self.pool.page_size() - PAGE_HEADER_SIZE) / 4 - KEY_LENGTH_SIZE - ROW_ID_SIZE
+    }
+
+    /// Rows of all entries with the key.
+    pub fn get(&self, key: &[u8]) -> Result<Vec<RowId>, HashIndexError<'a>> {
+        let hash = XxHash3_64::oneshot(key);
+
+        let meta = self.pool.get(self.page_key(META_PAGE_ID))?;
+        let header = Header::read(meta.get())?;
+        let (directory_page, offset) = header.directory_slot(hash, self.pool.page_size());
+
+        let directory = self.pool.get(self.page_key(directory_page))?;
+        drop(meta);
+
+        let mut page = self
+            .pool
+            .get(self.page_key(read_u64(&directory.get().data, offset)))?;
+        drop(directory);
+
+        let mut rows = vec![];
+        loop {
+            let bucket = Bucket::read(page.get())?;
+            rows.extend(
+                bucket
+                    .entries
+                    .iter()
+                    .filter(|(k, _)| k == key)
+                    .map(|(_, row_id)| *row_id),
+            );
+
+            if bucket.link == NO_PAGE {
+                return Ok(rows);
+            }
+
+            // The next page is locked before this one is released.
+            page = self.pool.get(self.page_key(bucket.link))?;
+        }
+    }
+
+    pub fn insert(&self, key: &[u8], row_id: RowId) -> Result<(), HashIndexError<'a>> {
+        if key.len() > self.max_key_length() {
+            return Err(HashIndexError::KeyTooLong(key.len()));
+        }
+
+        let hash = XxHash3_64::oneshot(key);
+        let entry = (key.to_vec(), row_id);
+
+        {
+            let meta = self.pool.get(self.page_key(META_PAGE_ID))?;
+            let header = Header::read(meta.get())?;
+            let (directory_page, offset) = header.directory_slot(hash, self.pool.page_size());
+
+            let directory = self.pool.get(self.page_key(directory_page))?;
+            drop(meta);
+
+            let bucket_id = read_u64(&directory.get().data, offset);
+            let mut chain = self.lock_chain(bucket_id)?;
+            drop(directory);
+
+            if self.insert_into_chain(&mut chain, &entry)? {
+                return Ok(());
+            }
+        }
+
+        // The bucket is full, it is split or gets an overflow page while the
+        // meta page keeps other splits out.
+        let mut meta = self.pool.get_mut(self.page_key(META_PAGE_ID))?;
+        let mut header = Header::read(meta.get())?;
+
+        let mut directory = vec![];
+        for page_id in &header.directory {
+            directory.push(self.pool.get_mut(self.page_key(*page_id))?);
+        }
+
+        loop {
+            let (page, offset) = header.directory_slot(hash, self.pool.page_size());
+            let position = header.directory.iter().position(|id| *id == page).unwrap();
+            let bucket_id = read_u64(&directory[position].get().data, offset);
+
+            let mut chain = self.lock_chain(bucket_id)?;
+            if self.insert_into_chain(&mut chain, &entry)? {
+                return Ok(());
+            }
+
+            let local_depth = chain[0].1.local_depth;
+            let mut hashes = chain
+                .iter()
+                .flat_map(|(_, bucket)| &bucket.entries)
+                .map(|(key, _)| XxHash3_64::oneshot(key))
+                .chain([hash]);
+            let first_hash = hashes.next().unwrap();
+            let can_split =
+                local_depth < self.max_global_depth() && hashes.any(|h| h != first_hash);
+
+            if !can_split {
+                let overflow_id = self.pages.allocate();
+                let mut overflow = self.pool.new_page(self.page_key(overflow_id))?;
+
+                let mut bucket = Bucket::new(local_depth);
+                bucket.entries.push(entry);
+                self.write_bucket(&mut overflow, &bucket)?;
+
+                let (last_guard, last) = chain.last_mut().unwrap();
+                last.link = overflow_id;
+                return self.write_bucket(last_guard, last);
+            }
+
+            if local_depth == header.global_depth {
+                self.double_directory(&mut meta, &mut header, &mut directory)?;
+            }
+
+            self.split(chain, &header, &mut directory)?;
+        }
+    }
+
+    /// Removes the entry, returns whether it existed.
+    pub fn delete(&self, key: &[u8], row_id: RowId) -> Result<bool, HashIndexError<'a>> {
+        let hash = XxHash3_64::oneshot(key);
+        let entry = (key.to_vec(), row_id);
+
+        let meta = self.pool.get(self.page_key(META_PAGE_ID))?;
+        let header = Header::read(meta.get())?;
+        let (directory_page, offset) = header.directory_slot(hash, self.pool.page_size());
+
+        let directory = self.pool.get(self.page_key(directory_page))?;
+        drop(meta);
+
+        let mut guard = self
+            .pool
+            .get_mut(self.page_key(read_u64(&directory.get().data, offset)))?;
+        drop(directory);
+
+        let mut previous: Option<(WritePageGuard<'a>, Bucket)> = None;
+        loop {
+            let mut bucket = Bucket::read(guard.get())?;
+
+            if let Some(position) = bucket.entries.iter().position(|e| *e == entry) {
+                bucket.entries.swap_remove(position);
+
+                // An empty overflow page is unlinked, the first page of a bucket stays.
+                match previous {
+                    Some((mut previous_guard, mut previous)) if bucket.entries.is_empty() => {
+                        previous.link = bucket.link;
+                        self.write_bucket(&mut previous_guard, &previous)?;
+                        self.pages.free(self.pool, &mut guard)?;
+                    }
+                    _ => self.write_bucket(&mut guard, &bucket)?,
+                }
+
+                return Ok(true);
+            }
+
+            if bucket.link == NO_PAGE {
+                return Ok(false);
+            }
+
+            let next = self.pool.get_mut(self.page_key(bucket.link))?;
+            previous = Some((guard, bucket));
+            guard = next;
+        }
+    }
+
+    // Write-locks the pages of a bucket from the first one to the last overflow page.
+    fn lock_chain(
+        &self,
+        bucket_id: PageId,
+    ) -> Result<Vec<(WritePageGuard<'a>, Bucket)>, HashIndexError<'a>> {
+        let mut chain = vec![];
+        let mut page_id = bucket_id;
+
+        while page_id != NO_PAGE {
+            let guard = self.pool.get_mut(self.page_key(page_id))?;
+            let bucket = Bucket::read(guard.get())?;
+
+            page_id = bucket.link;
+            chain.push((guard, bucket));
+        }
+
+        Ok(chain)
+    }
+
+    // Puts the entry into the first page of the chain with room for it.
+    // Returns false when the chain is full.
+    fn insert_into_chain(
+        &self,
+        chain: &mut [(WritePageGuard<'a>, Bucket)],
+        entry: &Entry,
+    ) -> Result<bool, HashIndexError<'a>> {
+        if chain
+            .iter()
+            .any(|(_, bucket)| bucket.entries.contains(entry))
+        {
+            return Err(HashIndexError::DuplicateEntry);
+        }
+
+        let Some((guard, bucket)) = chain
+            .iter_mut()
+            .find(|(_, bucket)| bucket.size() + entry_size(entry) <= self.pool.page_size())
+        else {
+            return Ok(false);
+        };
+
+        bucket.entries.push(entry.clone());
+        self.write_bucket(guard, bucket)?;
+
+        Ok(true)
+    }
+
+    // Splits the bucket by the next bit of the hash, its entries are rewritten
+    // into two new chains. The directory must use more bits than the bucket.
+    fn split(
+        &self,
+        chain: Vec<(WritePageGuard<'a>, Bucket)>,
+        header: &Header,
+        directory: &mut [WritePageGuard<'a>],
+    ) -> Result<(), HashIndexError<'a>> {
+        let local_depth = chain[0].1.local_depth;
+        let bucket_id = chain[0].0.get().id;
+        let bit = 1 << local_depth;
+
+        let mut guards = vec![];
+        let (mut stay, mut moved) = (vec![], vec![]);
+        for (guard, bucket) in chain {
+            for entry in bucket.entries {
+                if XxHash3_64::oneshot(&entry.0) & bit == 0 {
+                    stay.push(entry);
+                } else {
+                    moved.push(entry);
+                }
+            }
+            guards.push(guard);
+        }
+
+        let new_bucket_id = self.pages.allocate();
+        let new_bucket = self.pool.new_page(self.page_key(new_bucket_id))?;
+
+        self.write_chain(guards, local_depth + 1, stay)?;
+        self.write_chain(vec![new_bucket], local_depth + 1, moved)?;
+
+        // Every directory entry with the suffix of the bucket and the new bit set moves.
+        let entries_per_page = directory_entries_per_page(self.pool.page_size());
+        for (page_index, page) in directory.iter_mut().enumerate() {
+            let mut changed = false;
+
+            for slot in 0..entries_per_page.min(1 << header.global_depth) {
+                let offset = PAGE_HEADER_SIZE + slot * PAGE_ID_SIZE;
+                let directory_index = (page_index * entries_per_page + slot) as u64;
+
+                if directory_index & bit != 0 && read_u64(&page.get().data, offset) == bucket_id {
+                    write_u64(&mut page.get_mut().data, offset, new_bucket_id);
+                    changed = true;
+                }
+            }
+
+            if changed {
+                self.pool.write_page(page)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Uses one more bit of the hash, the second half of the directory is a copy of the first.
+    fn double_directory(
+        &self,
+        meta: &mut WritePageGuard<'a>,
+        header: &mut Header,
+        directory: &mut Vec<WritePageGuard<'a>>,
+    ) -> Result<(), HashIndexError<'a>> {
+        let entries_per_page = directory_entries_per_page(self.pool.page_size());
+        let old_size = 1usize << header.global_depth;
+
+        let read_entry = |directory: &[WritePageGuard<'a>], i: usize| {
+            read_u64(
+                &directory[i / entries_per_page].get().data,
+                PAGE_HEADER_SIZE + i % entries_per_page * PAGE_ID_SIZE,
+            )
+        };
+
+        let old_pages = directory.len();
+        for i in old_size..old_size * 2 {
+            if i / entries_per_page == directory.len() {
+                let page_id = self.pages.allocate();
+                let mut page = self.pool.new_page(self.page_key(page_id))?;
+                page.get_mut().data[KIND_OFFSET] = DIRECTORY;
+
+                directory.push(page);
+                header.directory.push(page_id);
+            }
+
+            let bucket_id = read_entry(directory, i - old_size);
+            write_u64(
+                &mut directory[i / entries_per_page].get_mut().data,
+                PAGE_HEADER_SIZE + i % entries_per_page * PAGE_ID_SIZE,
+                bucket_id,
+            );
+        }
+
+        // The old pages change only when the directory fits into one page.
+        let first_changed = if old_pages == directory.len() {
+            0
+        } else {
+            old_pages
+        };
+        for page in &directory[first_changed..] {
+            self.pool.write_page(page)?;
+        }
+
+        header.global_depth += 1;
+        header.write(meta.get_mut());
+        self.pool.write_page(meta)?;
+
+        Ok(())
+    }
+
+    // Writes the entries into the pages, more pages are chained as needed and
+    // the pages left over are freed.
+    fn write_chain(
+        &self,
+        guards: Vec<WritePageGuard<'a>>,
+        local_depth: u8,
+        entries: Vec<Entry>,
+    ) -> Result<(), HashIndexError<'a>> {
+        let mut buckets = vec![Bucket::new(local_depth)];
+        for entry in entries {
+            let last = buckets.last_mut().unwrap();
+
+            if last.size() + entry_size(&entry) > self.pool.page_size() {
+                buckets.push(Bucket::new(local_depth));
+            }
+            buckets.last_mut().unwrap().entries.push(entry);
+        }
+
+        let mut guards = guards.into_iter();
+        let mut pages = vec![];
+        for _ in 0..buckets.len() {
+            match guards.next() {
+                Some(guard) => pages.push(guard),
+                None => {
+                    let page_id = self.pages.allocate();
+                    pages.push(self.pool.new_page(self.page_key(page_id))?);
+                }
+            }
+        }
+
+        for i in 0..buckets.len() {
+            buckets[i].link = pages.get(i + 1).map_or(NO_PAGE, |page| page.get().id);
+            self.write_bucket(&mut pages[i], &buckets[i])?;
+        }
+
+        for mut guard in guards {
+            self.pages.free(self.pool, &mut guard)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_bucket(
+        &self,
+        guard: &mut WritePageGuard<'a>,
+        bucket: &Bucket,
+    ) -> Result<(), HashIndexError<'a>> {
+        bucket.write(guard.get_mut());
+        self.pool.write_page(guard)?;
+
+        Ok(())
+    }
+
+    // The ids of all directory pages must fit into the meta page.
+    fn max_global_depth(&self) -> u8 {
+        let max_pages = ((self.pool.page_size() - PAGE_HEADER_SIZE) / PAGE_ID_SIZE).ilog2();
+
+        (directory_entries_per_page(self.pool.page_size()).ilog2() + max_pages) as u8
+    }
+
+    fn page_key(&self, page_id: PageId) -> PageKey {
+        PageKey::new(self.relation_id, page_id)
+    }
+}
+
+// Contents of the meta page.
+struct Header {
+    global_depth: u8,
+    directory: Vec<PageId>,
+}
+
+impl Header {
+    fn read<'a>(page: &Page) -> Result<Header, HashIndexError<'a>> {
+        let data = &page.data;
+        if data[KIND_OFFSET] != META {
+            return Err(HashIndexError::Corrupted(page.id));
+        }
+
+        let count = u16::from_be_bytes([data[COUNT_OFFSET], data[COUNT_OFFSET + 1]]) as usize;
+        if PAGE_HEADER_SIZE + count * PAGE_ID_SIZE > data.len() {
+            return Err(HashIndexError::Corrupted(page.id));
+        }
+
+        Ok(Header {
+            global_depth: data[DEPTH_OFFSET],
+            directory: (0..count)
+                .map(|i| read_u64(data, PAGE_HEADER_SIZE + i * PAGE_ID_SIZE))
+                .collect(),
+        })
+    }
+
+    fn write(&self, page: &mut Page) {
+        let data = &mut page.data;
+
+        data[KIND_OFFSET] = META;
+        data[DEPTH_OFFSET] = self.global_depth;
+        data[COUNT_OFFSET..LINK_OFFSET]
+            .copy_from_slice(&(self.directory.len() as u16).to_be_bytes());
+
+        for (i, page_id) in self.directory.iter().enumerate() {
+            write_u64(data, PAGE_HEADER_SIZE + i * PAGE_ID_SIZE, *page_id);
+        }
+    }
+
+    // Directory page and offset in it of the bucket id for the hash.
+    fn directory_slot(&self, hash: u64, page_size: usize) -> (PageId, usize) {
+        let entries_per_page = directory_entries_per_page(page_size);
+        let index = (hash & ((1 << self.global_depth) - 1)) as usize;
+
+        (
+            self.directory[index / entries_per_page],
+            PAGE_HEADER_SIZE + index % entries_per_page * PAGE_ID_SIZE,
+        )
+    }
+}
+
+// Page of a bucket, the first one or an overflow page.
+#[derive(Debug)]
+struct Bucket {
+    local_depth: u8,
+    entries: Vec<Entry>,
+    link: PageId,
+}
+
+impl Bucket {
+    fn new(local_depth: u8) -> Bucket {
+        Bucket {
+            local_depth,
+            entries: vec![],
+            link: NO_PAGE,
+        }
+    }
+
+    fn read<'a>(page: &Page) -> Result<Bucket, HashIndexError<'a>> {
+        let corrupted = || HashIndexError::Corrupted(page.id);
+        let data = &page.data;
+
+        if data[KIND_OFFSET] != BUCKET {
+            return Err(corrupted());
+        }
+
+        let count = u16::from_be_bytes([data[COUNT_OFFSET], data[COUNT_OFFSET + 1]]) as usize;
+        let mut bucket = Bucket {
+            local_depth: data[DEPTH_OFFSET],
+            entries: Vec::with_capacity(count),
+            link: read_u64(data, LINK_OFFSET),
+        };
+
+        let mut offset = PAGE_HEADER_SIZE;
+        for _ in 0..count {
+            let key_start = offset + KEY_LENGTH_SIZE;
+            let Some(length) = data.get(offset..key_start) else {
+                return Err(corrupted());
+            };
+
+            let key_end = key_start + u16::from_be_bytes([length[0], length[1]]) as usize;
+            if key_end + ROW_ID_SIZE > data.len() {
+                return Err(corrupted());
+            }
+
+            let row_id = RowId::new(
+                read_u64(data, key_end),
+                SlotId::from_be_bytes([data[key_end + 8], data[key_end + 9]]),
+            );
+            bucket
+                .entries
+                .push((data[key_start..key_end].to_vec(), row_id));
+
+            offset = key_end + ROW_ID_SIZE;
+        }
+
+        Ok(bucket)
+    }
+
+    fn write(&self, page: &mut Page) {
+        let data = &mut page.data;
+
+        data[KIND_OFFSET] = BUCKET;
+        data[DEPTH_OFFSET] = self.local_depth;
+        data[COUNT_OFFSET..LINK_OFFSET].copy_from_slice(&(self.entries.len() as u16).to_be_bytes());
+        write_u64(data, LINK_OFFSET, self.link);
+
+        let mut offset = PAGE_HEADER_SIZE;
+        for (key, row_id) in &self.entries {
+            let mut write = |bytes: &[u8]| {
+                data[offset..offset + bytes.len()].copy_from_slice(bytes);
+                offset += bytes.len();
+            };
+
+            write(&(key.len() as u16).to_be_bytes());
+            write(key);
+            write(&row_id.page_id.to_be_bytes());
+            write(&row_id.slot_id.to_be_bytes());
+        }
+
+        data[offset..].fill(0);
+    }
+
+    fn size(&self) -> usize {
+        PAGE_HEADER_SIZE + self.entries.iter().map(entry_size).sum::<usize>()
+    }
+}
+
+fn entry_size(entry: &Entry) -> usize {
+    KEY_LENGTH_SIZE + entry.0.len() + ROW_ID_SIZE
+}
+
+// A power of two, so a directory page holds the entries of whole suffixes.
+fn directory_entries_per_page(page_size: usize) -> usize {
+    let entries = (page_size - PAGE_HEADER_SIZE) / PAGE_ID_SIZE;
+
+    1 << entries.ilog2()
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn write_u64(data: &mut [u8], offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_be_bytes());
+}
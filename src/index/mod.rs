@@ -0,0 +1,79 @@
+pub mod btree;
+pub mod full_text;
+pub mod hash_index;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::Mutex;
+
+use crate::{
+    buffer_pool::buffer_pool::{BufferPool, GetPageError, WritePageGuard},
+    page::{HEADER_SIZE, Page, PageId},
+    persist::relation::RelationFile,
+};
+
+// Every index page starts with its kind, freed pages are of kind `FREE`.
+const KIND_OFFSET: usize = HEADER_SIZE;
+const FREE: u8 = 0;
+
+/// Pages of an index file. Freed pages are reused before the file grows,
+/// they are found again by their kind when the file is opened.
+pub struct PageAllocator {
+    next_page_id: AtomicU64,
+    free_pages: Mutex<Vec<PageId>>,
+}
+
+impl PageAllocator {
+    /// Reads the pages of the file after the meta page. A new index creates
+    /// pages below `first_page_id` itself.
+    pub fn open<'a>(
+        file: &RelationFile,
+        page_size: usize,
+        first_page_id: PageId,
+    ) -> Result<PageAllocator, GetPageError<'a>> {
+        let page_count = file.page_count();
+
+        let mut free_pages = vec![];
+        let mut page = Page::with_size(0, page_size);
+        for page_id in 1..page_count {
+            let Ok(_) = file.read_page(page_id, &mut page) else {
+                return Err(GetPageError::FailedToReadFromDisk);
+            };
+
+            if page.data[KIND_OFFSET] == FREE {
+                free_pages.push(page_id);
+            }
+        }
+
+        Ok(PageAllocator {
+            next_page_id: AtomicU64::new(page_count.max(first_page_id)),
+            free_pages: Mutex::new(free_pages),
+        })
+    }
+
+    pub fn page_count(&self) -> u64 {
+        self.next_page_id.load(Ordering::Acquire)
+    }
+
+    pub fn allocate(&self) -> PageId {
+        self.free_pages
+            .lock()
+            .pop()
+            .unwrap_or_else(|| self.next_page_id.fetch_add(1, Ordering::AcqRel))
+    }
+
+    /// Empties the page and writes it, it is reused by a later `allocate`.
+    pub fn free<'a>(
+        &self,
+        pool: &BufferPool<'a>,
+        guard: &mut WritePageGuard<'a>,
+    ) -> Result<(), GetPageError<'a>> {
+        let key = guard.get().key();
+        guard.get_mut().reset(key);
+        pool.write_page(guard)?;
+
+        self.free_pages.lock().push(key.page_id);
+
+        Ok(())
+    }
+}
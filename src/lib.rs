@@ -0,0 +1,17 @@
+// Page reads report a failure as `Err(())`, callers map it to their own errors.
+#![allow(clippy::result_unit_err)]
+
+pub mod bloom_filter;
+pub mod buffer_pool;
+pub mod catalog;
+pub mod free_space_map;
+pub mod heap_file;
+pub mod index;
+pub mod lsm;
+pub mod page;
+pub mod persist;
+pub mod schema;
+pub mod table;
+pub mod tuple;
+pub mod util;
+pub mod zone_map;
@@ -0,0 +1,66 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::util::side_file::{self, Reader};
+
+const FILE_EXTENSION: &str = "manifest";
+
+/// Runs of an LSM tree by level, the newest first in each level.
+///
+/// A run is live only once a manifest lists it. The manifest is written to a
+/// temporary file that replaces the old one, so a crash leaves either the old
+/// or the new list of runs.
+#[derive(Debug, Default, PartialEq)]
+pub struct Manifest {
+    pub next_run_id: u64,
+    pub levels: Vec<Vec<u64>>,
+}
+
+impl Manifest {
+    /// Reads the manifest of the tree `filename`, a new tree has no runs.
+    pub fn read(path: &str, filename: &str) -> Result<Manifest, Error> {
+        let data = match fs::read(manifest_path(path, filename)) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Manifest::default()),
+            Err(err) => return Err(err),
+        };
+
+        let mut reader = Reader::new(&data, "LSM manifest");
+
+        let next_run_id = reader.u64()?;
+        let level_count = reader.u16()?;
+
+        let mut levels = vec![];
+        for _ in 0..level_count {
+            let run_count = reader.u32()?;
+            let runs = (0..run_count)
+                .map(|_| reader.u64())
+                .collect::<Result<Vec<_>, _>>()?;
+            levels.push(runs);
+        }
+
+        Ok(Manifest {
+            next_run_id,
+            levels,
+        })
+    }
+
+    pub fn write(&self, path: &str, filename: &str) -> Result<(), Error> {
+        let mut data = vec![];
+        data.extend_from_slice(&self.next_run_id.to_be_bytes());
+        data.extend_from_slice(&(self.levels.len() as u16).to_be_bytes());
+        for runs in &self.levels {
+            data.extend_from_slice(&(runs.len() as u32).to_be_bytes());
+            for run in runs {
+                data.extend_from_slice(&run.to_be_bytes());
+            }
+        }
+
+        side_file::write(&manifest_path(path, filename), &data)
+    }
+}
+
+fn manifest_path(path: &str, filename: &str) -> PathBuf {
+    Path::new(path).join(format!("{}.{}", filename, FILE_EXTENSION))
+}
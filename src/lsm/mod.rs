@@ -0,0 +1,663 @@
+pub mod manifest;
+pub mod sstable;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Bound;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::catalog::{Catalog, Engine, TableInfo};
+use crate::page::{self, PageId};
+use crate::schema::Schema;
+use crate::tuple::{KeyColumn, Tuple, TupleDecodeError, TupleToDataError, TupleValue, encode_key};
+use manifest::Manifest;
+use sstable::SsTable;
+
+/// A key and its value, `None` is a tombstone of a deleted key.
+pub type Record = (Vec<u8>, Option<Vec<u8>>);
+
+type RecordSource<'s> = Box<dyn Iterator<Item = Result<Record, LsmError>> + Send + 's>;
+
+// Bytes a record takes in the memtable besides its key and value.
+const MEMTABLE_RECORD_OVERHEAD: usize = 32;
+
+#[derive(Debug)]
+pub enum LsmError {
+    Io(std::io::Error),
+    PageNotRead(PageId),
+    TupleToDataError(TupleToDataError),
+    TupleDecodeError(TupleDecodeError),
+    /// Key columns must not be null.
+    NullKey,
+    /// A tree is keyed by at least one column.
+    EmptyKey,
+    /// Options the tree cannot work with, e.g. an unsupported page size.
+    InvalidOptions(&'static str),
+    RecordTooBig(usize),
+    /// Name of a table that is not stored in an LSM tree.
+    WrongEngine(String),
+    Corrupted(&'static str),
+}
+
+impl From<std::io::Error> for LsmError {
+    fn from(err: std::io::Error) -> Self {
+        LsmError::Io(err)
+    }
+}
+
+impl From<TupleToDataError> for LsmError {
+    fn from(err: TupleToDataError) -> Self {
+        LsmError::TupleToDataError(err)
+    }
+}
+
+impl From<TupleDecodeError> for LsmError {
+    fn from(err: TupleDecodeError) -> Self {
+        LsmError::TupleDecodeError(err)
+    }
+}
+
+/// How runs are merged as they pile up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Compaction {
+    /// Flushed runs stay in level 0 until there are `level0_runs` of them,
+    /// then they are merged into the single run of level 1. Every level after
+    /// that is one run `size_ratio` times larger than the level before it,
+    /// level 1 holds `size_ratio` memtables. Reads check few runs, every
+    /// record is rewritten once per level.
+    Leveled {
+        level0_runs: usize,
+        size_ratio: usize,
+    },
+    /// A level holds up to `runs_per_level` runs, when it is full they are
+    /// merged into one run of the next level. Records are rewritten less
+    /// often than with leveled compaction, reads check more runs.
+    Tiered { runs_per_level: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LsmOptions {
+    pub page_size: usize,
+    /// Bytes of records the memtable holds before it is written to a run.
+    pub memtable_size: usize,
+    pub false_positive_rate: f64,
+    pub compaction: Compaction,
+}
+
+impl LsmOptions {
+    fn validate(&self) -> Result<(), LsmError> {
+        if !page::is_supported_size(self.page_size) {
+            return Err(LsmError::InvalidOptions("Unsupported page size"));
+        }
+        if !(self.false_positive_rate > 0.0 && self.false_positive_rate < 1.0) {
+            return Err(LsmError::InvalidOptions(
+                "A false positive rate is between 0 and 1",
+            ));
+        }
+
+        match self.compaction {
+            Compaction::Leveled {
+                level0_runs,
+                size_ratio,
+            } if level0_runs == 0 || size_ratio < 2 => {
+                Err(LsmError::InvalidOptions("Levels grow by a ratio above 1"))
+            }
+            Compaction::Tiered { runs_per_level } if runs_per_level < 2 => {
+                Err(LsmError::InvalidOptions("A tier merges at least two runs"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for LsmOptions {
+    fn default() -> Self {
+        LsmOptions {
+            page_size: page::SIZE,
+            memtable_size: 4 * 1024 * 1024,
+            false_positive_rate: 0.01,
+            compaction: Compaction::Leveled {
+                level0_runs: 4,
+                size_ratio: 10,
+            },
+        }
+    }
+}
+
+/// Rows of a table sorted by key in a log-structured merge tree, an engine
+/// for tables that are mostly appended to.
+///
+/// Writes go to an in-memory memtable. A full memtable is written out as an
+/// immutable sorted run, and runs are merged by compaction as they pile up,
+/// so pages are never written in place. A read checks the memtable
+/// and then the runs from the newest to the oldest, skipping runs whose bloom
+/// filter lacks the key; the newest value of a key wins, a delete writes a
+/// tombstone that hides older values until a compaction drops it.
+///
+/// The memtable is not logged, rows written after the last `flush` are lost
+/// in a crash. Flushes and compactions run on the writing thread while writes
+/// wait, reads and scans that already hold the runs they need go on.
+pub struct LsmTree<'a> {
+    path: String,
+    filename: String,
+    schema: &'a Schema,
+    key: Vec<usize>,
+    key_columns: Vec<KeyColumn>,
+    options: LsmOptions,
+    state: RwLock<State>,
+}
+
+struct State {
+    memtable: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    memtable_size: usize,
+    // Runs of every level, the newest first.
+    levels: Vec<Vec<Arc<SsTable>>>,
+    next_run_id: u64,
+}
+
+impl<'a> LsmTree<'a> {
+    /// Opens the tree stored in files named after `filename`, or creates an
+    /// empty one. Rows are keyed by the columns `key` of the schema.
+    pub fn open(
+        path: &str,
+        filename: &str,
+        schema: &'a Schema,
+        key: &[usize],
+        options: LsmOptions,
+    ) -> Result<LsmTree<'a>, LsmError> {
+        if key.is_empty() {
+            return Err(LsmError::EmptyKey);
+        }
+        options.validate()?;
+
+        let manifest = Manifest::read(path, filename)?;
+
+        let mut levels = vec![];
+        for level in &manifest.levels {
+            let runs = level
+                .iter()
+                .map(|&id| {
+                    Ok(Arc::new(SsTable::open(
+                        path,
+                        filename,
+                        id,
+                        options.page_size,
+                    )?))
+                })
+                .collect::<Result<Vec<_>, LsmError>>()?;
+            levels.push(runs);
+        }
+
+        remove_orphan_runs(path, filename, &manifest)?;
+
+        Ok(LsmTree {
+            path: path.to_owned(),
+            filename: filename.to_owned(),
+            schema,
+            key: key.to_vec(),
+            key_columns: key
+                .iter()
+                .map(|&column| KeyColumn::new(schema.columns()[column].data_type))
+                .collect(),
+            options,
+            state: RwLock::new(State {
+                memtable: BTreeMap::new(),
+                memtable_size: 0,
+                levels,
+                next_run_id: manifest.next_run_id,
+            }),
+        })
+    }
+
+    /// Opens the tree of a table created with the LSM engine.
+    pub fn open_table(
+        catalog: &Catalog,
+        info: &'a TableInfo,
+        options: LsmOptions,
+    ) -> Result<LsmTree<'a>, LsmError> {
+        let Engine::Lsm { key } = &info.engine else {
+            return Err(LsmError::WrongEngine(info.name.clone()));
+        };
+
+        LsmTree::open(catalog.path(), &info.filename, &info.schema, key, options)
+    }
+
+    /// Number of runs in every level.
+    pub fn run_counts(&self) -> Vec<usize> {
+        self.state.read().levels.iter().map(Vec::len).collect()
+    }
+
+    /// Inserts the row, or replaces the row with the same key.
+    pub fn put(&self, tuple: &Tuple) -> Result<(), LsmError> {
+        let key = self.encode_key(&self.key_values(tuple))?;
+
+        self.write(key, Some(tuple.to_data()?))
+    }
+
+    /// Deletes the row with the key, if there is one.
+    pub fn delete(&self, key: &[TupleValue]) -> Result<(), LsmError> {
+        let key = self.encode_key(key)?;
+
+        self.write(key, None)
+    }
+
+    /// Row with the key, whose values are in the order of the key columns.
+    pub fn get(&self, key: &[TupleValue]) -> Result<Option<Tuple<'a>>, LsmError> {
+        let key = self.encode_key(key)?;
+
+        let runs = {
+            let state = self.state.read();
+            if let Some(value) = state.memtable.get(&key) {
+                return self.decode(value.as_deref());
+            }
+
+            state.levels.iter().flatten().cloned().collect::<Vec<_>>()
+        };
+
+        for run in runs {
+            if let Some(value) = run.get(&key)? {
+                return self.decode(value.as_deref());
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Rows whose keys are between `start` and `end`, in key order.
+    pub fn scan(
+        &self,
+        start: Bound<&[TupleValue]>,
+        end: Bound<&[TupleValue]>,
+    ) -> Result<LsmScan<'a>, LsmError> {
+        let start = self.encode_bound(start)?;
+        let end = self.encode_bound(end)?;
+
+        let (memtable, runs) = {
+            let state = self.state.read();
+
+            let memtable = match is_empty_range(&start, &end) {
+                true => vec![],
+                false => state
+                    .memtable
+                    .range::<Vec<u8>, _>((start.as_ref(), end.as_ref()))
+                    .map(|(key, value)| Ok((key.clone(), value.clone())))
+                    .collect(),
+            };
+            let runs = state.levels.iter().flatten().cloned().collect::<Vec<_>>();
+
+            (memtable, runs)
+        };
+
+        let mut sources: Vec<RecordSource<'static>> = vec![Box::new(memtable.into_iter())];
+        for run in &runs {
+            sources.push(Box::new(run.records(start.as_ref().map(Vec::as_slice))));
+        }
+
+        Ok(LsmScan {
+            schema: self.schema,
+            records: MergedRecords::new(sources),
+            end,
+        })
+    }
+
+    /// Writes the memtable to a run, so all rows written so far are on disk.
+    pub fn flush(&self) -> Result<(), LsmError> {
+        let mut state = self.state.write();
+
+        self.flush_memtable(&mut state)
+    }
+
+    fn write(&self, key: Vec<u8>, value: Option<Vec<u8>>) -> Result<(), LsmError> {
+        // Checked now, a run with a record that fits no page can't be written.
+        if !sstable::fits_page(&key, value.as_deref(), self.options.page_size) {
+            return Err(LsmError::RecordTooBig(
+                key.len() + value.as_ref().map_or(0, Vec::len),
+            ));
+        }
+
+        let mut state = self.state.write();
+
+        let (key_len, value_len) = (key.len(), value.as_ref().map_or(0, Vec::len));
+        match state.memtable.insert(key, value) {
+            Some(old) => {
+                state.memtable_size =
+                    state.memtable_size + value_len - old.map_or(0, |old| old.len())
+            }
+            None => state.memtable_size += key_len + value_len + MEMTABLE_RECORD_OVERHEAD,
+        }
+
+        if state.memtable_size >= self.options.memtable_size {
+            self.flush_memtable(&mut state)?;
+        }
+
+        Ok(())
+    }
+
+    fn flush_memtable(&self, state: &mut State) -> Result<(), LsmError> {
+        if state.memtable.is_empty() {
+            return Ok(());
+        }
+
+        let id = state.take_run_id();
+
+        // Tombstones hide nothing when there are no runs.
+        let drop_tombstones = state.levels.iter().all(Vec::is_empty);
+        let records = state
+            .memtable
+            .iter()
+            .filter(|(_, value)| !drop_tombstones || value.is_some())
+            .map(|(key, value)| Ok((key.clone(), value.clone())));
+
+        let run = self.write_run(id, records)?;
+        if state.levels.is_empty() {
+            state.levels.push(vec![]);
+        }
+        if let Some(run) = run {
+            state.levels[0].insert(0, run);
+        }
+        self.compact(state)?;
+        self.write_manifest(state)?;
+
+        state.memtable.clear();
+        state.memtable_size = 0;
+
+        Ok(())
+    }
+
+    // Merges runs until every level is within the limits of the compaction.
+    fn compact(&self, state: &mut State) -> Result<(), LsmError> {
+        let mut level = 0;
+
+        while level < state.levels.len() {
+            let full = match self.options.compaction {
+                Compaction::Leveled {
+                    level0_runs,
+                    size_ratio,
+                } => match level {
+                    0 => state.levels[0].len() >= level0_runs,
+                    _ => {
+                        let size = state.levels[level]
+                            .iter()
+                            .map(|run| run.size())
+                            .sum::<u64>();
+                        let limit = (self.options.memtable_size as u64)
+                            .saturating_mul((size_ratio as u64).saturating_pow(level as u32));
+
+                        size > limit
+                    }
+                },
+                Compaction::Tiered { runs_per_level } => {
+                    state.levels[level].len() >= runs_per_level
+                }
+            };
+
+            if full {
+                self.compact_level(state, level)?;
+            }
+            level += 1;
+        }
+
+        Ok(())
+    }
+
+    // Merges the runs of the level into one run of the next level. With
+    // leveled compaction the run of the next level is merged in as well.
+    fn compact_level(&self, state: &mut State, level: usize) -> Result<(), LsmError> {
+        if state.levels.len() == level + 1 {
+            state.levels.push(vec![]);
+        }
+
+        let leveled = matches!(self.options.compaction, Compaction::Leveled { .. });
+
+        // Inputs stay in their levels until the new run is written.
+        let mut inputs = state.levels[level].clone();
+        if leveled {
+            inputs.extend(state.levels[level + 1].iter().cloned());
+        }
+
+        // Older values a tombstone could hide are only in the levels below.
+        let drop_tombstones = (leveled || state.levels[level + 1].is_empty())
+            && state.levels[level + 2..].iter().all(Vec::is_empty);
+
+        let sources = inputs
+            .iter()
+            .map(|run| Box::new(run.records(Bound::Unbounded)) as RecordSource)
+            .collect();
+        let records = MergedRecords::new(sources)
+            .filter(|record| !drop_tombstones || !matches!(record, Ok((_, None))));
+
+        let run = self.write_run(state.take_run_id(), records)?;
+        state.levels[level].clear();
+        if leveled {
+            state.levels[level + 1].clear();
+        }
+        if let Some(run) = run {
+            state.levels[level + 1].insert(0, run);
+        }
+
+        // Inputs are deleted once the new manifest no longer lists them.
+        self.write_manifest(state)?;
+        for run in inputs {
+            run.mark_obsolete();
+        }
+
+        Ok(())
+    }
+
+    // Writes a run of the records, there is none if they are empty.
+    fn write_run(
+        &self,
+        id: u64,
+        records: impl Iterator<Item = Result<Record, LsmError>>,
+    ) -> Result<Option<Arc<SsTable>>, LsmError> {
+        let mut records = records.peekable();
+        if records.peek().is_none() {
+            return Ok(None);
+        }
+
+        let run = SsTable::write(
+            &self.path,
+            &self.filename,
+            id,
+            self.options.page_size,
+            self.options.false_positive_rate,
+            records,
+        )?;
+
+        Ok(Some(Arc::new(run)))
+    }
+
+    fn write_manifest(&self, state: &State) -> Result<(), LsmError> {
+        let manifest = Manifest {
+            next_run_id: state.next_run_id,
+            levels: state
+                .levels
+                .iter()
+                .map(|runs| runs.iter().map(|run| run.id()).collect())
+                .collect(),
+        };
+
+        Ok(manifest.write(&self.path, &self.filename)?)
+    }
+
+    fn key_values(&self, tuple: &Tuple) -> Vec<TupleValue> {
+        self.key
+            .iter()
+            .map(|&column| tuple.values[column].clone())
+            .collect()
+    }
+
+    fn encode_key(&self, values: &[TupleValue]) -> Result<Vec<u8>, LsmError> {
+        if values.iter().any(TupleValue::is_null) {
+            return Err(LsmError::NullKey);
+        }
+
+        Ok(encode_key(&self.key_columns, values)?)
+    }
+
+    fn encode_bound(&self, bound: Bound<&[TupleValue]>) -> Result<Bound<Vec<u8>>, LsmError> {
+        Ok(match bound {
+            Bound::Included(values) => Bound::Included(self.encode_key(values)?),
+            Bound::Excluded(values) => Bound::Excluded(self.encode_key(values)?),
+            Bound::Unbounded => Bound::Unbounded,
+        })
+    }
+
+    fn decode(&self, value: Option<&[u8]>) -> Result<Option<Tuple<'a>>, LsmError> {
+        value
+            .map(|data| Ok(Tuple::read(self.schema, data)?))
+            .transpose()
+    }
+}
+
+impl State {
+    fn take_run_id(&mut self) -> u64 {
+        self.next_run_id += 1;
+
+        self.next_run_id - 1
+    }
+}
+
+/// Rows of a scan in key order.
+pub struct LsmScan<'a> {
+    schema: &'a Schema,
+    records: MergedRecords<'static>,
+    end: Bound<Vec<u8>>,
+}
+
+impl<'a> Iterator for LsmScan<'a> {
+    type Item = Result<Tuple<'a>, LsmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, value) = match self.records.next()? {
+                Ok(record) => record,
+                Err(err) => return Some(Err(err)),
+            };
+
+            let in_range = match &self.end {
+                Bound::Included(end) => &key <= end,
+                Bound::Excluded(end) => &key < end,
+                Bound::Unbounded => true,
+            };
+            if !in_range {
+                return None;
+            }
+
+            if let Some(value) = value {
+                return Some(Tuple::read(self.schema, &value).map_err(LsmError::from));
+            }
+        }
+    }
+}
+
+/// Records of many sorted sources merged in key order. Sources are ordered
+/// from the newest, of records with equal keys only the newest is returned.
+struct MergedRecords<'s> {
+    sources: Vec<RecordSource<'s>>,
+    heads: Vec<Option<Record>>,
+    started: bool,
+}
+
+impl<'s> MergedRecords<'s> {
+    fn new(sources: Vec<RecordSource<'s>>) -> MergedRecords<'s> {
+        MergedRecords {
+            heads: sources.iter().map(|_| None).collect(),
+            sources,
+            started: false,
+        }
+    }
+
+    fn advance(&mut self, source: usize) -> Result<(), LsmError> {
+        self.heads[source] = self.sources[source].next().transpose()?;
+
+        Ok(())
+    }
+}
+
+impl Iterator for MergedRecords<'_> {
+    type Item = Result<Record, LsmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            for source in 0..self.sources.len() {
+                if let Err(err) = self.advance(source) {
+                    return Some(Err(err));
+                }
+            }
+        }
+
+        // The first source with the smallest key is the newest of them.
+        let mut newest: Option<usize> = None;
+        for (source, head) in self.heads.iter().enumerate() {
+            if let Some((key, _)) = head
+                && newest.is_none_or(|newest| key < &self.heads[newest].as_ref().unwrap().0)
+            {
+                newest = Some(source);
+            }
+        }
+        let newest = newest?;
+
+        let record = self.heads[newest].take().unwrap();
+        for source in 0..self.sources.len() {
+            let older = source != newest
+                && self.heads[source]
+                    .as_ref()
+                    .is_some_and(|(key, _)| *key == record.0);
+
+            if (source == newest || older)
+                && let Err(err) = self.advance(source)
+            {
+                return Some(Err(err));
+            }
+        }
+
+        Some(Ok(record))
+    }
+}
+
+// A range `BTreeMap::range` would panic on.
+fn is_empty_range(start: &Bound<Vec<u8>>, end: &Bound<Vec<u8>>) -> bool {
+    match (start, end) {
+        (Bound::Included(start), Bound::Included(end)) => start > end,
+        (
+            Bound::Included(start) | Bound::Excluded(start),
+            Bound::Included(end) | Bound::Excluded(end),
+        ) => start >= end,
+        _ => false,
+    }
+}
+
+// Deletes run files the manifest doesn't list, left by a crash during a
+// flush or before the inputs of a compaction were deleted.
+fn remove_orphan_runs(path: &str, filename: &str, manifest: &Manifest) -> Result<(), LsmError> {
+    let prefix = format!("{}.", filename);
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(rest) = name.to_str().and_then(|name| name.strip_prefix(&prefix)) else {
+            continue;
+        };
+
+        // Segments of a run are named `<id>.sst`, `<id>.sst.1`, ...
+        let Some((id, segment)) = rest.split_once(".sst") else {
+            continue;
+        };
+        let Ok(id) = id.parse::<u64>() else {
+            continue;
+        };
+        if !segment.is_empty() && segment[1..].parse::<u64>().is_err() {
+            continue;
+        }
+
+        if !manifest.levels.iter().flatten().any(|&run| run == id) {
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,452 @@
+use std::collections::VecDeque;
+use std::ops::Bound;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use twox_hash::XxHash3_64;
+
+use crate::bloom_filter::BloomFilter;
+use crate::page::{HEADER_SIZE, Page, PageId, SLOT_SIZE};
+use crate::persist::{IoBackend, relation::RelationFile};
+use crate::schema::Schema;
+use crate::tuple::{Tuple, TupleValue};
+
+use super::{LsmError, Record};
+
+// A null value is a tombstone, the key was deleted.
+static RECORD_SCHEMA: LazyLock<Schema> =
+    LazyLock::new(|| Schema::parse(&[("key", "bytea not null"), ("value", "bytea")]).unwrap());
+static INDEX_SCHEMA: LazyLock<Schema> =
+    LazyLock::new(|| Schema::parse(&[("first_key", "bytea not null")]).unwrap());
+static BLOOM_SCHEMA: LazyLock<Schema> =
+    LazyLock::new(|| Schema::parse(&[("words", "bytea not null")]).unwrap());
+static FOOTER_SCHEMA: LazyLock<Schema> = LazyLock::new(|| {
+    Schema::parse(&[
+        ("data_pages", "bigint not null"),
+        ("index_pages", "bigint not null"),
+        ("bloom_pages", "bigint not null"),
+        ("record_count", "bigint not null"),
+        ("hash_count", "integer not null"),
+    ])
+    .unwrap()
+});
+
+// Room for the length and the null bitmap of a bloom tuple, so a chunk of
+// words always fits a page.
+const BLOOM_TUPLE_OVERHEAD: usize = 16;
+
+/// Name of the file of run `id` of the tree `filename`.
+pub fn run_filename(filename: &str, id: u64) -> String {
+    format!("{}.{}.sst", filename, id)
+}
+
+/// Whether a record of the key and value fits a page of `page_size`.
+pub fn fits_page(key: &[u8], value: Option<&[u8]>, page_size: usize) -> bool {
+    match record(key, value).to_data() {
+        Ok(data) => HEADER_SIZE + SLOT_SIZE + data.len() <= page_size,
+        Err(_) => false,
+    }
+}
+
+pub fn hash_key(key: &[u8]) -> u64 {
+    XxHash3_64::oneshot(key)
+}
+
+/// Immutable sorted run of records in a file of slotted pages.
+///
+/// Records are written in key order to data pages, which are followed by
+/// index pages with the first key of every data page, pages of the bloom
+/// filter of the keys and a footer page with their counts. The index and the
+/// filter are kept in memory, a lookup reads at most one data page.
+///
+/// A run replaced by a compaction is marked obsolete. Its file is deleted
+/// once the last scan reading it drops it.
+pub struct SsTable {
+    id: u64,
+    file: RelationFile,
+    data_pages: u64,
+    first_keys: Vec<Vec<u8>>,
+    bloom: BloomFilter,
+    obsolete: AtomicBool,
+}
+
+impl SsTable {
+    /// Writes a run of the records, which must be sorted by key and unique.
+    pub fn write(
+        path: &str,
+        filename: &str,
+        id: u64,
+        page_size: usize,
+        false_positive_rate: f64,
+        records: impl Iterator<Item = Result<Record, LsmError>>,
+    ) -> Result<SsTable, LsmError> {
+        let file = RelationFile::open(
+            path,
+            &run_filename(filename, id),
+            page_size,
+            IoBackend::Pread,
+        )?;
+
+        let mut page = Page::with_size(0, page_size);
+        let mut first_keys = vec![];
+        let mut hashes = vec![];
+
+        for record in records {
+            let (key, value) = record?;
+            let tuple = self::record(&key, value.as_deref());
+
+            if !page.has_space(&tuple)? {
+                if page.slots == 0 {
+                    return Err(LsmError::RecordTooBig(tuple.to_data()?.len()));
+                }
+
+                file.write_page(&page)?;
+                page = Page::with_size(page.id + 1, page_size);
+
+                if !page.has_space(&tuple)? {
+                    return Err(LsmError::RecordTooBig(tuple.to_data()?.len()));
+                }
+            }
+
+            if page.slots == 0 {
+                first_keys.push(key.clone());
+            }
+            page.write(&tuple)?;
+            hashes.push(hash_key(&key));
+        }
+        if page.slots > 0 {
+            file.write_page(&page)?;
+        }
+        let data_pages = first_keys.len() as u64;
+
+        let mut bloom = BloomFilter::new(hashes.len(), false_positive_rate);
+        for &hash in &hashes {
+            bloom.insert(hash);
+        }
+
+        let index_tuples = first_keys.iter().map(|key| Tuple {
+            schema: &INDEX_SCHEMA,
+            values: vec![TupleValue::Bytea(key.clone())],
+        });
+        let mut next_page_id = data_pages;
+        let index_pages = write_tuples(&file, &mut next_page_id, page_size, index_tuples)?;
+
+        let words_per_tuple = (page_size - HEADER_SIZE - SLOT_SIZE - BLOOM_TUPLE_OVERHEAD) / 8;
+        let bloom_tuples = bloom.words().chunks(words_per_tuple).map(|words| Tuple {
+            schema: &BLOOM_SCHEMA,
+            values: vec![TupleValue::Bytea(
+                words.iter().flat_map(|word| word.to_be_bytes()).collect(),
+            )],
+        });
+        let bloom_pages = write_tuples(&file, &mut next_page_id, page_size, bloom_tuples)?;
+
+        let mut footer = Page::with_size(next_page_id, page_size);
+        footer.write(&Tuple {
+            schema: &FOOTER_SCHEMA,
+            values: vec![
+                TupleValue::BigInt(data_pages as i64),
+                TupleValue::BigInt(index_pages as i64),
+                TupleValue::BigInt(bloom_pages as i64),
+                TupleValue::BigInt(hashes.len() as i64),
+                TupleValue::Integer(bloom.hash_count() as i32),
+            ],
+        })?;
+        file.write_page(&footer)?;
+        file.sync()?;
+
+        Ok(SsTable {
+            id,
+            file,
+            data_pages,
+            first_keys,
+            bloom,
+            obsolete: AtomicBool::new(false),
+        })
+    }
+
+    /// Opens a run written by `write`, reading its index and bloom filter.
+    pub fn open(
+        path: &str,
+        filename: &str,
+        id: u64,
+        page_size: usize,
+    ) -> Result<SsTable, LsmError> {
+        let file = RelationFile::open(
+            path,
+            &run_filename(filename, id),
+            page_size,
+            IoBackend::Pread,
+        )?;
+
+        let page_count = file.page_count();
+        if page_count == 0 {
+            return Err(LsmError::Corrupted("A run has no footer"));
+        }
+
+        let mut page = Page::with_size(0, page_size);
+        let footer = read_tuples(&file, &mut page, page_count - 1, &FOOTER_SCHEMA)?;
+        let [
+            TupleValue::BigInt(data_pages),
+            TupleValue::BigInt(index_pages),
+            TupleValue::BigInt(bloom_pages),
+            TupleValue::BigInt(_),
+            TupleValue::Integer(hash_count),
+        ] = footer.first().map(|values| &values[..]).unwrap_or_default()
+        else {
+            return Err(LsmError::Corrupted("Invalid run footer"));
+        };
+        let (Ok(data_pages), Ok(index_pages), Ok(bloom_pages), Ok(hash_count)) = (
+            u64::try_from(*data_pages),
+            u64::try_from(*index_pages),
+            u64::try_from(*bloom_pages),
+            u32::try_from(*hash_count),
+        ) else {
+            return Err(LsmError::Corrupted("Invalid run footer"));
+        };
+        let footer_page = data_pages
+            .checked_add(index_pages)
+            .and_then(|pages| pages.checked_add(bloom_pages));
+        if footer_page != Some(page_count - 1) {
+            return Err(LsmError::Corrupted("Run page counts don't match its file"));
+        }
+
+        let mut first_keys = vec![];
+        for page_id in data_pages..data_pages + index_pages {
+            for values in read_tuples(&file, &mut page, page_id, &INDEX_SCHEMA)? {
+                first_keys.push(bytea(&values[0])?.to_vec());
+            }
+        }
+        if first_keys.len() as u64 != data_pages {
+            return Err(LsmError::Corrupted(
+                "Run index doesn't match its data pages",
+            ));
+        }
+
+        let mut words = vec![];
+        for page_id in data_pages + index_pages..page_count - 1 {
+            for values in read_tuples(&file, &mut page, page_id, &BLOOM_SCHEMA)? {
+                words.extend(
+                    bytea(&values[0])?
+                        .chunks_exact(8)
+                        .map(|word| u64::from_be_bytes(word.try_into().unwrap())),
+                );
+            }
+        }
+        if words.is_empty() {
+            return Err(LsmError::Corrupted("Run has no bloom filter"));
+        }
+
+        Ok(SsTable {
+            id,
+            file,
+            data_pages,
+            first_keys,
+            bloom: BloomFilter::from_words(words, hash_count),
+            obsolete: AtomicBool::new(false),
+        })
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Size of the file in bytes, without its header.
+    pub fn size(&self) -> u64 {
+        self.file.page_count() * self.file.page_size() as u64
+    }
+
+    /// Value of the key, `Some(None)` if the run has its tombstone and
+    /// `None` if the run doesn't have the key at all.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Option<Vec<u8>>>, LsmError> {
+        if !self.bloom.may_contain(hash_key(key)) {
+            return Ok(None);
+        }
+
+        // Last page whose first key is not after the key.
+        let Some(page_id) = self
+            .first_keys
+            .partition_point(|first_key| first_key.as_slice() <= key)
+            .checked_sub(1)
+        else {
+            return Ok(None);
+        };
+
+        let mut page = Page::with_size(page_id as PageId, self.file.page_size());
+        for values in read_tuples(&self.file, &mut page, page_id as PageId, &RECORD_SCHEMA)? {
+            let (record_key, value) = decode_record(values)?;
+            if record_key == key {
+                return Ok(Some(value));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Records in key order, starting at `start`.
+    pub fn records(self: &Arc<SsTable>, start: Bound<&[u8]>) -> SsTableRecords {
+        let (page_id, skip) = match start {
+            Bound::Unbounded => (0, None),
+            Bound::Included(key) | Bound::Excluded(key) => {
+                let page_id = self
+                    .first_keys
+                    .partition_point(|first_key| first_key.as_slice() <= key)
+                    .saturating_sub(1);
+
+                (page_id as PageId, Some(start.map(<[u8]>::to_vec)))
+            }
+        };
+
+        SsTableRecords {
+            run: Arc::clone(self),
+            page_id,
+            skip,
+            records: VecDeque::new(),
+        }
+    }
+
+    /// Deletes the file once the run is dropped.
+    pub fn mark_obsolete(&self) {
+        self.obsolete.store(true, Ordering::Release);
+    }
+}
+
+impl Drop for SsTable {
+    fn drop(&mut self) {
+        // A file left behind is deleted when the tree is opened next time.
+        if self.obsolete.load(Ordering::Acquire) {
+            let _ = self.file.remove();
+        }
+    }
+}
+
+/// Records of a run read one page at a time.
+pub struct SsTableRecords {
+    run: Arc<SsTable>,
+    page_id: PageId,
+    // Records before this bound are skipped.
+    skip: Option<Bound<Vec<u8>>>,
+    records: VecDeque<Record>,
+}
+
+impl Iterator for SsTableRecords {
+    type Item = Result<Record, LsmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.records.is_empty() {
+            if self.page_id >= self.run.data_pages {
+                return None;
+            }
+
+            let mut page = Page::with_size(self.page_id, self.run.file.page_size());
+            let tuples = match read_tuples(&self.run.file, &mut page, self.page_id, &RECORD_SCHEMA)
+            {
+                Ok(tuples) => tuples,
+                Err(err) => {
+                    self.page_id = self.run.data_pages;
+                    return Some(Err(err));
+                }
+            };
+            self.page_id += 1;
+
+            for values in tuples {
+                match decode_record(values) {
+                    Ok(record) => self.records.push_back(record),
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+
+            if let Some(skip) = self.skip.take() {
+                self.records.retain(|(key, _)| match &skip {
+                    Bound::Included(start) => key >= start,
+                    Bound::Excluded(start) => key > start,
+                    Bound::Unbounded => true,
+                });
+            }
+        }
+
+        self.records.pop_front().map(Ok)
+    }
+}
+
+fn record<'k>(key: &[u8], value: Option<&[u8]>) -> Tuple<'k> {
+    Tuple {
+        schema: &RECORD_SCHEMA,
+        values: vec![
+            TupleValue::Bytea(key.to_vec()),
+            value.map_or(TupleValue::Null, |value| TupleValue::Bytea(value.to_vec())),
+        ],
+    }
+}
+
+fn decode_record(mut values: Vec<TupleValue>) -> Result<Record, LsmError> {
+    let value = match values.pop() {
+        Some(TupleValue::Null) => None,
+        Some(TupleValue::Bytea(value)) => Some(value),
+        _ => return Err(LsmError::Corrupted("Invalid run record")),
+    };
+    let key = match values.pop() {
+        Some(TupleValue::Bytea(key)) => key,
+        _ => return Err(LsmError::Corrupted("Invalid run record")),
+    };
+
+    Ok((key, value))
+}
+
+fn bytea(value: &TupleValue) -> Result<&[u8], LsmError> {
+    match value {
+        TupleValue::Bytea(bytes) => Ok(bytes),
+        _ => Err(LsmError::Corrupted("Bytea column expected")),
+    }
+}
+
+// Fills pages with the tuples from `next_page_id` on, returns the number of pages.
+fn write_tuples<'t>(
+    file: &RelationFile,
+    next_page_id: &mut PageId,
+    page_size: usize,
+    tuples: impl Iterator<Item = Tuple<'t>>,
+) -> Result<u64, LsmError> {
+    let first_page_id = *next_page_id;
+    let mut page = Page::with_size(first_page_id, page_size);
+
+    for tuple in tuples {
+        if !page.has_space(&tuple)? {
+            if page.slots == 0 {
+                return Err(LsmError::RecordTooBig(tuple.to_data()?.len()));
+            }
+
+            file.write_page(&page)?;
+            page = Page::with_size(page.id + 1, page_size);
+        }
+
+        page.write(&tuple)?;
+    }
+    if page.slots > 0 {
+        file.write_page(&page)?;
+        *next_page_id = page.id + 1;
+    }
+
+    Ok(*next_page_id - first_page_id)
+}
+
+fn read_tuples(
+    file: &RelationFile,
+    page: &mut Page,
+    page_id: PageId,
+    schema: &Schema,
+) -> Result<Vec<Vec<TupleValue>>, LsmError> {
+    page.id = page_id;
+    if file.read_page(page_id, page).is_err() {
+        return Err(LsmError::PageNotRead(page_id));
+    }
+    if page.refresh_metadata().is_err() {
+        return Err(LsmError::Corrupted(
+            "Run page slots don't fit into the page",
+        ));
+    }
+
+    page.read_iterator(schema)
+        .map(|tuple| Ok(tuple?.values))
+        .collect()
+}
@@ -1,24 +1,62 @@
 #![cfg_attr(test, allow(dead_code))]
-mod buffer_pool;
-mod page;
-mod persist;
-mod tuple;
-mod util;
 
-use persist::Reader;
+use std::ops::Bound;
+use std::sync::LazyLock;
 
-use crate::buffer_pool::buffer_pool::BufferPool;
+use naive_db::buffer_pool::buffer_pool::{BufferPool, GetPageError};
+use naive_db::catalog::FIRST_USER_RELATION_ID;
+use naive_db::heap_file::RowId;
+use naive_db::index::btree::BTree;
+use naive_db::page::{PageId, PageKey, RelationId, SIZE};
+use naive_db::persist::{IoBackend, MmapReader, relation::RelationFile};
+use naive_db::schema::{DataType, Schema};
+use naive_db::tuple::{KeyColumn, TupleRef, TupleValue, decode_key, encode_key};
+use naive_db::zone_map::ZoneMap;
+
+const PREFETCH_BATCH_SIZE: u64 = 32;
+const THREADS: u64 = 8;
+// Ids below the first user one are reserved for the catalog.
+const RELATION_ID: RelationId = FIRST_USER_RELATION_ID;
+const INDEX_RELATION_ID: RelationId = FIRST_USER_RELATION_ID + 1;
+const PAGES_PER_ZONE: u64 = 4;
+
+// Index keys of the data file are ids.
+static ID_KEY: LazyLock<[KeyColumn; 1]> = LazyLock::new(|| [KeyColumn::new(DataType::Integer)]);
+
+// Tuples of the data file are an id and a name. Neither is nullable, so the
+// tuples have no null bitmap.
+static DATA_SCHEMA: LazyLock<Schema> = LazyLock::new(|| {
+    Schema::parse(&[("id", "integer not null"), ("name", "varchar not null")]).unwrap()
+});
 
 fn main() {
-    let reader = Reader::new("./data", "simple.data");
-    let page_number = reader.page_count();
-    let pool = BufferPool::new(2 ^ 17, reader);
-    let pool_ref = &pool;
+    let relation_file = RelationFile::open(
+        "./data",
+        "simple.data",
+        SIZE,
+        IoBackend::io_uring_or_pread(),
+    )
+    .expect("Cannot open a data file");
+    let page_number = relation_file.page_count();
+
+    let pool = BufferPool::new(1 << 17, SIZE);
+    pool.add_relation(RELATION_ID, relation_file)
+        .expect("Cannot add a data file to the buffer pool");
+    // The data file is only ever read while the program runs.
+    let mmap_reader =
+        unsafe { MmapReader::new("./data", "simple.data") }.expect("Cannot map a file for reading");
+    let index = BTree::open(&pool, INDEX_RELATION_ID, "./data", "simple.data.id_index")
+        .expect("Cannot open an index file");
+    let mut zone_map = ZoneMap::open("./data", "simple.data", &DATA_SCHEMA, &[0], PAGES_PER_ZONE)
+        .expect("Cannot open a zone map");
 
     loop {
         println!("Select an action:");
         println!("1 - continue");
         println!("2 - show number of pages");
+        println!("3 - continue using mmap");
+        println!("4 - continue using an index");
+        println!("5 - continue using a zone map");
 
         let mut input = String::new();
         std::io::stdin()
@@ -31,32 +69,7 @@ fn main() {
 
                 let start_time = std::time::Instant::now();
 
-                std::thread::scope(|s| {
-                    for j in 0..8 {
-                        s.spawn(move || {
-                            for i in j * page_number / 8..(j + 1) * page_number / 8 {
-                                if i >= page_number {
-                                    break;
-                                }
-
-                                let result = pool_ref.get(i);
-                                let Ok(page) = result else {
-                                    println!("Page cant be read {:?}", result.err().unwrap());
-                                    panic!("");
-                                };
-
-                                for tuple_data in page.get().read_iterator_raw() {
-                                    let id =
-                                        i32::from_be_bytes(tuple_data[0..4].try_into().unwrap());
-
-                                    if id < 140651032 && id > 140641012 {
-                                        println!("Found in page {}. id: {}", i, id);
-                                    }
-                                }
-                            }
-                        });
-                    }
-                });
+                search_buffer_pool(&pool, page_number);
 
                 let duration = start_time.elapsed();
                 println!("Time taken: {:?}", duration);
@@ -64,9 +77,270 @@ fn main() {
             "2" => {
                 println!("There are {} pages", page_number);
             }
+            "3" => {
+                println!("Searching using mmap...");
+
+                let start_time = std::time::Instant::now();
+
+                search_mmap(&mmap_reader);
+
+                let duration = start_time.elapsed();
+                println!("Time taken: {:?}", duration);
+            }
+            "4" => {
+                // The data file never changes, the index is built on the first search.
+                if index_is_empty(&index) {
+                    println!("Building an index...");
+
+                    let start_time = std::time::Instant::now();
+
+                    build_index(&pool, &index, page_number);
+
+                    let duration = start_time.elapsed();
+                    println!("Time taken: {:?}", duration);
+                }
+
+                println!("Searching using an index...");
+
+                let start_time = std::time::Instant::now();
+
+                search_index(&index);
+
+                let duration = start_time.elapsed();
+                println!("Time taken: {:?}", duration);
+            }
+            "5" => {
+                // Like the index, the zone map is built once for the data file.
+                if zone_map.page_count() != page_number {
+                    println!("Building a zone map...");
+
+                    let start_time = std::time::Instant::now();
+
+                    build_zone_map(&pool, &mut zone_map, page_number);
+
+                    let duration = start_time.elapsed();
+                    println!("Time taken: {:?}", duration);
+                }
+
+                println!("Searching using a zone map...");
+
+                let start_time = std::time::Instant::now();
+
+                search_zone_map(&pool, &zone_map, page_number);
+
+                let duration = start_time.elapsed();
+                println!("Time taken: {:?}", duration);
+            }
             _ => {
                 println!("Invalid input, please try again.");
             }
         }
     }
 }
+
+fn search_buffer_pool<'a>(pool: &'a BufferPool<'a>, page_number: u64) {
+    std::thread::scope(|s| {
+        for j in 0..THREADS {
+            s.spawn(move || {
+                let first_page = j * page_number / THREADS;
+                let last_page = ((j + 1) * page_number / THREADS).min(page_number);
+
+                for i in first_page..last_page {
+                    if (i - first_page).is_multiple_of(PREFETCH_BATCH_SIZE) {
+                        let batch_end = (i + PREFETCH_BATCH_SIZE).min(last_page);
+                        let batch = (i..batch_end)
+                            .map(|page_id| PageKey::new(RELATION_ID, page_id))
+                            .collect::<Vec<_>>();
+
+                        if let Err(err) = pool.prefetch(&batch) {
+                            println!("Pages cant be prefetched {:?}", err);
+                        }
+                    }
+
+                    let page = match pool.get(PageKey::new(RELATION_ID, i)) {
+                        Ok(page) => page,
+                        // A corrupted page is skipped, the scan goes on with the next one.
+                        Err(GetPageError::CorruptedPage(key)) => {
+                            println!("Page {} is corrupted", key.page_id);
+                            continue;
+                        }
+                        Err(err) => {
+                            println!("Page cant be read {:?}", err);
+                            panic!("");
+                        }
+                    };
+
+                    search_tuples(i, page.get().read_iterator_ref(&DATA_SCHEMA));
+                }
+            });
+        }
+    });
+}
+
+fn search_mmap(reader: &MmapReader) {
+    let page_number = reader.page_count();
+
+    std::thread::scope(|s| {
+        for j in 0..THREADS {
+            s.spawn(move || {
+                let first_page = j * page_number / THREADS;
+                let last_page = ((j + 1) * page_number / THREADS).min(page_number);
+
+                for i in first_page..last_page {
+                    let Some(page) = reader.page(i) else {
+                        panic!("Page {} cant be read", i);
+                    };
+
+                    search_tuples(i, page.read_iterator_ref(&DATA_SCHEMA));
+                }
+            });
+        }
+    });
+}
+
+fn index_is_empty(index: &BTree) -> bool {
+    match index.range(Bound::Unbounded, Bound::Unbounded) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(err) => panic!("Index cant be read {:?}", err),
+    }
+}
+
+fn build_index<'a>(pool: &'a BufferPool<'a>, index: &BTree<'a>, page_number: u64) {
+    std::thread::scope(|s| {
+        for j in 0..THREADS {
+            s.spawn(move || {
+                let first_page = j * page_number / THREADS;
+                let last_page = ((j + 1) * page_number / THREADS).min(page_number);
+
+                for i in first_page..last_page {
+                    let page = match pool.get(PageKey::new(RELATION_ID, i)) {
+                        Ok(page) => page,
+                        Err(GetPageError::CorruptedPage(key)) => {
+                            println!("Page {} is corrupted", key.page_id);
+                            continue;
+                        }
+                        Err(err) => {
+                            println!("Page cant be read {:?}", err);
+                            panic!("");
+                        }
+                    };
+
+                    for (slot_id, data) in page.get().read_iterator_raw_with_slots() {
+                        let Ok(Some(id)) = TupleRef::new(&DATA_SCHEMA, data).get_i32(0) else {
+                            println!("Bad tuple in page {}", i);
+                            continue;
+                        };
+
+                        if let Err(err) = index.insert(&id_key(id), RowId::new(i, slot_id)) {
+                            println!("Id {} cant be indexed {:?}", id, err);
+                        }
+                    }
+                }
+            });
+        }
+    });
+}
+
+fn search_index(index: &BTree) {
+    let (start, end) = (id_key(140641012), id_key(140651032));
+
+    let entries = match index.range(Bound::Excluded(&start), Bound::Excluded(&end)) {
+        Ok(entries) => entries,
+        Err(err) => panic!("Index cant be read {:?}", err),
+    };
+
+    for entry in entries {
+        let Ok((key, row_id)) = entry else {
+            println!("Index cant be read {:?}", entry.err().unwrap());
+            return;
+        };
+
+        match decode_key(&*ID_KEY, &key).as_deref() {
+            Ok([TupleValue::Integer(id)]) => {
+                println!("Found in page {}. id: {}", row_id.page_id, id)
+            }
+            _ => println!("Bad index key {:?}", key),
+        }
+    }
+}
+
+fn build_zone_map<'a>(pool: &'a BufferPool<'a>, zone_map: &mut ZoneMap, page_number: u64) {
+    for i in 0..page_number {
+        let page = match pool.get(PageKey::new(RELATION_ID, i)) {
+            Ok(page) => page,
+            Err(GetPageError::CorruptedPage(key)) => {
+                println!("Page {} is corrupted", key.page_id);
+                continue;
+            }
+            Err(err) => {
+                println!("Page cant be read {:?}", err);
+                panic!("");
+            }
+        };
+
+        zone_map.cover(i);
+        for tuple in page.get().read_iterator_ref(&DATA_SCHEMA) {
+            let Ok(id) = tuple.get(0) else {
+                println!("Bad tuple in page {}", i);
+                continue;
+            };
+
+            zone_map.update(i, &[id]);
+        }
+    }
+
+    if let Err(err) = zone_map.flush() {
+        println!("Zone map cant be written {:?}", err);
+    }
+}
+
+fn search_zone_map<'a>(pool: &'a BufferPool<'a>, zone_map: &ZoneMap, page_number: u64) {
+    let (start, end) = (
+        TupleValue::Integer(140641012),
+        TupleValue::Integer(140651032),
+    );
+    let pages = (0..page_number)
+        .filter(|&i| zone_map.may_match(i, 0, Bound::Excluded(&start), Bound::Excluded(&end)))
+        .collect::<Vec<_>>();
+    println!("{} of {} pages may match", pages.len(), page_number);
+
+    std::thread::scope(|s| {
+        for chunk in pages.chunks(pages.len().div_ceil(THREADS as usize).max(1)) {
+            s.spawn(move || {
+                for &i in chunk {
+                    let page = match pool.get(PageKey::new(RELATION_ID, i)) {
+                        Ok(page) => page,
+                        Err(GetPageError::CorruptedPage(key)) => {
+                            println!("Page {} is corrupted", key.page_id);
+                            continue;
+                        }
+                        Err(err) => {
+                            println!("Page cant be read {:?}", err);
+                            panic!("");
+                        }
+                    };
+
+                    search_tuples(i, page.get().read_iterator_ref(&DATA_SCHEMA));
+                }
+            });
+        }
+    });
+}
+
+fn id_key(id: i32) -> Vec<u8> {
+    encode_key(&*ID_KEY, &[TupleValue::Integer(id)]).unwrap()
+}
+
+fn search_tuples<'a>(page_id: PageId, tuples: impl Iterator<Item = TupleRef<'a>>) {
+    for tuple in tuples {
+        // A corrupted tuple is reported instead of stopping the scan.
+        let Ok(Some(id)) = tuple.get_i32(0) else {
+            println!("Bad tuple in page {}", page_id);
+            continue;
+        };
+
+        if id < 140651032 && id > 140641012 {
+            println!("Found in page {}. id: {}", page_id, id);
+        }
+    }
+}
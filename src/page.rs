@@ -1,14 +1,18 @@
-use crate::tuple::{Tuple, TupleToDataError, TupleValue};
+use crate::schema::{Schema, SchemaError};
+use crate::tuple::{Projection, Tuple, TupleDecodeError, TupleRef, TupleToDataError, TupleValue};
 use std::mem;
 
 pub const SIZE: usize = 1024 * 8;
+// Slot ids and tuple lengths are u16, so they can address pages up to 64 KiB.
+pub const SUPPORTED_SIZES: [usize; 4] = [1024 * 4, 1024 * 8, 1024 * 16, 1024 * 32];
 
 // version + number of slots
 type Header = (u16, u16);
-const HEADER_SIZE: usize = mem::size_of::<Header>();
+pub const HEADER_SIZE: usize = mem::size_of::<Header>();
 
 pub type PageId = u64;
-type SlotId = u16;
+pub type RelationId = u32;
+pub type SlotId = u16;
 type TupleLength = u16;
 
 pub struct Slot {
@@ -17,7 +21,7 @@ pub struct Slot {
     pub is_thumbstone: u8,
 }
 
-const SLOT_SIZE: usize = 5;
+pub const SLOT_SIZE: usize = 5;
 
 impl Slot {
     pub fn new(id: SlotId, length: TupleLength) -> Slot {
@@ -52,55 +56,163 @@ impl Slot {
     pub fn length(&self) -> usize {
         self.length as usize
     }
+
+    pub fn is_deleted(&self) -> bool {
+        self.is_thumbstone != 0
+    }
+}
+
+/// Identity of a page in a buffer pool shared by many relations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PageKey {
+    pub relation_id: RelationId,
+    pub page_id: PageId,
+}
+
+impl PageKey {
+    pub fn new(relation_id: RelationId, page_id: PageId) -> PageKey {
+        PageKey {
+            relation_id,
+            page_id,
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; 12] {
+        let mut bytes = [0; 12];
+
+        bytes[0..4].copy_from_slice(&self.relation_id.to_be_bytes());
+        bytes[4..12].copy_from_slice(&self.page_id.to_be_bytes());
+
+        bytes
+    }
+}
+
+/// Slots of a page that don't fit into it, read from a corrupted page.
+#[derive(Debug, PartialEq)]
+pub struct CorruptedPageError;
+
+#[derive(Debug)]
+pub enum UpdateTupleError {
+    TupleToDataError(TupleToDataError),
+    TupleNotFound,
+    NotEnoughSpace,
+}
+
+impl From<TupleToDataError> for UpdateTupleError {
+    fn from(err: TupleToDataError) -> UpdateTupleError {
+        UpdateTupleError::TupleToDataError(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReadTupleError {
+    TupleNotFound,
+    TupleDecodeError(TupleDecodeError),
+}
+
+impl From<TupleDecodeError> for ReadTupleError {
+    fn from(err: TupleDecodeError) -> ReadTupleError {
+        ReadTupleError::TupleDecodeError(err)
+    }
+}
+
+pub fn is_supported_size(size: usize) -> bool {
+    SUPPORTED_SIZES.contains(&size)
 }
 
 #[derive(Debug)]
 pub struct Page {
     pub id: PageId,
-    pub data: [u8; SIZE],
+    pub relation_id: RelationId,
+    pub data: Box<[u8]>,
     pub free_space: usize,
     pub slots: usize,
 }
 
 impl<'a> Page {
     pub fn new(page_id: PageId) -> Page {
-        let mut data: [u8; SIZE] = [Default::default(); SIZE];
+        Page::with_size(page_id, SIZE)
+    }
+
+    pub fn with_size(page_id: PageId, size: usize) -> Page {
+        assert!(is_supported_size(size), "Unsupported page size {}", size);
+
+        let mut data = vec![0; size].into_boxed_slice();
 
         data[0..2].copy_from_slice(&[0, 1]);
         data[2..4].copy_from_slice(&[0, 0]);
 
         Page {
             id: page_id,
-            data: data,
-            free_space: SIZE - HEADER_SIZE,
+            relation_id: 0,
+            data,
+            free_space: size - HEADER_SIZE,
             slots: 0,
         }
     }
 
-    pub fn refresh_metadata(&mut self) {
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn key(&self) -> PageKey {
+        PageKey::new(self.relation_id, self.id)
+    }
+
+    /// Turns a reused frame into an empty page.
+    pub fn reset(&mut self, key: PageKey) {
+        self.data.fill(0);
+        self.data[0..2].copy_from_slice(&[0, 1]);
+
+        self.id = key.page_id;
+        self.relation_id = key.relation_id;
+        self.free_space = self.size() - HEADER_SIZE;
+        self.slots = 0;
+    }
+
+    /// Reads the slot count and the free space from the page bytes. A page
+    /// whose slots don't fit into it is left without slots and free space.
+    pub fn refresh_metadata(&mut self) -> Result<(), CorruptedPageError> {
         let slots = u16::from_be_bytes([self.data[2], self.data[3]]) as usize;
         let data_size = self.data[HEADER_SIZE..]
-            .chunks(SLOT_SIZE)
+            .chunks_exact(SLOT_SIZE)
             .take(slots)
-            .fold(0, |acc, s| acc + Slot::read(s).length());
+            .map(|s| Slot::read(s).length())
+            .sum::<usize>();
+
+        let free_space = (self.size() - HEADER_SIZE)
+            .checked_sub(slots * SLOT_SIZE)
+            .and_then(|space| space.checked_sub(data_size));
+        let Some(free_space) = free_space else {
+            self.free_space = 0;
+            self.slots = 0;
+
+            return Err(CorruptedPageError);
+        };
 
-        self.free_space = SIZE - HEADER_SIZE - slots * SLOT_SIZE - data_size;
+        self.free_space = free_space;
         self.slots = slots;
+
+        Ok(())
     }
 
-    pub fn from_data(page_id: PageId, data: [u8; SIZE]) -> Page {
-        let slots = u16::from_be_bytes([data[2], data[3]]) as usize;
-        let data_size = data[HEADER_SIZE..]
-            .chunks(SLOT_SIZE)
-            .take(slots)
-            .fold(0, |acc, s| acc + Slot::read(s).length());
+    pub fn from_data(page_id: PageId, data: Box<[u8]>) -> Result<Page, CorruptedPageError> {
+        assert!(
+            is_supported_size(data.len()),
+            "Unsupported page size {}",
+            data.len()
+        );
 
-        Page {
+        let mut page = Page {
             id: page_id,
-            data: data,
-            free_space: SIZE - HEADER_SIZE - slots * SLOT_SIZE - data_size,
-            slots,
-        }
+            relation_id: 0,
+            data,
+            free_space: 0,
+            slots: 0,
+        };
+        page.refresh_metadata()?;
+
+        Ok(page)
     }
 
     pub fn has_space(&self, tuple: &Tuple) -> Result<bool, TupleToDataError> {
@@ -139,66 +251,263 @@ impl<'a> Page {
         Ok(slot)
     }
 
-    pub fn read(&'a self, slot_id: SlotId, types: &'a [&str]) -> Result<Tuple<'a>, &'a str> {
-        let mut data_offset = 0;
-        let slot = self.data[HEADER_SIZE..]
-            .chunks(SLOT_SIZE)
-            .take(self.slots)
-            .map(|slot_data| Slot::read(slot_data))
-            .find(|slot| {
-                data_offset += slot.length();
+    /// Replaces a tuple keeping its slot id. Tuples stored after it are
+    /// shifted when the new tuple has a different length.
+    pub fn update(&mut self, slot_id: SlotId, tuple: &Tuple) -> Result<Slot, UpdateTupleError> {
+        let tuple_data = tuple.to_data()?;
 
-                slot.id == slot_id
+        let Some(mut slot) = self.slot(slot_id).filter(|slot| !slot.is_deleted()) else {
+            return Err(UpdateTupleError::TupleNotFound);
+        };
+
+        if tuple_data.len() > slot.length() + self.free_space {
+            return Err(UpdateTupleError::NotEnoughSpace);
+        }
+
+        let (data_start, data_end) = (0..self.slots)
+            .map(|slot_id| self.slot(slot_id as SlotId).unwrap())
+            .fold((self.size(), self.size()), |(start, end), s| {
+                if s.id <= slot.id {
+                    (start - s.length(), start)
+                } else {
+                    (start - s.length(), end)
+                }
             });
+        let tuple_start = data_end - slot.length();
+        let new_tuple_start = data_end - tuple_data.len();
+
+        // Tuples of the slots after this one are stored below it.
+        self.data.copy_within(
+            data_start..tuple_start,
+            data_start + slot.length() - tuple_data.len(),
+        );
+        self.data[new_tuple_start..data_end].copy_from_slice(&tuple_data);
+
+        self.free_space = self.free_space + slot.length() - tuple_data.len();
+        slot.length = tuple_data.len() as TupleLength;
+        self.write_slot(&slot);
+
+        Ok(slot)
+    }
+
+    /// Marks a tuple as deleted. Its bytes stay on the page until `compact`.
+    pub fn delete(&mut self, slot_id: SlotId) -> Result<(), &'static str> {
+        let Some(mut slot) = self.slot(slot_id) else {
+            return Err("Cannot delete tuple");
+        };
+
+        if slot.is_deleted() {
+            return Err("Tuple is already deleted");
+        }
 
-        match slot {
-            Some(s) => {
-                let data_length = self.data.len();
+        slot.is_thumbstone = 1;
+        self.write_slot(&slot);
 
-                Ok(Tuple::read(
-                    &types,
-                    &self.data[data_length - data_offset..data_length - data_offset + s.length()],
-                ))
+        Ok(())
+    }
+
+    /// Reclaims the space of deleted tuples. A deleted slot stays on the page
+    /// with zero length, so ids of the other tuples don't change.
+    pub fn compact(&mut self) {
+        let mut read_end = self.size();
+        let mut write_end = self.size();
+
+        for slot_id in 0..self.slots {
+            let mut slot = self.slot(slot_id as SlotId).unwrap();
+            let tuple_start = read_end - slot.length();
+
+            if slot.is_deleted() {
+                slot.length = 0;
+                self.write_slot(&slot);
+            } else {
+                self.data
+                    .copy_within(tuple_start..read_end, write_end - slot.length());
+                write_end -= slot.length();
             }
-            None => Err("Cannot read tuple"),
+
+            read_end = tuple_start;
         }
+
+        let slots_end = HEADER_SIZE + self.slots * SLOT_SIZE;
+        self.data[slots_end..write_end].fill(0);
+
+        self.free_space = write_end - slots_end;
     }
 
-    pub fn read_iterator(&'a self) -> impl Iterator<Item = impl Fn(&'a [&str]) -> Tuple<'a>> {
-        let mut data_offset = 0;
-        let data_length = self.data.len();
+    fn slot(&self, slot_id: SlotId) -> Option<Slot> {
+        if slot_id as usize >= self.slots {
+            return None;
+        }
 
-        self.data[HEADER_SIZE..]
-            .chunks(SLOT_SIZE)
-            .take(self.slots)
-            .map(move |slot_data| {
-                let slot = Slot::read(slot_data);
+        let slot_start = HEADER_SIZE + slot_id as usize * SLOT_SIZE;
 
-                data_offset += slot.length();
+        Some(Slot::read(&self.data[slot_start..slot_start + SLOT_SIZE]))
+    }
 
-                move |types: &'a [&str]| {
-                    Tuple::read(
-                        &types,
-                        &self.data
-                            [data_length - data_offset..data_length - data_offset + slot.length()],
-                    )
-                }
-            })
+    fn write_slot(&mut self, slot: &Slot) {
+        let slot_start = HEADER_SIZE + slot.id as usize * SLOT_SIZE;
+
+        self.data[slot_start..slot_start + SLOT_SIZE].copy_from_slice(&slot.to_data());
+    }
+
+    pub fn view(&'a self) -> PageView<'a> {
+        PageView {
+            id: self.id,
+            data: &self.data,
+            slots: self.slots,
+        }
+    }
+
+    pub fn read<'t>(
+        &self,
+        slot_id: SlotId,
+        schema: &'t Schema,
+    ) -> Result<Tuple<'t>, ReadTupleError> {
+        self.view().read(slot_id, schema)
+    }
+
+    pub fn read_iterator(
+        &'a self,
+        schema: &'a Schema,
+    ) -> impl Iterator<Item = Result<Tuple<'a>, TupleDecodeError>> {
+        self.view().read_iterator(schema)
+    }
+
+    pub fn read_iterator_projected(
+        &'a self,
+        schema: &'a Schema,
+        columns: &[usize],
+    ) -> Result<impl Iterator<Item = Result<Vec<TupleValue>, TupleDecodeError>>, SchemaError> {
+        self.view().read_iterator_projected(schema, columns)
+    }
+
+    pub fn read_ref(
+        &'a self,
+        slot_id: SlotId,
+        schema: &'a Schema,
+    ) -> Result<TupleRef<'a>, ReadTupleError> {
+        self.view().read_ref(slot_id, schema)
+    }
+
+    pub fn read_iterator_ref(&'a self, schema: &'a Schema) -> impl Iterator<Item = TupleRef<'a>> {
+        self.view().read_iterator_ref(schema)
     }
 
     pub fn read_iterator_raw(&'a self) -> impl Iterator<Item = &'a [u8]> {
+        self.view().read_iterator_raw()
+    }
+
+    pub fn read_iterator_raw_with_slots(&'a self) -> impl Iterator<Item = (SlotId, &'a [u8])> {
+        self.view().read_iterator_raw_with_slots()
+    }
+}
+
+/// Read-only page that borrows its bytes, e.g. from a buffer pool frame or
+/// straight from a memory mapped file.
+#[derive(Debug, Clone, Copy)]
+pub struct PageView<'a> {
+    pub id: PageId,
+    pub data: &'a [u8],
+    pub slots: usize,
+}
+
+impl<'a> PageView<'a> {
+    pub fn from_data(page_id: PageId, data: &'a [u8]) -> PageView<'a> {
+        PageView {
+            id: page_id,
+            data,
+            slots: u16::from_be_bytes([data[2], data[3]]) as usize,
+        }
+    }
+
+    pub fn read<'t>(
+        self,
+        slot_id: SlotId,
+        schema: &'t Schema,
+    ) -> Result<Tuple<'t>, ReadTupleError> {
+        Ok(Tuple::read(schema, self.tuple_data(slot_id)?)?)
+    }
+
+    /// Tuple that decodes its columns from the page bytes only when they are read.
+    pub fn read_ref(
+        self,
+        slot_id: SlotId,
+        schema: &'a Schema,
+    ) -> Result<TupleRef<'a>, ReadTupleError> {
+        Ok(TupleRef::new(schema, self.tuple_data(slot_id)?))
+    }
+
+    /// Decodes live tuples, a tuple that cannot be decoded is returned as an
+    /// error and the iteration goes on.
+    pub fn read_iterator(
+        self,
+        schema: &'a Schema,
+    ) -> impl Iterator<Item = Result<Tuple<'a>, TupleDecodeError>> {
+        self.slot_data().filter(|(slot, _)| !slot.is_deleted()).map(
+            move |(slot, data)| match data {
+                Some(data) => Tuple::read(schema, data),
+                None => Err(TupleDecodeError::BadLength(slot.length())),
+            },
+        )
+    }
+
+    /// Values of `columns` of every live tuple, in the order of `columns`.
+    pub fn read_iterator_projected(
+        self,
+        schema: &'a Schema,
+        columns: &[usize],
+    ) -> Result<impl Iterator<Item = Result<Vec<TupleValue>, TupleDecodeError>>, SchemaError> {
+        let projection = Projection::new(schema, columns)?;
+
+        Ok(self
+            .read_iterator_raw()
+            .map(move |data| projection.read(data)))
+    }
+
+    pub fn read_iterator_ref(self, schema: &'a Schema) -> impl Iterator<Item = TupleRef<'a>> {
+        self.read_iterator_raw()
+            .map(move |data| TupleRef::new(schema, data))
+    }
+
+    pub fn read_iterator_raw(self) -> impl Iterator<Item = &'a [u8]> {
+        self.read_iterator_raw_with_slots().map(|(_, data)| data)
+    }
+
+    /// Bytes of live tuples. Slots that point outside of the page are skipped,
+    /// `read_iterator` reports them.
+    pub fn read_iterator_raw_with_slots(self) -> impl Iterator<Item = (SlotId, &'a [u8])> {
+        self.slot_data()
+            .filter(|(slot, _)| !slot.is_deleted())
+            .filter_map(|(slot, data)| Some((slot.id, data?)))
+    }
+
+    fn tuple_data(self, slot_id: SlotId) -> Result<&'a [u8], ReadTupleError> {
+        match self.slot_data().find(|(slot, _)| slot.id == slot_id) {
+            Some((slot, data)) if !slot.is_deleted() => {
+                Ok(data.ok_or(TupleDecodeError::BadLength(slot.length()))?)
+            }
+            _ => Err(ReadTupleError::TupleNotFound),
+        }
+    }
+
+    /// Every slot with the bytes of its tuple, `None` if they are not within the page.
+    fn slot_data(self) -> impl Iterator<Item = (Slot, Option<&'a [u8]>)> {
         let mut data_offset = 0;
         let data_length = self.data.len();
 
         self.data[HEADER_SIZE..]
-            .chunks(SLOT_SIZE)
+            .chunks_exact(SLOT_SIZE)
             .take(self.slots)
             .map(move |slot_data| {
                 let slot = Slot::read(slot_data);
 
                 data_offset += slot.length();
 
-                &self.data[data_length - data_offset..data_length - data_offset + slot.length()]
+                let data = data_length
+                    .checked_sub(data_offset)
+                    .and_then(|start| self.data.get(start..start + slot.length()));
+
+                (slot, data)
             })
     }
 }
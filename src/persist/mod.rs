@@ -0,0 +1,378 @@
+pub mod relation;
+pub mod uring;
+
+use crate::page::{Page, PageId, PageView, SIZE, is_supported_size};
+use memmap2::Mmap;
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use uring::IoUringQueue;
+
+const IO_URING_QUEUE_DEPTH: u32 = 64;
+
+const FILE_MAGIC: &[u8; 8] = b"NAIVEDB\0";
+const FILE_FORMAT_VERSION: u16 = 1;
+// magic + format version + page size
+const FILE_HEADER_SIZE: usize = 8 + 2 + 4;
+
+/// Page size of a file and the offset of its first page.
+///
+/// The header takes a whole page, so pages stay aligned to their size. Files
+/// written before the header was introduced have no header and 8 KiB pages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileLayout {
+    pub page_size: usize,
+    pub data_offset: u64,
+}
+
+impl FileLayout {
+    pub fn new(page_size: usize) -> FileLayout {
+        FileLayout {
+            page_size,
+            data_offset: page_size as u64,
+        }
+    }
+
+    pub fn headerless() -> FileLayout {
+        FileLayout {
+            page_size: SIZE,
+            data_offset: 0,
+        }
+    }
+
+    /// Returns `None` for an empty file, its layout is decided by the first write.
+    pub fn read(file: &File) -> Result<Option<FileLayout>, Error> {
+        let file_length = file.metadata()?.len();
+        if file_length == 0 {
+            return Ok(None);
+        }
+
+        let mut header = [0; FILE_HEADER_SIZE];
+        if file_length < FILE_HEADER_SIZE as u64 {
+            return Ok(Some(FileLayout::headerless()));
+        }
+        file.read_exact_at(&mut header, 0)?;
+
+        if &header[0..8] != FILE_MAGIC {
+            return Ok(Some(FileLayout::headerless()));
+        }
+
+        let version = u16::from_be_bytes([header[8], header[9]]);
+        if version != FILE_FORMAT_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported file format version {}", version),
+            ));
+        }
+
+        let page_size = u32::from_be_bytes(header[10..14].try_into().unwrap()) as usize;
+        if !is_supported_size(page_size) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported page size {}", page_size),
+            ));
+        }
+
+        Ok(Some(FileLayout::new(page_size)))
+    }
+
+    pub fn header(&self) -> Vec<u8> {
+        let mut header = vec![0; self.data_offset as usize];
+
+        header[0..8].copy_from_slice(FILE_MAGIC);
+        header[8..10].copy_from_slice(&FILE_FORMAT_VERSION.to_be_bytes());
+        header[10..14].copy_from_slice(&(self.page_size as u32).to_be_bytes());
+
+        header
+    }
+
+    pub fn page_offset(&self, page_id: PageId) -> u64 {
+        self.data_offset + page_id * self.page_size as u64
+    }
+
+    pub fn page_count(&self, file_length: u64) -> u64 {
+        file_length.saturating_sub(self.data_offset) / self.page_size as u64
+    }
+}
+
+/// Cloning an io_uring backend shares its rings, e.g. between file segments.
+#[derive(Clone)]
+pub enum IoBackend {
+    Pread,
+    IoUring(Arc<IoUringQueue>),
+}
+
+impl IoBackend {
+    /// io_uring backend if the kernel supports it, pread/pwrite otherwise.
+    pub fn io_uring_or_pread() -> IoBackend {
+        match IoUringQueue::new(IO_URING_QUEUE_DEPTH) {
+            Ok(queue) => IoBackend::IoUring(Arc::new(queue)),
+            Err(_) => IoBackend::Pread,
+        }
+    }
+}
+
+/// Keeps the file open for writing, the layout is read or written once.
+pub struct Writer {
+    file: File,
+    backend: IoBackend,
+    layout: OnceLock<FileLayout>,
+}
+
+impl Writer {
+    pub fn new(path: &str, filename: &str) -> Result<Writer, Error> {
+        Writer::with_backend(path, filename, IoBackend::Pread)
+    }
+
+    pub fn with_backend(path: &str, filename: &str, backend: IoBackend) -> Result<Writer, Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(Path::new(path).join(filename))?;
+
+        Ok(Writer {
+            file,
+            backend,
+            layout: OnceLock::new(),
+        })
+    }
+
+    pub fn insert_page(&self, page: &Page) -> Result<(), Error> {
+        self.layout(page.size())?;
+
+        self.file
+            .write_all_at(&page.data, self.file.metadata()?.len())
+    }
+
+    /// Creates the file with a header for pages of `page_size` unless it exists.
+    pub fn init(&self, page_size: usize) -> Result<FileLayout, Error> {
+        self.layout(page_size)
+    }
+
+    /// Writes a page to the position of `page_id` of this file.
+    pub fn write_page_at(&self, page_id: PageId, page: &Page) -> Result<(), Error> {
+        let layout = self.layout(page.size())?;
+
+        self.file
+            .write_all_at(&page.data, layout.page_offset(page_id))
+    }
+
+    /// Extends the file with empty pages up to `page_count` pages.
+    pub fn reserve(&self, page_count: u64, page_size: usize) -> Result<(), Error> {
+        let layout = self.layout(page_size)?;
+
+        let length = layout.page_offset(page_count);
+        if self.file.metadata()?.len() < length {
+            self.file.set_len(length)?;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes the written pages to the disk.
+    pub fn sync(&self) -> Result<(), Error> {
+        self.file.sync_data()
+    }
+
+    /// Appends pages to the end of the file as a single batch.
+    pub fn insert_pages(&self, pages: &[&Page]) -> Result<(), Error> {
+        let Some(first_page) = pages.first() else {
+            return Ok(());
+        };
+
+        let layout = self.layout(first_page.size())?;
+        if pages.iter().any(|page| page.size() != layout.page_size) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Pages of different sizes in one batch",
+            ));
+        }
+
+        let first_page_id = layout.page_count(self.file.metadata()?.len());
+        let requests = pages
+            .iter()
+            .enumerate()
+            .map(|(i, page)| {
+                let offset = layout.page_offset(first_page_id + i as u64);
+
+                (offset, &page.data[..])
+            })
+            .collect::<Vec<_>>();
+
+        match &self.backend {
+            IoBackend::Pread => {
+                for (offset, data) in requests {
+                    self.file.write_all_at(data, offset)?;
+                }
+
+                Ok(())
+            }
+            IoBackend::IoUring(queue) => queue.write_at(self.file.as_raw_fd(), &requests),
+        }
+    }
+
+    /// Layout of the file, a new file gets a header for pages of `page_size`.
+    ///
+    /// The header is only read by the first call, later calls check the page
+    /// size against the cached layout.
+    fn layout(&self, page_size: usize) -> Result<FileLayout, Error> {
+        let layout = match self.layout.get() {
+            Some(layout) => *layout,
+            None => {
+                let layout = match FileLayout::read(&self.file)? {
+                    Some(layout) => layout,
+                    None => {
+                        let layout = FileLayout::new(page_size);
+                        self.file.write_all_at(&layout.header(), 0)?;
+
+                        layout
+                    }
+                };
+
+                *self.layout.get_or_init(|| layout)
+            }
+        };
+
+        if layout.page_size != page_size {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Page size {} doesn't match the file page size {}",
+                    page_size, layout.page_size
+                ),
+            ));
+        }
+
+        Ok(layout)
+    }
+}
+
+pub struct Reader {
+    file: File,
+    backend: IoBackend,
+    layout: FileLayout,
+}
+
+impl Reader {
+    pub fn new(path: &str, filename: &str) -> Result<Reader, Error> {
+        Reader::with_backend(path, filename, IoBackend::Pread)
+    }
+
+    pub fn with_backend(path: &str, filename: &str, backend: IoBackend) -> Result<Reader, Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(Path::new(path).join(filename))?;
+        let layout = FileLayout::read(&file)?.unwrap_or_else(FileLayout::headerless);
+
+        Ok(Reader {
+            file,
+            backend,
+            layout,
+        })
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.layout.page_size
+    }
+
+    pub fn page_count(&self) -> u64 {
+        self.layout.page_count(self.file.metadata().unwrap().len())
+    }
+
+    pub fn read_page(&self, page_id: u64, page: &mut Page) -> Result<(), ()> {
+        if page.size() != self.layout.page_size {
+            return Err(());
+        }
+
+        let offset = self.layout.page_offset(page_id);
+        self.file.read_exact_at(&mut page.data, offset).or(Err(()))
+    }
+
+    /// Reads a batch of pages, every page is read from the position of its `id`.
+    pub fn read_pages(&self, pages: &mut [&mut Page]) -> Result<(), ()> {
+        let mut requests = pages
+            .iter_mut()
+            .map(|page| (page.id, &mut **page))
+            .collect::<Vec<_>>();
+
+        self.read_pages_at(&mut requests)
+    }
+
+    /// Reads a batch of pages from the positions of the given page ids.
+    pub fn read_pages_at(&self, pages: &mut [(PageId, &mut Page)]) -> Result<(), ()> {
+        match &self.backend {
+            IoBackend::Pread => pages
+                .iter_mut()
+                .try_for_each(|(page_id, page)| self.read_page(*page_id, page)),
+            IoBackend::IoUring(queue) => {
+                if pages
+                    .iter()
+                    .any(|(_, page)| page.size() != self.layout.page_size)
+                {
+                    return Err(());
+                }
+
+                let mut requests = pages
+                    .iter_mut()
+                    .map(|(page_id, page)| (self.layout.page_offset(*page_id), &mut page.data[..]))
+                    .collect::<Vec<_>>();
+
+                queue
+                    .read_at(self.file.as_raw_fd(), &mut requests)
+                    .or(Err(()))
+            }
+        }
+    }
+}
+
+/// Read-only access path for read-mostly files: pages borrow their bytes
+/// straight from the mapping instead of being copied into pool frames.
+pub struct MmapReader {
+    mmap: Mmap,
+    layout: FileLayout,
+}
+
+impl MmapReader {
+    /// Maps the file as it is now, pages appended later are not seen.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated while the reader lives, neither by a
+    /// `Writer` nor by another process: reading a page past the new end of
+    /// the file raises SIGBUS. Pages written in place change under readers.
+    pub unsafe fn new(path: &str, filename: &str) -> Result<MmapReader, Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(Path::new(path).join(filename))?;
+        let layout = FileLayout::read(&file)?.unwrap_or_else(FileLayout::headerless);
+
+        // The caller keeps the file from being truncated, see above.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        Ok(MmapReader { mmap, layout })
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.layout.page_size
+    }
+
+    pub fn page_count(&self) -> u64 {
+        self.layout.page_count(self.mmap.len() as u64)
+    }
+
+    pub fn page(&self, page_id: PageId) -> Option<PageView<'_>> {
+        if page_id >= self.page_count() {
+            return None;
+        }
+
+        let offset = self.layout.page_offset(page_id) as usize;
+        let data = self.mmap.get(offset..offset + self.layout.page_size)?;
+
+        Some(PageView::from_data(page_id, data))
+    }
+}
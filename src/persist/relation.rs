@@ -0,0 +1,207 @@
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use parking_lot::RwLock;
+
+use crate::page::{Page, PageId};
+use crate::persist::{FileLayout, IoBackend, Reader, Writer};
+
+pub const SEGMENT_SIZE: u64 = 1024 * 1024 * 1024;
+
+struct Segment {
+    reader: Reader,
+    writer: Writer,
+}
+
+/// Pages of one relation split into segment files `name`, `name.1`, `name.2`, ...
+///
+/// Page `n` lives in segment `n / pages_per_segment`, so every segment but the
+/// last one is full. A new segment is added when a page past the last one is written.
+pub struct RelationFile {
+    path: String,
+    name: String,
+    page_size: usize,
+    pages_per_segment: u64,
+    backend: IoBackend,
+    segments: RwLock<Vec<Segment>>,
+}
+
+impl RelationFile {
+    pub fn open(
+        path: &str,
+        name: &str,
+        page_size: usize,
+        backend: IoBackend,
+    ) -> Result<RelationFile, Error> {
+        RelationFile::with_segment_size(path, name, page_size, backend, SEGMENT_SIZE)
+    }
+
+    pub fn with_segment_size(
+        path: &str,
+        name: &str,
+        page_size: usize,
+        backend: IoBackend,
+        segment_size: u64,
+    ) -> Result<RelationFile, Error> {
+        let (first_segment, layout) = open_segment(path, name, 0, page_size, &backend)?;
+
+        let pages_per_segment = segment_size.saturating_sub(layout.data_offset) / page_size as u64;
+        if pages_per_segment == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Segment size {} is too small", segment_size),
+            ));
+        }
+
+        let mut segments = vec![first_segment];
+        while Path::new(path)
+            .join(segment_filename(name, segments.len()))
+            .exists()
+        {
+            segments.push(open_segment(path, name, segments.len(), page_size, &backend)?.0);
+        }
+
+        Ok(RelationFile {
+            path: path.to_owned(),
+            name: name.to_owned(),
+            page_size,
+            pages_per_segment,
+            backend,
+            segments: RwLock::new(segments),
+        })
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segments.read().len()
+    }
+
+    pub fn page_count(&self) -> u64 {
+        let segments = self.segments.read();
+        let full_segments = (segments.len() - 1) as u64;
+
+        full_segments * self.pages_per_segment + segments.last().unwrap().reader.page_count()
+    }
+
+    pub fn read_page(&self, page_id: PageId, page: &mut Page) -> Result<(), ()> {
+        let (segment, segment_page_id) = self.locate(page_id);
+
+        let segments = self.segments.read();
+        let segment = segments.get(segment).ok_or(())?;
+
+        segment.reader.read_page(segment_page_id, page)
+    }
+
+    /// Reads a batch of pages, every page is read from the position of its `id`.
+    pub fn read_pages(&self, pages: &mut [&mut Page]) -> Result<(), ()> {
+        let segments = self.segments.read();
+
+        let mut requests = segments.iter().map(|_| vec![]).collect::<Vec<_>>();
+        for page in pages.iter_mut() {
+            let (segment, segment_page_id) = self.locate(page.id);
+
+            requests
+                .get_mut(segment)
+                .ok_or(())?
+                .push((segment_page_id, &mut **page));
+        }
+
+        for (segment, mut requests) in segments.iter().zip(requests) {
+            if !requests.is_empty() {
+                segment.reader.read_pages_at(&mut requests)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes a page to the position of its `id`, adding segments if needed.
+    pub fn write_page(&self, page: &Page) -> Result<(), Error> {
+        let (segment, segment_page_id) = self.locate(page.id);
+
+        {
+            let segments = self.segments.read();
+            if let Some(segment) = segments.get(segment) {
+                return segment.writer.write_page_at(segment_page_id, page);
+            }
+        }
+
+        let mut segments = self.segments.write();
+        while segments.len() <= segment {
+            segments
+                .last()
+                .unwrap()
+                .writer
+                .reserve(self.pages_per_segment, self.page_size)?;
+
+            let (new_segment, _) = open_segment(
+                &self.path,
+                &self.name,
+                segments.len(),
+                self.page_size,
+                &self.backend,
+            )?;
+            segments.push(new_segment);
+        }
+
+        segments[segment]
+            .writer
+            .write_page_at(segment_page_id, page)
+    }
+
+    /// Flushes the written pages of all segments to the disk.
+    pub fn sync(&self) -> Result<(), Error> {
+        for segment in self.segments.read().iter() {
+            segment.writer.sync()?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the segment files. Pages stay readable through this file
+    /// until it is dropped, as its segments are still open.
+    pub fn remove(&self) -> Result<(), Error> {
+        let segments = self.segments.read();
+
+        for segment in 0..segments.len() {
+            std::fs::remove_file(
+                Path::new(&self.path).join(segment_filename(&self.name, segment)),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn locate(&self, page_id: PageId) -> (usize, PageId) {
+        (
+            (page_id / self.pages_per_segment) as usize,
+            page_id % self.pages_per_segment,
+        )
+    }
+}
+
+fn segment_filename(name: &str, segment: usize) -> String {
+    match segment {
+        0 => name.to_owned(),
+        _ => format!("{}.{}", name, segment),
+    }
+}
+
+fn open_segment(
+    path: &str,
+    name: &str,
+    segment: usize,
+    page_size: usize,
+    backend: &IoBackend,
+) -> Result<(Segment, FileLayout), Error> {
+    let filename = segment_filename(name, segment);
+
+    let writer = Writer::with_backend(path, &filename, backend.clone())?;
+    let layout = writer.init(page_size)?;
+    let reader = Reader::with_backend(path, &filename, backend.clone())?;
+
+    Ok((Segment { reader, writer }, layout))
+}
@@ -0,0 +1,195 @@
+use std::{
+    io::{Error, ErrorKind},
+    os::fd::RawFd,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use io_uring::{IoUring, opcode, squeue, types};
+use parking_lot::{Mutex, MutexGuard};
+
+// Flag of io_uring_enter to wait for completions.
+const IORING_ENTER_GETEVENTS: u32 = 1;
+
+/// A set of io_uring instances shared by all threads of a reader or writer.
+///
+/// Every ring is guarded by its own mutex, a caller takes the first ring
+/// that is not busy so parallel scans don't serialize on a single queue.
+pub struct IoUringQueue {
+    // A ring is dropped when entries are stuck in its submission queue, the
+    // next caller makes a new one.
+    rings: Vec<Mutex<Option<IoUring>>>,
+    depth: usize,
+    next_ring: AtomicUsize,
+}
+
+impl IoUringQueue {
+    pub fn new(depth: u32) -> Result<IoUringQueue, Error> {
+        let rings_count = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+
+        let rings = (0..rings_count)
+            .map(|_| IoUring::new(depth).map(|ring| Mutex::new(Some(ring))))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(IoUringQueue {
+            rings,
+            depth: depth as usize,
+            next_ring: AtomicUsize::new(0),
+        })
+    }
+
+    /// Reads every buffer from its offset. Returns when all reads are completed.
+    pub fn read_at(&self, fd: RawFd, requests: &mut [(u64, &mut [u8])]) -> Result<(), Error> {
+        let entries = requests
+            .iter_mut()
+            .map(|(offset, buffer)| {
+                opcode::Read::new(types::Fd(fd), buffer.as_mut_ptr(), buffer.len() as u32)
+                    .offset(*offset)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        let lengths = requests.iter().map(|(_, b)| b.len()).collect::<Vec<_>>();
+
+        self.submit(entries, &lengths)
+    }
+
+    /// Writes every buffer to its offset. Returns when all writes are completed.
+    pub fn write_at(&self, fd: RawFd, requests: &[(u64, &[u8])]) -> Result<(), Error> {
+        let entries = requests
+            .iter()
+            .map(|(offset, buffer)| {
+                opcode::Write::new(types::Fd(fd), buffer.as_ptr(), buffer.len() as u32)
+                    .offset(*offset)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        let lengths = requests.iter().map(|(_, b)| b.len()).collect::<Vec<_>>();
+
+        self.submit(entries, &lengths)
+    }
+
+    fn lock_ring(&self) -> MutexGuard<'_, Option<IoUring>> {
+        let start = self.next_ring.fetch_add(1, Ordering::Relaxed);
+
+        for i in 0..self.rings.len() {
+            if let Some(guard) = self.rings[(start + i) % self.rings.len()].try_lock() {
+                return guard;
+            }
+        }
+
+        self.rings[start % self.rings.len()].lock()
+    }
+
+    fn submit(&self, entries: Vec<squeue::Entry>, lengths: &[usize]) -> Result<(), Error> {
+        let mut guard = self.lock_ring();
+        if guard.is_none() {
+            *guard = Some(IoUring::new(self.depth as u32)?);
+        }
+        let ring = guard.as_mut().unwrap();
+
+        let result = self.submit_entries(ring, &entries, lengths);
+
+        // Entries the kernel never took would go out with the next batch of
+        // the ring, long after their buffers are gone. Nothing of the ring is
+        // in flight any more, so it is dropped with them and made anew.
+        if !ring.submission().is_empty() {
+            *guard = None;
+        }
+
+        result
+    }
+
+    fn submit_entries(
+        &self,
+        ring: &mut IoUring,
+        entries: &[squeue::Entry],
+        lengths: &[usize],
+    ) -> Result<(), Error> {
+        let mut result = Ok(());
+
+        for (chunk_index, chunk) in entries.chunks(self.depth).enumerate() {
+            let first_entry = chunk_index * self.depth;
+
+            for (i, entry) in chunk.iter().enumerate() {
+                let entry = entry.clone().user_data((first_entry + i) as u64);
+
+                // Buffers are owned by the caller and outlive this call: every
+                // entry the kernel took is waited for before returning, even
+                // after an error. A chunk never exceeds the queue depth, so
+                // push can't fail.
+                unsafe { ring.submission().push(&entry) }
+                    .expect("Chunk doesn't fit into the submission queue");
+            }
+
+            let mut completed = 0;
+            let mut failed = false;
+            loop {
+                // Entries still in the submission queue never reached the kernel.
+                let in_flight = chunk.len() - completed - ring.submission().len();
+                if in_flight == 0 && (failed || completed == chunk.len()) {
+                    break;
+                }
+
+                let waited = match failed {
+                    false => ring.submit_and_wait(chunk.len() - completed),
+                    // Only waits, a failed submission is not retried.
+                    true => unsafe {
+                        ring.submitter().enter::<()>(
+                            0,
+                            in_flight as u32,
+                            IORING_ENTER_GETEVENTS,
+                            None,
+                        )
+                    },
+                };
+
+                match waited {
+                    Ok(_) => {}
+                    Err(err) if is_transient(&err) => {}
+                    Err(err) if !failed => {
+                        failed = true;
+                        if result.is_ok() {
+                            result = Err(err);
+                        }
+                    }
+                    // Waiting on a ring that took entries can't fail, if it does
+                    // the kernel may still write into buffers the caller frees.
+                    Err(err) => panic!("Can't wait for io_uring completions: {}", err),
+                }
+
+                for completion in ring.completion() {
+                    completed += 1;
+
+                    let expected_length = lengths[completion.user_data() as usize];
+                    let completion_result = match completion.result() {
+                        r if r < 0 => Err(Error::from_raw_os_error(-r)),
+                        r if r as usize != expected_length => {
+                            Err(Error::from(ErrorKind::UnexpectedEof))
+                        }
+                        _ => Ok(()),
+                    };
+
+                    if result.is_ok() {
+                        result = completion_result;
+                    }
+                }
+            }
+
+            if failed {
+                break;
+            }
+        }
+
+        result
+    }
+}
+
+// Errors after which submitting or waiting again may succeed: a signal, no
+// memory for new requests yet, or completions to reap first.
+fn is_transient(err: &Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::ResourceBusy
+    )
+}
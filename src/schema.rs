@@ -0,0 +1,277 @@
+use crate::util::decimal;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataType {
+    SmallInt,
+    Integer,
+    BigInt,
+    Real,
+    Double,
+    Boolean,
+    Varchar,
+    /// Blank padded string of `length` characters.
+    Char {
+        length: u16,
+    },
+    Bytea,
+    Uuid,
+    Date,
+    Time,
+    Timestamp,
+    TimestampTz,
+    Interval,
+    /// Exact number of `precision` digits, `scale` of them after the decimal point.
+    Decimal {
+        precision: u8,
+        scale: u8,
+    },
+}
+
+impl DataType {
+    /// Parses a type name, case insensitive.
+    pub fn parse(name: &str) -> Result<DataType, SchemaError> {
+        match name {
+            s if starts_with_ignore_case(s, "decimal") || starts_with_ignore_case(s, "numeric") => {
+                DataType::parse_decimal(name)
+            }
+            s if starts_with_ignore_case(s, "char") => DataType::parse_char(name),
+            s if s.eq_ignore_ascii_case("smallint") => Ok(DataType::SmallInt),
+            s if s.eq_ignore_ascii_case("integer") => Ok(DataType::Integer),
+            s if s.eq_ignore_ascii_case("bigint") => Ok(DataType::BigInt),
+            s if s.eq_ignore_ascii_case("real") => Ok(DataType::Real),
+            s if s.eq_ignore_ascii_case("double") => Ok(DataType::Double),
+            s if s.eq_ignore_ascii_case("boolean") => Ok(DataType::Boolean),
+            s if s.eq_ignore_ascii_case("varchar") => Ok(DataType::Varchar),
+            s if s.eq_ignore_ascii_case("bytea") => Ok(DataType::Bytea),
+            s if s.eq_ignore_ascii_case("varbinary") => Ok(DataType::Bytea),
+            s if s.eq_ignore_ascii_case("uuid") => Ok(DataType::Uuid),
+            s if s.eq_ignore_ascii_case("date") => Ok(DataType::Date),
+            s if s.eq_ignore_ascii_case("time") => Ok(DataType::Time),
+            s if s.eq_ignore_ascii_case("timestamp") => Ok(DataType::Timestamp),
+            s if s.eq_ignore_ascii_case("timestamptz") => Ok(DataType::TimestampTz),
+            s if s.eq_ignore_ascii_case("interval") => Ok(DataType::Interval),
+            _ => Err(SchemaError::UnknownType(name.to_owned())),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        let name = match self {
+            DataType::SmallInt => "smallint",
+            DataType::Integer => "integer",
+            DataType::BigInt => "bigint",
+            DataType::Real => "real",
+            DataType::Double => "double",
+            DataType::Boolean => "boolean",
+            DataType::Varchar => "varchar",
+            DataType::Bytea => "bytea",
+            DataType::Uuid => "uuid",
+            DataType::Date => "date",
+            DataType::Time => "time",
+            DataType::Timestamp => "timestamp",
+            DataType::TimestampTz => "timestamptz",
+            DataType::Interval => "interval",
+            DataType::Decimal { precision, scale } => {
+                return format!("decimal({},{})", precision, scale);
+            }
+            DataType::Char { length } => return format!("char({})", length),
+        };
+
+        name.to_owned()
+    }
+
+    /// Encoded size of a value, `None` for variable length types.
+    pub fn fixed_size(&self) -> Option<usize> {
+        match self {
+            DataType::SmallInt => Some(2),
+            DataType::Integer | DataType::Real | DataType::Date => Some(4),
+            DataType::BigInt | DataType::Double => Some(8),
+            DataType::Time | DataType::Timestamp | DataType::TimestampTz => Some(8),
+            DataType::Interval | DataType::Uuid => Some(16),
+            DataType::Boolean => Some(1),
+            DataType::Decimal { precision, .. } => Some(decimal::encoded_size(*precision)),
+            DataType::Varchar | DataType::Char { .. } | DataType::Bytea => None,
+        }
+    }
+
+    /// Parses `decimal`, `decimal(p)` or `decimal(p,s)`, numeric is a synonym.
+    /// The default precision is the maximum one, the default scale is 0.
+    fn parse_decimal(name: &str) -> Result<DataType, SchemaError> {
+        let invalid = || SchemaError::InvalidTypeModifier(name.to_owned());
+
+        // "decimal" and "numeric" have the same length.
+        let modifiers = name["decimal".len()..].trim();
+        let (precision, scale) = if modifiers.is_empty() {
+            (decimal::MAX_PRECISION, 0)
+        } else {
+            let modifiers = modifiers
+                .strip_prefix('(')
+                .and_then(|m| m.strip_suffix(')'))
+                .ok_or_else(|| SchemaError::UnknownType(name.to_owned()))?;
+
+            let mut modifiers = modifiers.split(',').map(|m| m.trim().parse::<u8>());
+            let precision = modifiers.next().unwrap().map_err(|_| invalid())?;
+            let scale = modifiers.next().unwrap_or(Ok(0)).map_err(|_| invalid())?;
+            if modifiers.next().is_some() {
+                return Err(invalid());
+            }
+
+            (precision, scale)
+        };
+
+        if precision == 0 || precision > decimal::MAX_PRECISION || scale > precision {
+            return Err(invalid());
+        }
+
+        Ok(DataType::Decimal { precision, scale })
+    }
+
+    /// Parses `char`, `char(n)`, `character` or `character(n)`, the default length is 1.
+    fn parse_char(name: &str) -> Result<DataType, SchemaError> {
+        let modifiers = if starts_with_ignore_case(name, "character") {
+            &name["character".len()..]
+        } else {
+            &name["char".len()..]
+        }
+        .trim();
+
+        if modifiers.is_empty() {
+            return Ok(DataType::Char { length: 1 });
+        }
+
+        let length = modifiers
+            .strip_prefix('(')
+            .and_then(|m| m.strip_suffix(')'))
+            .ok_or_else(|| SchemaError::UnknownType(name.to_owned()))?
+            .trim()
+            .parse::<u16>()
+            .map_err(|_| SchemaError::InvalidTypeModifier(name.to_owned()))?;
+
+        if length == 0 {
+            return Err(SchemaError::InvalidTypeModifier(name.to_owned()));
+        }
+
+        Ok(DataType::Char { length })
+    }
+}
+
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.get(..prefix.len())
+        .is_some_and(|p| p.eq_ignore_ascii_case(prefix))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub data_type: DataType,
+    pub nullable: bool,
+}
+
+impl Column {
+    /// Nullable column, as columns are in SQL unless declared `NOT NULL`.
+    pub fn new(name: &str, data_type: DataType) -> Column {
+        Column {
+            name: name.to_owned(),
+            data_type,
+            nullable: true,
+        }
+    }
+
+    pub fn not_null(mut self) -> Column {
+        self.nullable = false;
+        self
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SchemaError {
+    UnknownType(String),
+    DuplicateColumn(String),
+    /// E.g. a decimal scale greater than its precision.
+    InvalidTypeModifier(String),
+    /// Ordinal of a column the schema doesn't have.
+    ColumnNotFound(usize),
+}
+
+/// Ordered columns of a relation. A schema is validated when it is built,
+/// so reading tuples with it never meets an unknown type.
+///
+/// Tuples of a schema with nullable columns start with a null bitmap, one bit
+/// per column. Schemas without nullable columns have no bitmap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    columns: Vec<Column>,
+}
+
+impl Schema {
+    pub fn new(columns: Vec<Column>) -> Result<Schema, SchemaError> {
+        for (i, column) in columns.iter().enumerate() {
+            if columns[..i].iter().any(|c| c.name == column.name) {
+                return Err(SchemaError::DuplicateColumn(column.name.clone()));
+            }
+        }
+
+        Ok(Schema { columns })
+    }
+
+    /// Schema of unnamed columns, e.g. of a key or an intermediate result.
+    pub fn from_types(data_types: &[DataType]) -> Schema {
+        Schema {
+            columns: data_types
+                .iter()
+                .enumerate()
+                .map(|(i, data_type)| Column::new(&format!("column_{}", i), *data_type))
+                .collect(),
+        }
+    }
+
+    /// Builds a schema from (name, type name) pairs, a type name may end with `NOT NULL`.
+    pub fn parse(columns: &[(&str, &str)]) -> Result<Schema, SchemaError> {
+        let columns = columns
+            .iter()
+            .map(|(name, type_name)| {
+                let words = type_name.split_whitespace().collect::<Vec<_>>();
+
+                match words.as_slice() {
+                    [type_name @ .., not, null]
+                        if not.eq_ignore_ascii_case("not") && null.eq_ignore_ascii_case("null") =>
+                    {
+                        Ok(Column::new(name, DataType::parse(&type_name.join(" "))?).not_null())
+                    }
+                    _ => Ok(Column::new(name, DataType::parse(&words.join(" "))?)),
+                }
+            })
+            .collect::<Result<Vec<_>, SchemaError>>()?;
+
+        Schema::new(columns)
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Ordinal position of the column `name`.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.name == name)
+    }
+
+    /// Size of the null bitmap in front of every tuple.
+    pub fn null_bitmap_size(&self) -> usize {
+        if self.columns.iter().any(|c| c.nullable) {
+            self.columns.len().div_ceil(8)
+        } else {
+            0
+        }
+    }
+
+    pub fn data_types(&self) -> impl Iterator<Item = DataType> + '_ {
+        self.columns.iter().map(|c| c.data_type)
+    }
+}
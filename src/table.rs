@@ -0,0 +1,421 @@
+use parking_lot::Mutex;
+
+use crate::{
+    buffer_pool::buffer_pool::BufferPool,
+    catalog::{Catalog, CatalogError, Engine, IndexInfo, TableInfo},
+    heap_file::{HeapFile, HeapFileError, RowId, ScanRow},
+    index::btree::{BTree, BTreeError},
+    persist::{IoBackend, relation::RelationFile},
+    tuple::{KeyColumn, Tuple, TupleToDataError, TupleValue, encode_key},
+};
+
+#[derive(Debug)]
+pub enum TableError<'a> {
+    HeapFile(HeapFileError<'a>),
+    Index(BTreeError<'a>),
+    Catalog(CatalogError<'a>),
+    Key(TupleToDataError),
+    IndexNotFound(String),
+    /// Name of a table stored by another engine than the heap.
+    WrongEngine(String),
+    /// Name of the unique index that already has the key.
+    UniqueViolation(String),
+}
+
+impl<'a> From<HeapFileError<'a>> for TableError<'a> {
+    fn from(err: HeapFileError<'a>) -> TableError<'a> {
+        TableError::HeapFile(err)
+    }
+}
+
+impl<'a> From<BTreeError<'a>> for TableError<'a> {
+    fn from(err: BTreeError<'a>) -> TableError<'a> {
+        TableError::Index(err)
+    }
+}
+
+impl<'a> From<CatalogError<'a>> for TableError<'a> {
+    fn from(err: CatalogError<'a>) -> TableError<'a> {
+        TableError::Catalog(err)
+    }
+}
+
+impl From<TupleToDataError> for TableError<'_> {
+    fn from(err: TupleToDataError) -> Self {
+        TableError::Key(err)
+    }
+}
+
+/// Rows of a table with all of its indexes. Every write changes the heap
+/// file and the indexes together: unique keys are checked first, and a write
+/// that fails halfway is undone.
+///
+/// Writes run one at a time, so a key found free by the check is still free
+/// when it is inserted. Reads don't wait for them.
+pub struct Table<'a> {
+    pool: &'a BufferPool<'a>,
+    path: String,
+    info: &'a TableInfo,
+    heap_file: HeapFile<'a>,
+    indexes: Vec<TableIndex<'a>>,
+    write_lock: Mutex<()>,
+}
+
+struct TableIndex<'a> {
+    info: IndexInfo,
+    tree: BTree<'a>,
+    key_columns: Vec<KeyColumn>,
+}
+
+// An index change of a write, reverted in reverse order when a later one fails.
+enum IndexChange {
+    Insert(usize, Vec<u8>, RowId),
+    Delete(usize, Vec<u8>, RowId),
+}
+
+impl<'a> Table<'a> {
+    /// Opens the heap file of the table and all of its indexes in the catalog.
+    /// Tables of the LSM engine are opened by `LsmTree::open_table`.
+    pub fn open(
+        pool: &'a BufferPool<'a>,
+        catalog: &Catalog<'a>,
+        info: &'a TableInfo,
+    ) -> Result<Table<'a>, TableError<'a>> {
+        if info.engine != Engine::Heap {
+            return Err(TableError::WrongEngine(info.name.clone()));
+        }
+
+        let heap_file =
+            HeapFile::open(pool, info.id, catalog.path(), &info.filename, &info.schema)?;
+
+        let mut table = Table {
+            pool,
+            path: catalog.path().to_owned(),
+            info,
+            heap_file,
+            indexes: vec![],
+            write_lock: Mutex::new(()),
+        };
+
+        for index in catalog.indexes(info.id)? {
+            table.open_index(index)?;
+        }
+
+        Ok(table)
+    }
+
+    pub fn indexes(&self) -> impl Iterator<Item = &IndexInfo> {
+        self.indexes.iter().map(|index| &index.info)
+    }
+
+    /// Builds an index of the table from the rows it has, and registers it once
+    /// it is built. Entries are sorted and written bottom up, not inserted one
+    /// by one. The file of an index that fails to build is removed.
+    pub fn create_index(
+        &mut self,
+        catalog: &Catalog<'a>,
+        name: &str,
+        columns: &[&str],
+        unique: bool,
+    ) -> Result<IndexInfo, TableError<'a>> {
+        let mut ordinals = Vec::with_capacity(columns.len());
+        for column in columns {
+            let Some(ordinal) = self.info.schema.index_of(column) else {
+                return Err(CatalogError::ColumnNotFound(column.to_string()).into());
+            };
+            ordinals.push(ordinal);
+        }
+        let key_columns = self.key_columns(&ordinals);
+
+        let mut entries = vec![];
+        for row in self.heap_file.scan() {
+            let (row_id, tuple) = row?;
+
+            let key = encode_key(&key_columns, &key_values(&tuple, &ordinals))?;
+            entries.push((key, row_id, has_null(&tuple, &ordinals)));
+        }
+
+        // Existing rows are checked before the index is registered.
+        if unique {
+            entries.sort_unstable();
+            let duplicate = entries
+                .windows(2)
+                .any(|pair| pair[0].0 == pair[1].0 && !pair[0].2);
+
+            if duplicate {
+                return Err(TableError::UniqueViolation(name.to_owned()));
+            }
+        }
+
+        let entries = entries
+            .into_iter()
+            .map(|(key, row_id, _)| (key, row_id))
+            .collect();
+
+        catalog.create_index_with(name, &self.info.name, columns, unique, |info| {
+            let built = self
+                .open_index(info.clone())
+                .and_then(|index| Ok(index.tree.build(entries)?));
+
+            if built.is_err() {
+                self.indexes.retain(|index| index.info.id != info.id);
+                let _ = RelationFile::open(
+                    &self.path,
+                    &info.filename,
+                    self.pool.page_size(),
+                    IoBackend::Pread,
+                )
+                .and_then(|file| file.remove());
+            }
+
+            built
+        })
+    }
+
+    pub fn get(&self, row_id: RowId) -> Result<Tuple<'a>, TableError<'a>> {
+        Ok(self.heap_file.get(row_id)?)
+    }
+
+    pub fn scan(&self) -> impl Iterator<Item = ScanRow<'a>> {
+        self.heap_file.scan()
+    }
+
+    /// Rows whose indexed columns equal `values`, found by the index `name`.
+    pub fn lookup(
+        &self,
+        name: &str,
+        values: &[TupleValue],
+    ) -> Result<Vec<(RowId, Tuple<'a>)>, TableError<'a>> {
+        let Some(index) = self.indexes.iter().find(|index| index.info.name == name) else {
+            return Err(TableError::IndexNotFound(name.to_owned()));
+        };
+
+        let key = encode_key(&index.key_columns, values)?;
+
+        index
+            .tree
+            .get(&key)?
+            .into_iter()
+            .map(|row_id| Ok((row_id, self.heap_file.get(row_id)?)))
+            .collect()
+    }
+
+    pub fn insert(&self, tuple: &Tuple) -> Result<RowId, TableError<'a>> {
+        let _write = self.write_lock.lock();
+
+        let keys = self.keys(tuple)?;
+        self.check_unique(tuple, &keys, None)?;
+
+        let row_id = self.heap_file.insert(tuple)?;
+
+        let changes = keys
+            .into_iter()
+            .enumerate()
+            .map(|(index, key)| IndexChange::Insert(index, key, row_id))
+            .collect();
+
+        if let Err(err) = self.apply(changes) {
+            let _ = self.heap_file.delete(row_id);
+            return Err(err);
+        }
+
+        Ok(row_id)
+    }
+
+    /// Updates the row, like `HeapFile::update` it may get a new id. Index
+    /// entries change only for keys that change, or for all if the row moves.
+    pub fn update(&self, row_id: RowId, tuple: &Tuple) -> Result<RowId, TableError<'a>> {
+        let _write = self.write_lock.lock();
+
+        let old = self.heap_file.get(row_id)?;
+        let old_keys = self.keys(&old)?;
+        let keys = self.keys(tuple)?;
+        self.check_unique(tuple, &keys, Some(&old_keys))?;
+
+        let new_row_id = self.heap_file.update(row_id, tuple)?;
+
+        let mut changes = vec![];
+        for (index, (old_key, key)) in old_keys.iter().zip(keys).enumerate() {
+            if *old_key != key || new_row_id != row_id {
+                changes.push(IndexChange::Delete(index, old_key.clone(), row_id));
+                changes.push(IndexChange::Insert(index, key, new_row_id));
+            }
+        }
+
+        if let Err(err) = self.apply(changes) {
+            self.restore(row_id, new_row_id, &old, &old_keys);
+            return Err(err);
+        }
+
+        Ok(new_row_id)
+    }
+
+    pub fn delete(&self, row_id: RowId) -> Result<(), TableError<'a>> {
+        let _write = self.write_lock.lock();
+
+        let old = self.heap_file.get(row_id)?;
+
+        let changes = self
+            .keys(&old)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, key)| IndexChange::Delete(index, key, row_id))
+            .collect::<Vec<_>>();
+        let undo = changes.iter().rev().map(IndexChange::reverse).collect();
+
+        // Entries go first, they are put back if the row cannot be deleted.
+        self.apply(changes)?;
+        if let Err(err) = self.heap_file.delete(row_id) {
+            let _ = self.apply(undo);
+            return Err(err.into());
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&self) -> Result<(), TableError<'a>> {
+        self.heap_file.flush()?;
+
+        Ok(())
+    }
+
+    // Puts an updated row back once its index changes were reverted, which
+    // left the entries pointing at `row_id`. A row that moved was deleted
+    // there, so it is inserted again and the entries follow it.
+    fn restore(&self, row_id: RowId, new_row_id: RowId, old: &Tuple, old_keys: &[Vec<u8>]) {
+        let restored = if new_row_id == row_id {
+            self.heap_file.update(row_id, old)
+        } else {
+            self.heap_file
+                .delete(new_row_id)
+                .and_then(|_| self.heap_file.insert(old))
+        };
+
+        if let Ok(restored) = restored
+            && restored != row_id
+        {
+            let changes = old_keys
+                .iter()
+                .enumerate()
+                .flat_map(|(index, key)| {
+                    [
+                        IndexChange::Delete(index, key.clone(), row_id),
+                        IndexChange::Insert(index, key.clone(), restored),
+                    ]
+                })
+                .collect();
+            let _ = self.apply(changes);
+        }
+    }
+
+    fn open_index(&mut self, info: IndexInfo) -> Result<&TableIndex<'a>, TableError<'a>> {
+        let tree = BTree::open(self.pool, info.id, &self.path, &info.filename)?;
+        let key_columns = self.key_columns(&info.columns);
+
+        self.indexes.push(TableIndex {
+            info,
+            tree,
+            key_columns,
+        });
+
+        Ok(self.indexes.last().unwrap())
+    }
+
+    fn key_columns(&self, columns: &[usize]) -> Vec<KeyColumn> {
+        columns
+            .iter()
+            .map(|&column| KeyColumn::new(self.info.schema.columns()[column].data_type))
+            .collect()
+    }
+
+    // Keys of the tuple in every index, in the order of `indexes`.
+    fn keys(&self, tuple: &Tuple) -> Result<Vec<Vec<u8>>, TableError<'a>> {
+        self.indexes
+            .iter()
+            .map(|index| {
+                let key = encode_key(&index.key_columns, &key_values(tuple, &index.info.columns))?;
+                if key.len() > index.tree.max_key_length() {
+                    return Err(BTreeError::KeyTooLong(key.len()).into());
+                }
+
+                Ok(key)
+            })
+            .collect()
+    }
+
+    // A key with a null is never a duplicate. An updated row may keep its
+    // keys, `old_keys` are its keys before the update.
+    fn check_unique(
+        &self,
+        tuple: &Tuple,
+        keys: &[Vec<u8>],
+        old_keys: Option<&[Vec<u8>]>,
+    ) -> Result<(), TableError<'a>> {
+        for (i, index) in self.indexes.iter().enumerate() {
+            if !index.info.unique || has_null(tuple, &index.info.columns) {
+                continue;
+            }
+            if old_keys.is_some_and(|old_keys| old_keys[i] == keys[i]) {
+                continue;
+            }
+
+            if !index.tree.get(&keys[i])?.is_empty() {
+                return Err(TableError::UniqueViolation(index.info.name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Applies the changes in order, the applied ones are reverted if one fails.
+    fn apply(&self, changes: Vec<IndexChange>) -> Result<(), TableError<'a>> {
+        for (applied, change) in changes.iter().enumerate() {
+            if let Err(err) = self.apply_change(change) {
+                for change in changes[..applied].iter().rev() {
+                    let _ = self.apply_change(&change.reverse());
+                }
+
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_change(&self, change: &IndexChange) -> Result<(), TableError<'a>> {
+        match change {
+            IndexChange::Insert(index, key, row_id) => {
+                self.indexes[*index].tree.insert(key, *row_id)?
+            }
+            IndexChange::Delete(index, key, row_id) => {
+                self.indexes[*index].tree.delete(key, *row_id)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl IndexChange {
+    fn reverse(&self) -> IndexChange {
+        match self {
+            IndexChange::Insert(index, key, row_id) => {
+                IndexChange::Delete(*index, key.clone(), *row_id)
+            }
+            IndexChange::Delete(index, key, row_id) => {
+                IndexChange::Insert(*index, key.clone(), *row_id)
+            }
+        }
+    }
+}
+
+fn key_values(tuple: &Tuple, columns: &[usize]) -> Vec<TupleValue> {
+    columns
+        .iter()
+        .map(|&column| tuple.values[column].clone())
+        .collect()
+}
+
+fn has_null(tuple: &Tuple, columns: &[usize]) -> bool {
+    columns.iter().any(|&column| tuple.values[column].is_null())
+}
@@ -1,16 +1,43 @@
-use std::{mem, num::TryFromIntError};
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    num::TryFromIntError,
+};
 
-use crate::util::type_converter::{int_to_bytes, string_to_bytes};
+use crate::schema::{DataType, Schema, SchemaError};
+use crate::util::decimal::{Decimal, MAX_PRECISION, RoundingMode};
+use crate::util::temporal::{Date, Interval, Time, Timestamp, TimestampTz};
+use crate::util::type_converter::{
+    big_int_to_bytes, bool_to_bytes, bytes_to_big_int, bytes_to_bool, bytes_to_double,
+    bytes_to_int, bytes_to_real, bytes_to_small_int, double_to_bytes, int_to_bytes, real_to_bytes,
+    small_int_to_bytes, string_to_bytes,
+};
+use crate::util::uuid::Uuid;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum TupleValue {
+    Null,
+    SmallInt(i16),
     Integer(i32),
+    BigInt(i64),
+    Real(f32),
+    Double(f64),
+    Boolean(bool),
     Varchar(String),
+    Char(String),
+    Bytea(Vec<u8>),
+    Uuid(Uuid),
+    Date(Date),
+    Time(Time),
+    Timestamp(Timestamp),
+    TimestampTz(TimestampTz),
+    Interval(Interval),
+    Decimal(Decimal),
 }
 
 #[derive(Debug)]
 pub struct Tuple<'a> {
-    pub types: &'a[&'a str],
+    pub schema: &'a Schema,
     pub values: Vec<TupleValue>,
 }
 
@@ -24,63 +51,130 @@ pub enum TypeConversionError {
 #[derive(Debug)]
 pub enum TupleToDataError {
     TypeConversionError(TypeConversionError),
+    ColumnCountMismatch(usize),
+    ValueTypeMismatch(usize),
+    NullInNotNullColumn(usize),
+    /// The decimal has more integer digits than the precision and scale of the column allow.
+    DecimalOutOfRange(usize),
+    /// The string has more characters than the char column, not counting trailing spaces.
+    CharTooLong(usize),
+}
+
+/// Tuple bytes that do not match the schema they are read with, e.g. from a
+/// corrupted page. Types are checked when a schema is built, so every column
+/// type is known here.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TupleDecodeError {
+    /// Length of data that is too short for the null bitmap, or longer than its columns.
+    BadLength(usize),
+    TruncatedColumn(usize),
+    InvalidUtf8(usize),
+    /// Bytes that no value encodes to, e.g. a bad escape in a key.
+    BadEncoding(usize),
 }
 
-impl <'a> From<TryFromIntError> for TupleToDataError {
+impl From<TryFromIntError> for TupleToDataError {
     fn from(err: TryFromIntError) -> TupleToDataError {
         TupleToDataError::TypeConversionError(TypeConversionError::IntConversionError(err))
     }
 }
 
 impl<'a> Tuple<'a> {
-    pub fn read(types: &'a[&str], data: &[u8]) -> Tuple<'a> {
-        let mut current_offset = 0;
-        let mut values = Vec::with_capacity(types.len());
+    pub fn read(schema: &'a Schema, data: &[u8]) -> Result<Tuple<'a>, TupleDecodeError> {
+        let Some(null_bitmap) = data.get(..schema.null_bitmap_size()) else {
+            return Err(TupleDecodeError::BadLength(data.len()));
+        };
+        let mut current_offset = null_bitmap.len();
+        let mut values = Vec::with_capacity(schema.len());
 
-        for &t in types {
-            let value = match t {
-                s if s.eq_ignore_ascii_case("integer") => {
-                    let bytes: [u8; 4] = data[current_offset..current_offset+mem::size_of::<i32>()]
-                        .try_into()
-                        .unwrap_or_else(| _ | panic!("Can't parse value to i32"));
+        for (column, data_type) in schema.data_types().enumerate() {
+            if !null_bitmap.is_empty() && is_null(null_bitmap, column) {
+                values.push(TupleValue::Null);
+                continue;
+            }
 
-                    current_offset += bytes.len();
-
-                    TupleValue::Integer(i32::from_be_bytes(bytes))
-                },
-                s if s.eq_ignore_ascii_case("varchar") => {
-                    let string_length = VarcharLength::from_be_bytes([ data[current_offset], data[current_offset+1] ]);
-                    let bytes = data[current_offset+2..current_offset+2+(string_length as usize)].to_vec();
+            let (bytes, next_offset) = column_bytes(data, current_offset, data_type, column)?;
+            current_offset = next_offset;
 
-                    current_offset += bytes.len() + std::mem::size_of::<VarcharLength>();
-
-                    unsafe {
-                        TupleValue::Varchar(String::from_utf8_unchecked(bytes))
-                    }
-                },
-                _ => panic!("Unsupported type {}", t),
-            };
+            let value = decode_value(data_type, bytes, column)?;
 
             values.push(value);
         }
 
-        Tuple {
-            types,
-            values,
+        if current_offset != data.len() {
+            return Err(TupleDecodeError::BadLength(data.len()));
         }
+
+        Ok(Tuple { schema, values })
+    }
+
+    /// Memcomparable key of `columns` of the tuple, see `encode_key`.
+    pub fn key(
+        &self,
+        columns: &[usize],
+        key_columns: &[KeyColumn],
+    ) -> Result<Vec<u8>, TupleToDataError> {
+        encode_key_values(key_columns, columns.iter().map(|c| &self.values[*c]))
     }
 
     pub fn to_data(&self) -> Result<Vec<u8>, TupleToDataError> {
-        let mut new_tuple: Vec<u8> = Vec::new();
-        for v in self.values.iter() {
+        if self.values.len() != self.schema.len() {
+            return Err(TupleToDataError::ColumnCountMismatch(self.values.len()));
+        }
+
+        let null_bitmap_size = self.schema.null_bitmap_size();
+        let mut new_tuple: Vec<u8> = vec![0; null_bitmap_size];
+
+        for (column, (v, schema_column)) in
+            self.values.iter().zip(self.schema.columns()).enumerate()
+        {
             let bytes: &[u8] = match v {
+                TupleValue::Null if !schema_column.nullable => {
+                    return Err(TupleToDataError::NullInNotNullColumn(column));
+                }
+                TupleValue::Null => {
+                    set_null(&mut new_tuple[..null_bitmap_size], column);
+
+                    &[]
+                }
+                v if !v.has_type(schema_column.data_type) => {
+                    return Err(TupleToDataError::ValueTypeMismatch(column));
+                }
+                TupleValue::SmallInt(i) => &small_int_to_bytes(i),
                 TupleValue::Integer(i) => &int_to_bytes(i),
-                TupleValue::Varchar(i) => {
-                    let string_bytes = string_to_bytes(i);
-                    let len: VarcharLength = string_bytes.len().try_into()?;
+                TupleValue::BigInt(i) => &big_int_to_bytes(i),
+                TupleValue::Real(f) => &real_to_bytes(f),
+                TupleValue::Double(f) => &double_to_bytes(f),
+                TupleValue::Boolean(b) => &bool_to_bytes(b),
+                TupleValue::Date(d) => &d.to_bytes(),
+                TupleValue::Time(t) => &t.to_bytes(),
+                TupleValue::Timestamp(t) => &t.to_bytes(),
+                TupleValue::TimestampTz(t) => &t.to_bytes(),
+                TupleValue::Interval(i) => &i.to_bytes(),
+                TupleValue::Decimal(d) => {
+                    let DataType::Decimal { precision, scale } = schema_column.data_type else {
+                        unreachable!()
+                    };
 
-                    &[len.to_be_bytes().as_slice(), string_bytes].concat()
-                },
+                    // Extra fractional digits are rounded as in SQL, extra integer digits are an error.
+                    match d.rescale(scale, RoundingMode::HalfUp) {
+                        Ok(d) if d.fits(precision, scale) => &d.to_bytes(precision),
+                        _ => return Err(TupleToDataError::DecimalOutOfRange(column)),
+                    }
+                }
+                TupleValue::Uuid(u) => &u.to_bytes(),
+                TupleValue::Varchar(i) => &length_prefixed(string_to_bytes(i))?,
+                TupleValue::Bytea(b) => &length_prefixed(b)?,
+                TupleValue::Char(c) => {
+                    let DataType::Char { length } = schema_column.data_type else {
+                        unreachable!()
+                    };
+
+                    match pad_char(c, length as usize) {
+                        Some(padded) => &length_prefixed(string_to_bytes(&padded))?,
+                        None => return Err(TupleToDataError::CharTooLong(column)),
+                    }
+                }
             };
 
             new_tuple.extend_from_slice(bytes);
@@ -90,18 +184,996 @@ impl<'a> Tuple<'a> {
     }
 }
 
+/// Tuple that borrows its bytes, e.g. from a page, and decodes a column only
+/// when it is asked for. Columns in front of it are skipped by their lengths.
+#[derive(Debug, Clone, Copy)]
+pub struct TupleRef<'a> {
+    pub schema: &'a Schema,
+    pub data: &'a [u8],
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TupleRefError {
+    NoSuchColumn(usize),
+    /// The column is not of the type of the getter.
+    TypeMismatch(usize),
+    TupleDecodeError(TupleDecodeError),
+}
+
+impl From<TupleDecodeError> for TupleRefError {
+    fn from(err: TupleDecodeError) -> TupleRefError {
+        TupleRefError::TupleDecodeError(err)
+    }
+}
+
+// Getters return `None` for a null value.
+impl<'a> TupleRef<'a> {
+    pub fn new(schema: &'a Schema, data: &'a [u8]) -> TupleRef<'a> {
+        TupleRef { schema, data }
+    }
+
+    pub fn is_null(self, column: usize) -> Result<bool, TupleRefError> {
+        Ok(self.column(column, |_| true)?.is_none())
+    }
+
+    /// Owned value of a column of any type.
+    pub fn get(self, column: usize) -> Result<TupleValue, TupleRefError> {
+        match self.column(column, |_| true)? {
+            Some(bytes) => Ok(decode_value(
+                self.schema.columns()[column].data_type,
+                bytes,
+                column,
+            )?),
+            None => Ok(TupleValue::Null),
+        }
+    }
+
+    pub fn get_i16(self, column: usize) -> Result<Option<i16>, TupleRefError> {
+        Ok(self
+            .column(column, |t| t == DataType::SmallInt)?
+            .map(bytes_to_small_int))
+    }
+
+    pub fn get_i32(self, column: usize) -> Result<Option<i32>, TupleRefError> {
+        Ok(self
+            .column(column, |t| t == DataType::Integer)?
+            .map(bytes_to_int))
+    }
+
+    pub fn get_i64(self, column: usize) -> Result<Option<i64>, TupleRefError> {
+        Ok(self
+            .column(column, |t| t == DataType::BigInt)?
+            .map(bytes_to_big_int))
+    }
+
+    pub fn get_f32(self, column: usize) -> Result<Option<f32>, TupleRefError> {
+        Ok(self
+            .column(column, |t| t == DataType::Real)?
+            .map(bytes_to_real))
+    }
+
+    pub fn get_f64(self, column: usize) -> Result<Option<f64>, TupleRefError> {
+        Ok(self
+            .column(column, |t| t == DataType::Double)?
+            .map(bytes_to_double))
+    }
+
+    pub fn get_bool(self, column: usize) -> Result<Option<bool>, TupleRefError> {
+        Ok(self
+            .column(column, |t| t == DataType::Boolean)?
+            .map(bytes_to_bool))
+    }
+
+    /// Varchar or blank padded char column, borrowed from the tuple bytes.
+    pub fn get_str(self, column: usize) -> Result<Option<&'a str>, TupleRefError> {
+        match self.column(column, |t| {
+            matches!(t, DataType::Varchar | DataType::Char { .. })
+        })? {
+            Some(bytes) => Ok(Some(decode_str(bytes, column)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_bytes(self, column: usize) -> Result<Option<&'a [u8]>, TupleRefError> {
+        self.column(column, |t| t == DataType::Bytea)
+    }
+
+    /// Decodes every column, checking the length of the data as `Tuple::read` does.
+    pub fn to_tuple(self) -> Result<Tuple<'a>, TupleDecodeError> {
+        Tuple::read(self.schema, self.data)
+    }
+
+    fn column(
+        self,
+        column: usize,
+        has_type: impl Fn(DataType) -> bool,
+    ) -> Result<Option<&'a [u8]>, TupleRefError> {
+        let Some(schema_column) = self.schema.columns().get(column) else {
+            return Err(TupleRefError::NoSuchColumn(column));
+        };
+        if !has_type(schema_column.data_type) {
+            return Err(TupleRefError::TypeMismatch(column));
+        }
+
+        let Some(null_bitmap) = self.data.get(..self.schema.null_bitmap_size()) else {
+            return Err(TupleDecodeError::BadLength(self.data.len()).into());
+        };
+        let is_null = |c| !null_bitmap.is_empty() && is_null(null_bitmap, c);
+
+        if is_null(column) {
+            return Ok(None);
+        }
+
+        let mut offset = null_bitmap.len();
+        for (c, data_type) in self.schema.data_types().enumerate().take(column) {
+            if !is_null(c) {
+                offset = column_bytes(self.data, offset, data_type, c)?.1;
+            }
+        }
+
+        Ok(Some(
+            column_bytes(self.data, offset, schema_column.data_type, column)?.0,
+        ))
+    }
+}
+
+/// Reads a subset of columns, in the requested order, skipping the bytes of
+/// the others. Columns after the last requested one are never looked at.
+///
+/// When every column in front of the requested ones is fixed size and not
+/// null, the offsets are the same in every tuple and computed once.
+#[derive(Debug, Clone)]
+pub struct Projection<'a> {
+    schema: &'a Schema,
+    columns: Vec<usize>,
+    offsets: Option<Vec<usize>>,
+}
+
+impl<'a> Projection<'a> {
+    pub fn new(schema: &'a Schema, columns: &[usize]) -> Result<Projection<'a>, SchemaError> {
+        if let Some(column) = columns.iter().find(|c| **c >= schema.len()) {
+            return Err(SchemaError::ColumnNotFound(*column));
+        }
+
+        let last = columns.iter().max().map_or(0, |c| c + 1);
+        let schema_columns = &schema.columns()[..last];
+
+        let fixed = schema_columns
+            .iter()
+            .all(|c| c.data_type.fixed_size().is_some())
+            && schema_columns.iter().rev().skip(1).all(|c| !c.nullable);
+
+        let offsets = fixed.then(|| {
+            schema_columns
+                .iter()
+                .scan(schema.null_bitmap_size(), |offset, c| {
+                    let start = *offset;
+                    *offset += c.data_type.fixed_size().unwrap();
+
+                    Some(start)
+                })
+                .collect()
+        });
+
+        Ok(Projection {
+            schema,
+            columns: columns.to_vec(),
+            offsets,
+        })
+    }
+
+    pub fn columns(&self) -> &[usize] {
+        &self.columns
+    }
+
+    pub fn read(&self, data: &[u8]) -> Result<Vec<TupleValue>, TupleDecodeError> {
+        let Some(null_bitmap) = data.get(..self.schema.null_bitmap_size()) else {
+            return Err(TupleDecodeError::BadLength(data.len()));
+        };
+        let is_null = |c| !null_bitmap.is_empty() && is_null(null_bitmap, c);
+
+        if let Some(offsets) = &self.offsets {
+            return self
+                .columns
+                .iter()
+                .map(|&c| {
+                    if is_null(c) {
+                        return Ok(TupleValue::Null);
+                    }
+
+                    let data_type = self.schema.columns()[c].data_type;
+                    let (bytes, _) = column_bytes(data, offsets[c], data_type, c)?;
+
+                    decode_value(data_type, bytes, c)
+                })
+                .collect();
+        }
+
+        let last = self.columns.iter().max().map_or(0, |c| c + 1);
+        let mut values = (0..self.columns.len())
+            .map(|_| TupleValue::Null)
+            .collect::<Vec<_>>();
+        let mut offset = null_bitmap.len();
+
+        for (c, data_type) in self.schema.data_types().enumerate().take(last) {
+            if is_null(c) {
+                continue;
+            }
+
+            let (bytes, next_offset) = column_bytes(data, offset, data_type, c)?;
+            offset = next_offset;
+
+            for (i, _) in self.columns.iter().enumerate().filter(|(_, p)| **p == c) {
+                values[i] = decode_value(data_type, bytes, c)?;
+            }
+        }
+
+        Ok(values)
+    }
+}
+
+/// Column of a memcomparable key: keys compare with `memcmp` in the order of
+/// their values. Ascending keys put nulls last and descending ones first, as
+/// in PostgreSQL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyColumn {
+    pub data_type: DataType,
+    pub descending: bool,
+    pub nulls_first: bool,
+}
+
+impl KeyColumn {
+    pub fn new(data_type: DataType) -> KeyColumn {
+        KeyColumn {
+            data_type,
+            descending: false,
+            nulls_first: false,
+        }
+    }
+
+    pub fn descending(mut self) -> KeyColumn {
+        self.descending = true;
+        self.nulls_first = true;
+        self
+    }
+
+    pub fn nulls_first(mut self) -> KeyColumn {
+        self.nulls_first = true;
+        self
+    }
+
+    pub fn nulls_last(mut self) -> KeyColumn {
+        self.nulls_first = false;
+        self
+    }
+}
+
+// Every key column starts with a marker, the null marker sorts on the chosen side of values.
+const NULL_FIRST: u8 = 0x00;
+const NOT_NULL: u8 = 0x01;
+const NULL_LAST: u8 = 0x02;
+// Strings end with 0x00 0x01 and their zero bytes become 0x00 0xff, so a
+// string sorts before every longer string it is a prefix of.
+const ESCAPE: u8 = 0x00;
+const ESCAPED_ZERO: u8 = 0xff;
+const TERMINATOR: u8 = 0x01;
+
+/// Encodes `values` as a key that sorts bytewise like the values: integers
+/// with a flipped sign bit, floats with ordered bits, strings escaped and
+/// terminated. Bytes of descending columns are inverted.
+///
+/// Equal values encode equally: char values without their padding, decimals
+/// at the scale of the column, -0.0 as 0.0 and every NaN as one NaN.
+/// Intervals are ordered by their span but keep their fields, so intervals
+/// of equal span with different fields, e.g. 1 month and 30 days, have
+/// different keys.
+pub fn encode_key(
+    columns: &[KeyColumn],
+    values: &[TupleValue],
+) -> Result<Vec<u8>, TupleToDataError> {
+    encode_key_values(columns, values.iter())
+}
+
+pub fn decode_key(columns: &[KeyColumn], key: &[u8]) -> Result<Vec<TupleValue>, TupleDecodeError> {
+    let mut reader = KeyReader {
+        key,
+        offset: 0,
+        mask: 0,
+    };
+    let mut values = Vec::with_capacity(columns.len());
+
+    for (column, key_column) in columns.iter().enumerate() {
+        reader.mask = 0;
+
+        match reader.read(1, column)?[0] {
+            NOT_NULL => {}
+            NULL_FIRST | NULL_LAST => {
+                values.push(TupleValue::Null);
+                continue;
+            }
+            _ => return Err(TupleDecodeError::BadEncoding(column)),
+        }
+
+        if key_column.descending {
+            reader.mask = 0xff;
+        }
+
+        let value = match key_column.data_type {
+            DataType::SmallInt => TupleValue::SmallInt(
+                (u16::from_be_bytes(reader.read_array(column)?) ^ 1 << 15) as i16,
+            ),
+            DataType::Integer => TupleValue::Integer(unflip_i32(reader.read_array(column)?)),
+            DataType::BigInt => TupleValue::BigInt(unflip_i64(reader.read_array(column)?)),
+            DataType::Real => TupleValue::Real(unorder_f32(reader.read_array(column)?)),
+            DataType::Double => TupleValue::Double(unorder_f64(reader.read_array(column)?)),
+            DataType::Boolean => TupleValue::Boolean(reader.read(1, column)?[0] != 0),
+            DataType::Date => TupleValue::Date(Date(unflip_i32(reader.read_array(column)?))),
+            DataType::Time => TupleValue::Time(Time(unflip_i64(reader.read_array(column)?))),
+            DataType::Timestamp => {
+                TupleValue::Timestamp(Timestamp(unflip_i64(reader.read_array(column)?)))
+            }
+            DataType::TimestampTz => {
+                TupleValue::TimestampTz(TimestampTz(unflip_i64(reader.read_array(column)?)))
+            }
+            DataType::Interval => {
+                reader.read(16, column)?;
+
+                TupleValue::Interval(Interval::new(
+                    unflip_i32(reader.read_array(column)?),
+                    unflip_i32(reader.read_array(column)?),
+                    unflip_i64(reader.read_array(column)?),
+                ))
+            }
+            DataType::Decimal { scale, .. } => {
+                let value = u128::from_be_bytes(reader.read_array(column)?) ^ 1 << 127;
+
+                TupleValue::Decimal(Decimal::new(value as i128, scale))
+            }
+            DataType::Uuid => TupleValue::Uuid(Uuid(reader.read_array(column)?)),
+            DataType::Bytea => TupleValue::Bytea(reader.read_escaped(column)?),
+            DataType::Varchar => {
+                TupleValue::Varchar(decode_str(&reader.read_escaped(column)?, column)?.to_owned())
+            }
+            DataType::Char { length } => {
+                let bytes = reader.read_escaped(column)?;
+                let value = decode_str(&bytes, column)?;
+
+                TupleValue::Char(
+                    pad_char(value, length as usize).unwrap_or_else(|| value.to_owned()),
+                )
+            }
+        };
+
+        values.push(value);
+    }
+
+    if reader.offset != key.len() {
+        return Err(TupleDecodeError::BadLength(key.len()));
+    }
+
+    Ok(values)
+}
+
+fn encode_key_values<'v>(
+    columns: &[KeyColumn],
+    values: impl ExactSizeIterator<Item = &'v TupleValue>,
+) -> Result<Vec<u8>, TupleToDataError> {
+    if values.len() != columns.len() {
+        return Err(TupleToDataError::ColumnCountMismatch(values.len()));
+    }
+
+    let mut key = vec![];
+
+    for (column, (value, key_column)) in values.zip(columns).enumerate() {
+        if value.is_null() {
+            key.push(if key_column.nulls_first {
+                NULL_FIRST
+            } else {
+                NULL_LAST
+            });
+            continue;
+        }
+        if !value.has_type(key_column.data_type) {
+            return Err(TupleToDataError::ValueTypeMismatch(column));
+        }
+
+        key.push(NOT_NULL);
+        let start = key.len();
+
+        match value {
+            TupleValue::Null => {}
+            TupleValue::SmallInt(i) => key.extend_from_slice(&(*i as u16 ^ 1 << 15).to_be_bytes()),
+            TupleValue::Integer(i) => key.extend_from_slice(&flip_i32(*i)),
+            TupleValue::BigInt(i) => key.extend_from_slice(&flip_i64(*i)),
+            TupleValue::Real(f) => key.extend_from_slice(&order_f32(*f)),
+            TupleValue::Double(f) => key.extend_from_slice(&order_f64(*f)),
+            TupleValue::Boolean(b) => key.push(*b as u8),
+            TupleValue::Date(d) => key.extend_from_slice(&flip_i32(d.0)),
+            TupleValue::Time(t) => key.extend_from_slice(&flip_i64(t.0)),
+            TupleValue::Timestamp(t) => key.extend_from_slice(&flip_i64(t.0)),
+            TupleValue::TimestampTz(t) => key.extend_from_slice(&flip_i64(t.0)),
+            TupleValue::Interval(i) => {
+                key.extend_from_slice(&(i.span() as u128 ^ 1 << 127).to_be_bytes());
+                key.extend_from_slice(&flip_i32(i.months));
+                key.extend_from_slice(&flip_i32(i.days));
+                key.extend_from_slice(&flip_i64(i.micros));
+            }
+            TupleValue::Decimal(d) => {
+                let DataType::Decimal { precision, scale } = key_column.data_type else {
+                    unreachable!()
+                };
+
+                match d.rescale(scale, RoundingMode::HalfUp) {
+                    Ok(d) if d.fits(precision, scale) => {
+                        key.extend_from_slice(&(d.value as u128 ^ 1 << 127).to_be_bytes())
+                    }
+                    _ => return Err(TupleToDataError::DecimalOutOfRange(column)),
+                }
+            }
+            TupleValue::Uuid(u) => key.extend_from_slice(&u.0),
+            TupleValue::Bytea(b) => escape(b, &mut key),
+            TupleValue::Varchar(s) => escape(s.as_bytes(), &mut key),
+            TupleValue::Char(c) => {
+                let DataType::Char { length } = key_column.data_type else {
+                    unreachable!()
+                };
+
+                match pad_char(c, length as usize) {
+                    Some(padded) => escape(padded.trim_end_matches(' ').as_bytes(), &mut key),
+                    None => return Err(TupleToDataError::CharTooLong(column)),
+                }
+            }
+        }
+
+        if key_column.descending {
+            key[start..].iter_mut().for_each(|b| *b = !*b);
+        }
+    }
+
+    Ok(key)
+}
+
+fn escape(bytes: &[u8], key: &mut Vec<u8>) {
+    for byte in bytes {
+        match *byte {
+            0 => key.extend_from_slice(&[ESCAPE, ESCAPED_ZERO]),
+            byte => key.push(byte),
+        }
+    }
+
+    key.extend_from_slice(&[ESCAPE, TERMINATOR]);
+}
+
+fn flip_i32(value: i32) -> [u8; 4] {
+    (value as u32 ^ 1 << 31).to_be_bytes()
+}
+
+fn flip_i64(value: i64) -> [u8; 8] {
+    (value as u64 ^ 1 << 63).to_be_bytes()
+}
+
+fn unflip_i32(bytes: [u8; 4]) -> i32 {
+    (u32::from_be_bytes(bytes) ^ 1 << 31) as i32
+}
+
+fn unflip_i64(bytes: [u8; 8]) -> i64 {
+    (u64::from_be_bytes(bytes) ^ 1 << 63) as i64
+}
+
+// Negative floats have all bits inverted, positive ones the sign bit only.
+// There is one NaN that sorts after infinity, and no negative zero.
+fn order_f32(f: f32) -> [u8; 4] {
+    let f = if f.is_nan() {
+        f32::NAN
+    } else if f == 0.0 {
+        0.0
+    } else {
+        f
+    };
+    let bits = f.to_bits();
+
+    (if bits >> 31 == 1 {
+        !bits
+    } else {
+        bits | 1 << 31
+    })
+    .to_be_bytes()
+}
+
+fn order_f64(f: f64) -> [u8; 8] {
+    let f = if f.is_nan() {
+        f64::NAN
+    } else if f == 0.0 {
+        0.0
+    } else {
+        f
+    };
+    let bits = f.to_bits();
+
+    (if bits >> 63 == 1 {
+        !bits
+    } else {
+        bits | 1 << 63
+    })
+    .to_be_bytes()
+}
+
+fn unorder_f32(bytes: [u8; 4]) -> f32 {
+    let bits = u32::from_be_bytes(bytes);
+
+    f32::from_bits(if bits >> 31 == 1 {
+        bits ^ 1 << 31
+    } else {
+        !bits
+    })
+}
+
+fn unorder_f64(bytes: [u8; 8]) -> f64 {
+    let bits = u64::from_be_bytes(bytes);
+
+    f64::from_bits(if bits >> 63 == 1 {
+        bits ^ 1 << 63
+    } else {
+        !bits
+    })
+}
+
+struct KeyReader<'k> {
+    key: &'k [u8],
+    offset: usize,
+    // Inverts bytes of descending columns.
+    mask: u8,
+}
+
+impl<'k> KeyReader<'k> {
+    fn read(&mut self, length: usize, column: usize) -> Result<Vec<u8>, TupleDecodeError> {
+        let Some(bytes) = self.key.get(self.offset..self.offset + length) else {
+            return Err(TupleDecodeError::TruncatedColumn(column));
+        };
+        self.offset += length;
+
+        Ok(bytes.iter().map(|b| b ^ self.mask).collect())
+    }
+
+    fn read_array<const N: usize>(&mut self, column: usize) -> Result<[u8; N], TupleDecodeError> {
+        Ok(self.read(N, column)?.try_into().unwrap())
+    }
+
+    fn read_escaped(&mut self, column: usize) -> Result<Vec<u8>, TupleDecodeError> {
+        let mut bytes = vec![];
+
+        loop {
+            match self.read(1, column)?[0] {
+                ESCAPE => match self.read(1, column)?[0] {
+                    ESCAPED_ZERO => bytes.push(0),
+                    TERMINATOR => return Ok(bytes),
+                    _ => return Err(TupleDecodeError::BadEncoding(column)),
+                },
+                byte => bytes.push(byte),
+            }
+        }
+    }
+}
+
+// Bytes of the non-null column that starts at `offset`, and the offset of the next column.
+fn column_bytes(
+    data: &[u8],
+    offset: usize,
+    data_type: DataType,
+    column: usize,
+) -> Result<(&[u8], usize), TupleDecodeError> {
+    let mut offset = offset;
+
+    let size = match data_type.fixed_size() {
+        Some(size) => size,
+        None => {
+            let Some(length) = data.get(offset..offset + std::mem::size_of::<VarcharLength>())
+            else {
+                return Err(TupleDecodeError::TruncatedColumn(column));
+            };
+            offset += length.len();
+
+            VarcharLength::from_be_bytes([length[0], length[1]]) as usize
+        }
+    };
+
+    match data.get(offset..offset + size) {
+        Some(bytes) => Ok((bytes, offset + size)),
+        None => Err(TupleDecodeError::TruncatedColumn(column)),
+    }
+}
+
+fn decode_value(
+    data_type: DataType,
+    bytes: &[u8],
+    column: usize,
+) -> Result<TupleValue, TupleDecodeError> {
+    Ok(match data_type {
+        DataType::SmallInt => TupleValue::SmallInt(bytes_to_small_int(bytes)),
+        DataType::Integer => TupleValue::Integer(bytes_to_int(bytes)),
+        DataType::BigInt => TupleValue::BigInt(bytes_to_big_int(bytes)),
+        DataType::Real => TupleValue::Real(bytes_to_real(bytes)),
+        DataType::Double => TupleValue::Double(bytes_to_double(bytes)),
+        DataType::Boolean => TupleValue::Boolean(bytes_to_bool(bytes)),
+        DataType::Date => TupleValue::Date(Date::from_bytes(bytes)),
+        DataType::Time => TupleValue::Time(Time::from_bytes(bytes)),
+        DataType::Timestamp => TupleValue::Timestamp(Timestamp::from_bytes(bytes)),
+        DataType::TimestampTz => TupleValue::TimestampTz(TimestampTz::from_bytes(bytes)),
+        DataType::Interval => TupleValue::Interval(Interval::from_bytes(bytes)),
+        DataType::Decimal { precision, scale } => {
+            TupleValue::Decimal(Decimal::from_bytes(bytes, precision, scale))
+        }
+        DataType::Uuid => TupleValue::Uuid(Uuid::from_bytes(bytes)),
+        DataType::Bytea => TupleValue::Bytea(bytes.to_vec()),
+        DataType::Char { .. } => TupleValue::Char(decode_str(bytes, column)?.to_owned()),
+        DataType::Varchar => TupleValue::Varchar(decode_str(bytes, column)?.to_owned()),
+    })
+}
+
+fn decode_str(bytes: &[u8], column: usize) -> Result<&str, TupleDecodeError> {
+    std::str::from_utf8(bytes).or(Err(TupleDecodeError::InvalidUtf8(column)))
+}
+
+fn length_prefixed(bytes: &[u8]) -> Result<Vec<u8>, TupleToDataError> {
+    let len: VarcharLength = bytes.len().try_into()?;
+
+    Ok([len.to_be_bytes().as_slice(), bytes].concat())
+}
+
+// Pads a char value with spaces to `length` characters. Longer values are cut
+// if only spaces are cut, as in SQL.
+fn pad_char(value: &str, length: usize) -> Option<String> {
+    let count = value.chars().count();
+    if count <= length {
+        return Some(format!("{}{}", value, " ".repeat(length - count)));
+    }
+
+    let (cut, rest) = value.split_at(value.char_indices().nth(length).unwrap().0);
+    rest.chars().all(|c| c == ' ').then(|| cut.to_owned())
+}
+
+// Bit `column % 8` of byte `column / 8` is set for a null value.
+fn is_null(null_bitmap: &[u8], column: usize) -> bool {
+    null_bitmap[column / 8] & (1 << (column % 8)) != 0
+}
+
+fn set_null(null_bitmap: &mut [u8], column: usize) {
+    null_bitmap[column / 8] |= 1 << (column % 8);
+}
+
+/// Tuples compare by their values, column by column, as SQL rows do. Their
+/// schemas are not compared.
 impl<'a> PartialEq for Tuple<'a> {
     fn eq(&self, other: &Self) -> bool {
-        self.types == other.types && self.values == other.values
+        self.values == other.values
     }
 }
 
-impl<'a> PartialEq for TupleValue {
-    fn eq(&self, other: &Self) -> bool {
+impl<'a> Eq for Tuple<'a> {}
+
+impl<'a> PartialOrd for Tuple<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for Tuple<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.values.cmp(&other.values)
+    }
+}
+
+impl<'a> Hash for Tuple<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.values.hash(state);
+    }
+}
+
+impl<'a> Tuple<'a> {
+    /// Compares the values of `columns` only, e.g. the sort or join key of tuples.
+    pub fn compare_on(&self, other: &Tuple, columns: &[usize]) -> Ordering {
+        self.compare_on_collated(other, columns, &Binary)
+    }
+
+    pub fn compare_on_collated(
+        &self,
+        other: &Tuple,
+        columns: &[usize],
+        collation: &dyn Collation,
+    ) -> Ordering {
+        columns
+            .iter()
+            .map(|c| self.values[*c].cmp_collated(&other.values[*c], collation))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Order of strings. Strings a collation finds equal must hash equally.
+pub trait Collation {
+    fn compare(&self, l: &str, r: &str) -> Ordering;
+
+    fn hash(&self, s: &str, state: &mut dyn Hasher);
+}
+
+/// Orders strings by their code points, which is also the order of their UTF-8 bytes.
+pub struct Binary;
+
+impl Collation for Binary {
+    fn compare(&self, l: &str, r: &str) -> Ordering {
+        l.cmp(r)
+    }
+
+    fn hash(&self, s: &str, mut state: &mut dyn Hasher) {
+        s.hash(&mut state);
+    }
+}
+
+/// Orders strings by their lowercase form.
+pub struct CaseInsensitive;
+
+impl Collation for CaseInsensitive {
+    fn compare(&self, l: &str, r: &str) -> Ordering {
+        l.chars()
+            .flat_map(char::to_lowercase)
+            .cmp(r.chars().flat_map(char::to_lowercase))
+    }
+
+    fn hash(&self, s: &str, mut state: &mut dyn Hasher) {
+        for c in s.chars().flat_map(char::to_lowercase) {
+            c.hash(&mut state);
+        }
+        // Same terminator as `str::hash`, so "ab" + "c" and "a" + "bc" differ.
+        state.write_u8(0xff);
+    }
+}
+
+// Values of different families are never equal and are ordered by family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Family {
+    Boolean,
+    Number,
+    String,
+    Bytea,
+    Uuid,
+    DateTime,
+    Time,
+    Interval,
+    Null,
+}
+
+// Numbers of all types are compared by their exact value.
+enum Number {
+    Exact(Decimal),
+    Float(f64),
+}
+
+impl TupleValue {
+    /// Type of the value, `None` for null which fits a column of any type.
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            TupleValue::Null => None,
+            TupleValue::SmallInt(_) => Some(DataType::SmallInt),
+            TupleValue::Integer(_) => Some(DataType::Integer),
+            TupleValue::BigInt(_) => Some(DataType::BigInt),
+            TupleValue::Real(_) => Some(DataType::Real),
+            TupleValue::Double(_) => Some(DataType::Double),
+            TupleValue::Boolean(_) => Some(DataType::Boolean),
+            TupleValue::Varchar(_) => Some(DataType::Varchar),
+            TupleValue::Char(c) => Some(DataType::Char {
+                length: c.chars().count() as u16,
+            }),
+            TupleValue::Bytea(_) => Some(DataType::Bytea),
+            TupleValue::Uuid(_) => Some(DataType::Uuid),
+            TupleValue::Date(_) => Some(DataType::Date),
+            TupleValue::Time(_) => Some(DataType::Time),
+            TupleValue::Timestamp(_) => Some(DataType::Timestamp),
+            TupleValue::TimestampTz(_) => Some(DataType::TimestampTz),
+            TupleValue::Interval(_) => Some(DataType::Interval),
+            TupleValue::Decimal(d) => Some(DataType::Decimal {
+                precision: MAX_PRECISION,
+                scale: d.scale,
+            }),
+        }
+    }
+
+    /// Whether the value can be stored in a column of `data_type`. A decimal
+    /// fits any decimal column and a char any char column, they are rescaled
+    /// or padded and checked on write.
+    pub fn has_type(&self, data_type: DataType) -> bool {
+        match (self, data_type) {
+            (TupleValue::Decimal(_), DataType::Decimal { .. }) => true,
+            (TupleValue::Char(_), DataType::Char { .. }) => true,
+            _ => self.data_type() == Some(data_type),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, TupleValue::Null)
+    }
+
+    /// Comparison of SQL expressions: unknown, i.e. `None`, if either value is
+    /// null or the values are of types that cannot be compared.
+    pub fn sql_cmp(&self, other: &TupleValue) -> Option<Ordering> {
+        if self.is_null() || other.is_null() || self.family() != other.family() {
+            return None;
+        }
+
+        Some(self.cmp(other))
+    }
+
+    /// Total order with strings compared by `collation`, see `Ord`.
+    pub fn cmp_collated(&self, other: &TupleValue, collation: &dyn Collation) -> Ordering {
+        let family = self.family();
+        if family != other.family() {
+            return family.cmp(&other.family());
+        }
+
         match (self, other) {
-            (Self::Integer(l0), Self::Integer(r0)) => l0 == r0,
-            (Self::Varchar(l0), Self::Varchar(r0)) => l0 == r0,
-            _ => false,
+            (Self::Boolean(l0), Self::Boolean(r0)) => l0.cmp(r0),
+            (Self::Bytea(l0), Self::Bytea(r0)) => l0.cmp(r0),
+            (Self::Uuid(l0), Self::Uuid(r0)) => l0.cmp(r0),
+            (Self::Time(l0), Self::Time(r0)) => l0.cmp(r0),
+            (Self::Interval(l0), Self::Interval(r0)) => l0.cmp(r0),
+            _ => match family {
+                Family::Number => match (self.number().unwrap(), other.number().unwrap()) {
+                    (Number::Exact(l0), Number::Exact(r0)) => l0.cmp(&r0),
+                    (Number::Float(l0), Number::Float(r0)) => compare_floats(l0, r0),
+                    (Number::Exact(_), Number::Float(r0)) if r0.is_nan() => Ordering::Less,
+                    (Number::Float(l0), Number::Exact(_)) if l0.is_nan() => Ordering::Greater,
+                    (Number::Exact(l0), Number::Float(r0)) => l0.cmp_f64(r0),
+                    (Number::Float(l0), Number::Exact(r0)) => r0.cmp_f64(l0).reverse(),
+                },
+                Family::String => {
+                    collation.compare(self.string().unwrap(), other.string().unwrap())
+                }
+                Family::DateTime => self.micros().cmp(&other.micros()),
+                _ => Ordering::Equal,
+            },
         }
     }
-}
\ No newline at end of file
+
+    /// Hash that agrees with `cmp_collated`: values of different types that are
+    /// equal, e.g. 1 and 1.0, hash equally.
+    pub fn hash_collated(&self, mut state: &mut dyn Hasher, collation: &dyn Collation) {
+        self.family().hash(&mut state);
+
+        match self {
+            Self::Null => {}
+            Self::Boolean(b) => b.hash(&mut state),
+            Self::Bytea(b) => b.hash(&mut state),
+            Self::Uuid(u) => u.hash(&mut state),
+            Self::Time(t) => t.hash(&mut state),
+            Self::Interval(i) => i.span().hash(&mut state),
+            Self::Varchar(_) | Self::Char(_) => collation.hash(self.string().unwrap(), state),
+            Self::Date(_) | Self::Timestamp(_) | Self::TimestampTz(_) => {
+                self.micros().hash(&mut state)
+            }
+            Self::SmallInt(_)
+            | Self::Integer(_)
+            | Self::BigInt(_)
+            | Self::Decimal(_)
+            | Self::Real(_)
+            | Self::Double(_) => {
+                // A number equal to a double hashes as the double, any other
+                // exact number by its value without trailing zeros.
+                let f = match self.number().unwrap() {
+                    Number::Float(f) => f,
+                    Number::Exact(d) => match d.to_f64_exact() {
+                        Some(f) => f,
+                        None => {
+                            let d = d.normalize();
+                            return (d.value, d.scale).hash(&mut state);
+                        }
+                    },
+                };
+                let f = if f.is_nan() {
+                    f64::NAN
+                } else if f == 0.0 {
+                    0.0
+                } else {
+                    f
+                };
+
+                f.to_bits().hash(&mut state);
+            }
+        }
+    }
+
+    fn family(&self) -> Family {
+        match self {
+            Self::Null => Family::Null,
+            Self::Boolean(_) => Family::Boolean,
+            Self::SmallInt(_)
+            | Self::Integer(_)
+            | Self::BigInt(_)
+            | Self::Decimal(_)
+            | Self::Real(_)
+            | Self::Double(_) => Family::Number,
+            Self::Varchar(_) | Self::Char(_) => Family::String,
+            Self::Bytea(_) => Family::Bytea,
+            Self::Uuid(_) => Family::Uuid,
+            Self::Date(_) | Self::Timestamp(_) | Self::TimestampTz(_) => Family::DateTime,
+            Self::Time(_) => Family::Time,
+            Self::Interval(_) => Family::Interval,
+        }
+    }
+
+    fn number(&self) -> Option<Number> {
+        match self {
+            Self::SmallInt(i) => Some(Number::Exact(Decimal::new(*i as i128, 0))),
+            Self::Integer(i) => Some(Number::Exact(Decimal::new(*i as i128, 0))),
+            Self::BigInt(i) => Some(Number::Exact(Decimal::new(*i as i128, 0))),
+            Self::Decimal(d) => Some(Number::Exact(*d)),
+            Self::Real(f) => Some(Number::Float(*f as f64)),
+            Self::Double(f) => Some(Number::Float(*f)),
+            _ => None,
+        }
+    }
+
+    // Char values are compared without their padding, as in PostgreSQL.
+    fn string(&self) -> Option<&str> {
+        match self {
+            Self::Varchar(s) => Some(s),
+            Self::Char(c) => Some(c.trim_end_matches(' ')),
+            _ => None,
+        }
+    }
+
+    // A timestamp without a time zone is taken as UTC, a date as its midnight.
+    fn micros(&self) -> i128 {
+        match self {
+            Self::Date(d) => d.micros(),
+            Self::Timestamp(t) => t.0 as i128,
+            Self::TimestampTz(t) => t.0 as i128,
+            _ => 0,
+        }
+    }
+}
+
+impl PartialEq for TupleValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TupleValue {}
+
+impl PartialOrd for TupleValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Total order for sorting, grouping and joins, with strings in the `Binary`
+/// collation. Use `sql_cmp` for the three-valued comparison of SQL.
+///
+/// - Nulls equal each other and sort after every other value.
+/// - Numbers of all types compare by their exact value, a double equals only
+///   the decimals and integers of exactly its value. NaN equals NaN and is
+///   greater than any other number, as in PostgreSQL.
+/// - Char values are compared without trailing spaces, also with varchar.
+/// - Dates, timestamps and timestamps with a time zone compare as instants in UTC.
+/// - Values of types that cannot be compared are ordered by type: booleans,
+///   numbers, strings, bytea, uuids, date and time values, times, intervals.
+impl Ord for TupleValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_collated(other, &Binary)
+    }
+}
+
+impl Hash for TupleValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash_collated(state, &Binary);
+    }
+}
+
+fn compare_floats(l: f64, r: f64) -> Ordering {
+    match (l.is_nan(), r.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        // -0.0 and 0.0 are equal.
+        (false, false) => l.partial_cmp(&r).unwrap(),
+    }
+}
@@ -0,0 +1,444 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Digits an `i128` always holds.
+pub const MAX_PRECISION: u8 = 38;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecimalError {
+    Overflow,
+    DivisionByZero,
+    InvalidFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Towards zero.
+    Down,
+    /// Away from zero.
+    Up,
+    Floor,
+    Ceiling,
+    HalfUp,
+    HalfDown,
+    /// Banker's rounding, ties go to the even digit.
+    HalfEven,
+}
+
+/// Exact number `value * 10^-scale`.
+///
+/// Numbers with different scales are equal when they have the same value,
+/// e.g. 1.5 and 1.50.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    pub value: i128,
+    pub scale: u8,
+}
+
+impl Decimal {
+    /// `scale` is at most `MAX_PRECISION`, arithmetic fails with a larger one.
+    pub fn new(value: i128, scale: u8) -> Decimal {
+        debug_assert!(scale <= MAX_PRECISION, "Scale {} is too large", scale);
+
+        Decimal { value, scale }
+    }
+
+    /// Parses `[+-]digits[.digits]`, the scale is the number of fractional digits.
+    pub fn parse(s: &str) -> Result<Decimal, DecimalError> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if integer.is_empty() && fraction.is_empty()
+            || !integer
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return Err(DecimalError::InvalidFormat);
+        }
+        if fraction.len() > MAX_PRECISION as usize {
+            return Err(DecimalError::Overflow);
+        }
+
+        let mut value: i128 = 0;
+        for digit in integer.bytes().chain(fraction.bytes()) {
+            value = value
+                .checked_mul(10)
+                .and_then(|v| v.checked_add((digit - b'0') as i128))
+                .ok_or(DecimalError::Overflow)?;
+        }
+
+        Ok(Decimal::new(
+            if negative { -value } else { value },
+            fraction.len() as u8,
+        ))
+    }
+
+    /// Number of digits of the unscaled value.
+    pub fn digits(&self) -> u8 {
+        let mut digits = 1;
+        let mut value = self.value.unsigned_abs() / 10;
+
+        while value > 0 {
+            digits += 1;
+            value /= 10;
+        }
+
+        digits
+    }
+
+    /// Whether the number fits DECIMAL(precision, scale) without losing digits.
+    pub fn fits(&self, precision: u8, scale: u8) -> bool {
+        match self.rescale(scale, RoundingMode::Down) {
+            Ok(rescaled) => {
+                rescaled == *self && rescaled.value.unsigned_abs() < 10_u128.pow(precision as u32)
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Same number with `scale` fractional digits, rounded if digits are dropped.
+    pub fn rescale(&self, scale: u8, mode: RoundingMode) -> Result<Decimal, DecimalError> {
+        if scale > MAX_PRECISION {
+            return Err(DecimalError::Overflow);
+        }
+
+        match scale.cmp(&self.scale) {
+            Ordering::Equal => Ok(*self),
+            Ordering::Greater => Ok(Decimal::new(
+                self.value
+                    .checked_mul(pow10((scale - self.scale).into())?)
+                    .ok_or(DecimalError::Overflow)?,
+                scale,
+            )),
+            Ordering::Less => Ok(Decimal::new(
+                divide(self.value, pow10((self.scale - scale).into())?, mode),
+                scale,
+            )),
+        }
+    }
+
+    pub fn round(&self, scale: u8, mode: RoundingMode) -> Result<Decimal, DecimalError> {
+        if scale >= self.scale {
+            return Ok(*self);
+        }
+
+        self.rescale(scale, mode)
+    }
+
+    pub fn add(&self, other: &Decimal) -> Result<Decimal, DecimalError> {
+        let (l, r) = Decimal::align(self, other)?;
+
+        Ok(Decimal::new(
+            l.value.checked_add(r.value).ok_or(DecimalError::Overflow)?,
+            l.scale,
+        ))
+    }
+
+    pub fn sub(&self, other: &Decimal) -> Result<Decimal, DecimalError> {
+        let (l, r) = Decimal::align(self, other)?;
+
+        Ok(Decimal::new(
+            l.value.checked_sub(r.value).ok_or(DecimalError::Overflow)?,
+            l.scale,
+        ))
+    }
+
+    /// Exact product, its scale is the sum of the scales.
+    pub fn mul(&self, other: &Decimal) -> Result<Decimal, DecimalError> {
+        let scale = self.scale as u16 + other.scale as u16;
+        if scale > MAX_PRECISION as u16 {
+            return Err(DecimalError::Overflow);
+        }
+
+        Ok(Decimal::new(
+            self.value
+                .checked_mul(other.value)
+                .ok_or(DecimalError::Overflow)?,
+            scale as u8,
+        ))
+    }
+
+    /// Quotient with `scale` fractional digits, the last digit is rounded with `mode`.
+    pub fn div(
+        &self,
+        other: &Decimal,
+        scale: u8,
+        mode: RoundingMode,
+    ) -> Result<Decimal, DecimalError> {
+        if other.value == 0 {
+            return Err(DecimalError::DivisionByZero);
+        }
+        if scale > MAX_PRECISION {
+            return Err(DecimalError::Overflow);
+        }
+
+        // self / other * 10^scale = self.value * 10^(scale + other.scale - self.scale) / other.value
+        let exponent = scale as i32 + other.scale as i32 - self.scale as i32;
+
+        let (dividend, divisor) = match u32::try_from(exponent) {
+            Ok(exponent) => {
+                let dividend = self
+                    .value
+                    .checked_mul(pow10(exponent)?)
+                    .ok_or(DecimalError::Overflow)?;
+
+                (dividend, other.value)
+            }
+            Err(_) => {
+                let divisor = other
+                    .value
+                    .checked_mul(pow10(exponent.unsigned_abs())?)
+                    .ok_or(DecimalError::Overflow)?;
+
+                (self.value, divisor)
+            }
+        };
+
+        Ok(Decimal::new(divide(dividend, divisor, mode), scale))
+    }
+
+    /// The double equal to the decimal, if there is one. Equal decimals of any
+    /// scale give the same double.
+    pub fn to_f64_exact(self) -> Option<f64> {
+        let decimal = self.normalize();
+
+        // A double is a binary fraction, so 10^-scale must reduce to 2^-scale.
+        let power_of_five = 5i128.pow(decimal.scale as u32);
+        if decimal.value % power_of_five != 0 {
+            return None;
+        }
+        let mantissa = decimal.value / power_of_five;
+
+        let significant = mantissa.unsigned_abs() >> mantissa.trailing_zeros().min(127);
+        if significant >= 1 << f64::MANTISSA_DIGITS {
+            return None;
+        }
+
+        Some(mantissa as f64 / (1u64 << decimal.scale) as f64)
+    }
+
+    /// Exact comparison with a double that is not NaN.
+    pub fn cmp_f64(&self, f: f64) -> Ordering {
+        let sign = if f > 0.0 {
+            1
+        } else if f < 0.0 {
+            -1
+        } else {
+            0
+        };
+        match self.value.signum().cmp(&sign) {
+            Ordering::Equal if sign == 0 => Ordering::Equal,
+            Ordering::Equal if f.is_infinite() => 0.cmp(&sign),
+            Ordering::Equal => {
+                let ordering = cmp_magnitude(self.value.unsigned_abs(), self.scale, f.abs());
+                if sign < 0 {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+            ordering => ordering,
+        }
+    }
+
+    /// Same value without trailing zeros, e.g. 2.5 for 2.50.
+    pub fn normalize(self) -> Decimal {
+        let mut decimal = self;
+        while decimal.scale > 0 && decimal.value % 10 == 0 {
+            decimal.value /= 10;
+            decimal.scale -= 1;
+        }
+
+        decimal
+    }
+
+    pub fn neg(&self) -> Decimal {
+        Decimal::new(-self.value, self.scale)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.value == 0
+    }
+
+    /// Unscaled value as stored in a column of `precision`, in 2, 4, 8 or 16 bytes.
+    pub fn to_bytes(self, precision: u8) -> Vec<u8> {
+        match encoded_size(precision) {
+            2 => (self.value as i16).to_be_bytes().to_vec(),
+            4 => (self.value as i32).to_be_bytes().to_vec(),
+            8 => (self.value as i64).to_be_bytes().to_vec(),
+            _ => self.value.to_be_bytes().to_vec(),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8], precision: u8, scale: u8) -> Decimal {
+        let value = match encoded_size(precision) {
+            2 => i16::from_be_bytes(bytes[..2].try_into().unwrap()) as i128,
+            4 => i32::from_be_bytes(bytes[..4].try_into().unwrap()) as i128,
+            8 => i64::from_be_bytes(bytes[..8].try_into().unwrap()) as i128,
+            _ => i128::from_be_bytes(bytes[..16].try_into().unwrap()),
+        };
+
+        Decimal::new(value, scale)
+    }
+
+    fn align(l: &Decimal, r: &Decimal) -> Result<(Decimal, Decimal), DecimalError> {
+        let scale = l.scale.max(r.scale);
+
+        Ok((
+            l.rescale(scale, RoundingMode::Down)?,
+            r.rescale(scale, RoundingMode::Down)?,
+        ))
+    }
+}
+
+/// Smallest integer that holds every unscaled value of `precision` digits.
+pub fn encoded_size(precision: u8) -> usize {
+    match precision {
+        0..=4 => 2,
+        5..=9 => 4,
+        10..=18 => 8,
+        _ => 16,
+    }
+}
+
+fn pow10(exponent: u32) -> Result<i128, DecimalError> {
+    10_i128.checked_pow(exponent).ok_or(DecimalError::Overflow)
+}
+
+fn divide(dividend: i128, divisor: i128, mode: RoundingMode) -> i128 {
+    let quotient = dividend / divisor;
+    let remainder = dividend % divisor;
+    if remainder == 0 {
+        return quotient;
+    }
+
+    let positive = (dividend < 0) == (divisor < 0);
+    let away_from_zero = if positive { quotient + 1 } else { quotient - 1 };
+
+    // Compares the remainder with a half of the divisor without overflowing.
+    let half = remainder
+        .unsigned_abs()
+        .cmp(&(divisor.unsigned_abs() - remainder.unsigned_abs()));
+
+    let round_away = match mode {
+        RoundingMode::Down => false,
+        RoundingMode::Up => true,
+        RoundingMode::Floor => !positive,
+        RoundingMode::Ceiling => positive,
+        RoundingMode::HalfUp => half != Ordering::Less,
+        RoundingMode::HalfDown => half == Ordering::Greater,
+        RoundingMode::HalfEven => {
+            half == Ordering::Greater || half == Ordering::Equal && quotient % 2 != 0
+        }
+    };
+
+    if round_away { away_from_zero } else { quotient }
+}
+
+// Unsigned 256-bit integer as its high and low halves, they compare in order.
+type Wide = (u128, u128);
+
+// `value * 10^-scale` against the positive finite double `f`, compared as
+// `value * 2^l` and `mantissa * 10^scale * 2^r` where `f = mantissa * 2^(r - l)`.
+fn cmp_magnitude(value: u128, scale: u8, f: f64) -> Ordering {
+    let bits = f.to_bits();
+    let biased_exponent = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = bits & ((1 << 52) - 1);
+    let (mantissa, exponent) = match biased_exponent {
+        0 => (fraction, -1074),
+        _ => (fraction | 1 << 52, biased_exponent - 1075),
+    };
+
+    let left = (0, value);
+    let right = mul_wide(mantissa as u128, 10u128.pow(scale as u32));
+    let (l, r) = (
+        exponent.min(0).unsigned_abs(),
+        exponent.max(0).unsigned_abs(),
+    );
+
+    // Numbers of different bit lengths are ordered by them, otherwise the
+    // shifted number is as long as the other one and fits.
+    let (left_len, right_len) = (bit_len(left) + l, bit_len(right) + r);
+    if left_len != right_len {
+        return left_len.cmp(&right_len);
+    }
+
+    shl(left, l).cmp(&shl(right, r))
+}
+
+fn mul_wide(a: u128, b: u128) -> Wide {
+    const LOW: u128 = u64::MAX as u128;
+
+    let (a1, a0) = (a >> 64, a & LOW);
+    let (b1, b0) = (b >> 64, b & LOW);
+    let (p00, p01, p10, p11) = (a0 * b0, a0 * b1, a1 * b0, a1 * b1);
+
+    let middle = (p00 >> 64) + (p01 & LOW) + (p10 & LOW);
+    let low = (p00 & LOW) | (middle << 64);
+    let high = p11 + (p01 >> 64) + (p10 >> 64) + (middle >> 64);
+
+    (high, low)
+}
+
+fn bit_len((high, low): Wide) -> u32 {
+    if high > 0 {
+        256 - high.leading_zeros()
+    } else {
+        128 - low.leading_zeros()
+    }
+}
+
+// `n` must leave the number within 256 bits.
+fn shl((high, low): Wide, n: u32) -> Wide {
+    match n {
+        0 => (high, low),
+        1..128 => ((high << n) | (low >> (128 - n)), low << n),
+        _ => (low << (n - 128), 0),
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match Decimal::align(self, other) {
+            Ok((l, r)) => l.value.cmp(&r.value),
+            // A value that cannot be scaled up is larger in magnitude than the other one.
+            Err(_) if self.scale < other.scale => self.value.signum().cmp(&0),
+            Err(_) => 0.cmp(&other.value.signum()),
+        }
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.value.unsigned_abs().to_string();
+        let sign = if self.value < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+
+        write!(f, "{}{}.{}", sign, integer, fraction)
+    }
+}
@@ -4,7 +4,7 @@ use std::{
     sync::atomic::{AtomicPtr, Ordering},
 };
 
-use crate::page::Page;
+use crate::page::{Page, SIZE};
 
 const RETRIES: usize = 100;
 
@@ -31,6 +31,10 @@ unsafe impl<'a> Sync for ConcurrentFreeListSlot<'a> {}
 
 impl<'a> ConcurrentFreeList<'a> {
     pub fn new(elements: Vec<usize>) -> Self {
+        Self::with_page_size(elements, SIZE)
+    }
+
+    pub fn with_page_size(elements: Vec<usize>, page_size: usize) -> Self {
         let Some(last_element) = elements.last() else {
             return Self {
                 next: AtomicPtr::new(std::ptr::null_mut()),
@@ -56,14 +60,14 @@ impl<'a> ConcurrentFreeList<'a> {
             pages: {
                 let mut pages = Vec::with_capacity(elements.len());
                 for _ in 0..elements.len() {
-                    pages.push(UnsafeCell::new(Page::new(0)));
+                    pages.push(UnsafeCell::new(Page::with_size(0, page_size)));
                 }
                 pages
             },
         }
     }
 
-    pub fn allocate_page(&self) -> Result<AllocatedPage, ()> {
+    pub fn allocate_page(&self) -> Result<AllocatedPage<'_>, ()> {
         for _ in 0..RETRIES {
             let Some(next) = NonNull::new(self.next.load(Ordering::Acquire)) else {
                 return Err(());
@@ -96,12 +100,13 @@ impl<'a> ConcurrentFreeList<'a> {
 
             let new_next = Box::into_raw(Box::new(ConcurrentFreeListSlot {
                 value: page.free_list_id,
-                next: AtomicPtr::new(next_ptr.clone()),
+                next: AtomicPtr::new(next_ptr),
             }));
 
-            if let Ok(_) =
-                self.next
-                    .compare_exchange(next_ptr, new_next, Ordering::Release, Ordering::Relaxed)
+            if self
+                .next
+                .compare_exchange(next_ptr, new_next, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
             {
                 break;
             }
@@ -1,2 +1,6 @@
+pub mod decimal;
 pub mod free_list;
+pub mod side_file;
+pub mod temporal;
 pub mod type_converter;
+pub mod uuid;
@@ -0,0 +1,86 @@
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Reads the big endian fields of a side file, e.g. a zone map or an LSM
+/// manifest. Reading past the end is an error naming the file kind.
+pub struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    kind: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8], kind: &'static str) -> Reader<'a> {
+        Reader {
+            data,
+            offset: 0,
+            kind,
+        }
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| self.corrupted())?;
+        self.offset += len;
+
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_be_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub fn corrupted(&self) -> Error {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{} is corrupted", self.kind),
+        )
+    }
+}
+
+/// Replaces the file at `path` with `data`. The data is written to a
+/// temporary file first, so a crash leaves either the old or the new file.
+pub fn write(path: &Path, data: &[u8]) -> Result<(), Error> {
+    let temp_path = temp_path(path);
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&temp_path)?;
+    file.write_all(data)?;
+    file.sync_data()?;
+
+    fs::rename(temp_path, path)
+}
+
+/// Removes the file at `path` if there is one.
+pub fn remove(path: &Path) -> Result<(), Error> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp_path = OsString::from(path.as_os_str());
+    temp_path.push(".tmp");
+
+    PathBuf::from(temp_path)
+}
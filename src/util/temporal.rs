@@ -0,0 +1,627 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+// Temporal values are counted from 1970-01-01 00:00:00 in the proleptic Gregorian
+// calendar. Time zones are fixed UTC offsets only, there is no time zone database.
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+// Intervals are compared as if every month had 30 days, as in PostgreSQL.
+const DAYS_PER_MONTH: i64 = 30;
+
+pub type ParseTemporalError = &'static str;
+/// Arithmetic whose result is out of the range of its type.
+pub type TemporalError = &'static str;
+
+/// Days since 1970-01-01.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date(pub i32);
+
+/// Microseconds since midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time(pub i64);
+
+/// Microseconds since 1970-01-01 00:00:00, without a time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(pub i64);
+
+/// Microseconds since 1970-01-01 00:00:00 UTC. The offset of the input is not kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimestampTz(pub i64);
+
+/// Months, days and microseconds are kept apart: the length of a month or a
+/// day is only known when the interval is added to a timestamp.
+#[derive(Debug, Clone, Copy)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub micros: i64,
+}
+
+impl Date {
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Option<Date> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+
+        Some(Date(days_from_civil(year, month, day) as i32))
+    }
+
+    /// Parses `YYYY-MM-DD`.
+    pub fn parse(s: &str) -> Result<Date, ParseTemporalError> {
+        let (year, rest) = match s.strip_prefix('-') {
+            Some(rest) => {
+                let (year, rest) = rest.split_once('-').ok_or("Invalid date")?;
+                (-parse_number(year, 4, 6)?, rest)
+            }
+            None => {
+                let (year, rest) = s.split_once('-').ok_or("Invalid date")?;
+                (parse_number(year, 4, 6)?, rest)
+            }
+        };
+
+        let (month, day) = rest.split_once('-').ok_or("Invalid date")?;
+
+        Date::from_ymd(
+            year as i32,
+            parse_number(month, 2, 2)? as u32,
+            parse_number(day, 2, 2)? as u32,
+        )
+        .ok_or("Date out of range")
+    }
+
+    pub fn ymd(&self) -> (i32, u32, u32) {
+        civil_from_days(self.0 as i64)
+    }
+
+    /// Microseconds of its midnight since 1970-01-01 00:00:00, distant dates
+    /// are out of the range of a timestamp.
+    pub fn micros(&self) -> i128 {
+        self.0 as i128 * MICROS_PER_DAY as i128
+    }
+
+    pub fn to_bytes(self) -> [u8; 4] {
+        self.0.to_be_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Date {
+        Date(i32::from_be_bytes(bytes[..4].try_into().unwrap()))
+    }
+}
+
+impl Time {
+    pub fn from_hms_micro(hour: u32, minute: u32, second: u32, micro: u32) -> Option<Time> {
+        if hour > 23 || minute > 59 || second > 59 || micro as i64 >= MICROS_PER_SECOND {
+            return None;
+        }
+
+        Some(Time(
+            hour as i64 * MICROS_PER_HOUR
+                + minute as i64 * MICROS_PER_MINUTE
+                + second as i64 * MICROS_PER_SECOND
+                + micro as i64,
+        ))
+    }
+
+    /// Parses `HH:MM`, `HH:MM:SS` or `HH:MM:SS.ffffff`.
+    pub fn parse(s: &str) -> Result<Time, ParseTemporalError> {
+        let mut parts = s.splitn(3, ':');
+
+        let hour = parse_number(parts.next().ok_or("Invalid time")?, 2, 2)?;
+        let minute = parse_number(parts.next().ok_or("Invalid time")?, 2, 2)?;
+        let (second, micro) = match parts.next() {
+            Some(seconds) => parse_seconds(seconds)?,
+            None => (0, 0),
+        };
+
+        let second = u32::try_from(second).or(Err("Time out of range"))?;
+
+        Time::from_hms_micro(hour as u32, minute as u32, second, micro as u32)
+            .ok_or("Time out of range")
+    }
+
+    pub fn to_bytes(self) -> [u8; 8] {
+        self.0.to_be_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Time {
+        Time(i64::from_be_bytes(bytes[..8].try_into().unwrap()))
+    }
+}
+
+impl Timestamp {
+    pub fn new(date: Date, time: Time) -> Timestamp {
+        Timestamp(date.0 as i64 * MICROS_PER_DAY + time.0)
+    }
+
+    /// Parses `YYYY-MM-DDTHH:MM:SS[.ffffff]`, a space may separate date and time.
+    /// A date alone is midnight of that day.
+    pub fn parse(s: &str) -> Result<Timestamp, ParseTemporalError> {
+        let (date, time) = match split_date_time(s) {
+            (date, Some(time)) => (Date::parse(date)?, Time::parse(time)?),
+            (date, None) => (Date::parse(date)?, Time(0)),
+        };
+
+        from_days(date.0 as i64, time.0).ok_or("Timestamp out of range")
+    }
+
+    pub fn date(&self) -> Date {
+        Date(self.0.div_euclid(MICROS_PER_DAY) as i32)
+    }
+
+    pub fn time(&self) -> Time {
+        Time(self.0.rem_euclid(MICROS_PER_DAY))
+    }
+
+    /// Interval of days and microseconds between `other` and this timestamp.
+    pub fn since(&self, other: Timestamp) -> Result<Interval, TemporalError> {
+        let micros = self.0.checked_sub(other.0).ok_or("Interval out of range")?;
+
+        Ok(Interval {
+            months: 0,
+            days: (micros / MICROS_PER_DAY) as i32,
+            micros: micros % MICROS_PER_DAY,
+        })
+    }
+
+    /// Months are added first and the day is clamped to the end of the month,
+    /// then days and microseconds, so 2024-01-31 + P1M is 2024-02-29.
+    pub fn checked_add(self, interval: Interval) -> Result<Timestamp, TemporalError> {
+        let (year, month, day) = self.date().ymd();
+        let (year, month) = add_months(year, month, interval.months);
+        let day = day.min(days_in_month(year, month));
+
+        let days = days_from_civil(year, month, day) + interval.days as i64;
+
+        from_days(days, self.time().0)
+            .and_then(|timestamp| timestamp.0.checked_add(interval.micros))
+            .map(Timestamp)
+            .ok_or("Timestamp out of range")
+    }
+
+    pub fn checked_sub(self, interval: Interval) -> Result<Timestamp, TemporalError> {
+        self.checked_add(interval.checked_neg()?)
+    }
+
+    pub fn to_bytes(self) -> [u8; 8] {
+        self.0.to_be_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Timestamp {
+        Timestamp(i64::from_be_bytes(bytes[..8].try_into().unwrap()))
+    }
+}
+
+impl TimestampTz {
+    /// Parses a timestamp followed by `Z` or an offset `+HH`, `+HH:MM` or `+HHMM`.
+    /// A timestamp without an offset is taken as UTC.
+    pub fn parse(s: &str) -> Result<TimestampTz, ParseTemporalError> {
+        let (local, offset) = match s.strip_suffix(['Z', 'z']) {
+            Some(local) => (local, 0),
+            None => match split_date_time(s) {
+                (_, Some(time)) => match time.rfind(['+', '-']) {
+                    Some(i) => (&s[..s.len() - time.len() + i], parse_offset(&time[i..])?),
+                    None => (s, 0),
+                },
+                (_, None) => (s, 0),
+            },
+        };
+
+        let utc = Timestamp::parse(local)?.0.checked_sub(offset);
+
+        utc.map(TimestampTz).ok_or("Timestamp out of range")
+    }
+
+    pub fn from_utc(timestamp: Timestamp) -> TimestampTz {
+        TimestampTz(timestamp.0)
+    }
+
+    pub fn to_utc(self) -> Timestamp {
+        Timestamp(self.0)
+    }
+
+    /// Local time at a fixed offset from UTC.
+    pub fn to_offset(self, offset_seconds: i32) -> Result<Timestamp, TemporalError> {
+        let local = self
+            .0
+            .checked_add(offset_seconds as i64 * MICROS_PER_SECOND);
+
+        local.map(Timestamp).ok_or("Timestamp out of range")
+    }
+
+    /// Days are always 24 hours long, as offsets are fixed.
+    pub fn checked_add(self, interval: Interval) -> Result<TimestampTz, TemporalError> {
+        Ok(TimestampTz::from_utc(self.to_utc().checked_add(interval)?))
+    }
+
+    pub fn checked_sub(self, interval: Interval) -> Result<TimestampTz, TemporalError> {
+        Ok(TimestampTz::from_utc(self.to_utc().checked_sub(interval)?))
+    }
+
+    pub fn to_bytes(self) -> [u8; 8] {
+        self.0.to_be_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> TimestampTz {
+        TimestampTz(i64::from_be_bytes(bytes[..8].try_into().unwrap()))
+    }
+}
+
+impl Interval {
+    pub fn new(months: i32, days: i32, micros: i64) -> Interval {
+        Interval {
+            months,
+            days,
+            micros,
+        }
+    }
+
+    /// Parses an ISO-8601 duration `PnYnMnWnDTnHnMnS`, every number may have a sign
+    /// and seconds may have a fraction, e.g. `P1Y2M10DT2H30M` or `PT-1.5S`.
+    pub fn parse(s: &str) -> Result<Interval, ParseTemporalError> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let s = s.strip_prefix(['P', 'p']).ok_or("Invalid interval")?;
+        if s.is_empty() {
+            return Err("Invalid interval");
+        }
+
+        let (date, time) = match s.split_once(['T', 't']) {
+            Some((_, "")) => return Err("Invalid interval"),
+            Some((date, time)) => (date, time),
+            None => (s, ""),
+        };
+
+        let mut interval = Interval::new(0, 0, 0);
+
+        for (number, unit) in duration_components(date)? {
+            let number = parse_signed(number)?;
+
+            match unit {
+                'Y' => interval.months = add_scaled(interval.months, number, 12)?,
+                'M' => interval.months = add_scaled(interval.months, number, 1)?,
+                'W' => interval.days = add_scaled(interval.days, number, 7)?,
+                'D' => interval.days = add_scaled(interval.days, number, 1)?,
+                _ => return Err("Invalid interval unit"),
+            }
+        }
+
+        for (number, unit) in duration_components(time)? {
+            let micros = match unit {
+                'H' => parse_signed(number)?.checked_mul(MICROS_PER_HOUR),
+                'M' => parse_signed(number)?.checked_mul(MICROS_PER_MINUTE),
+                'S' => {
+                    let (negative, number) = match number.strip_prefix('-') {
+                        Some(number) => (true, number),
+                        None => (false, number.strip_prefix('+').unwrap_or(number)),
+                    };
+                    let (seconds, micro) = parse_seconds(number)?;
+                    let micros = seconds
+                        .checked_mul(MICROS_PER_SECOND)
+                        .and_then(|micros| micros.checked_add(micro));
+
+                    micros.map(|micros| if negative { -micros } else { micros })
+                }
+                _ => return Err("Invalid interval unit"),
+            };
+
+            interval.micros = micros
+                .and_then(|micros| interval.micros.checked_add(micros))
+                .ok_or("Interval out of range")?;
+        }
+
+        if negative {
+            interval = interval.checked_neg()?;
+        }
+
+        Ok(interval)
+    }
+
+    pub fn checked_neg(self) -> Result<Interval, TemporalError> {
+        match (
+            self.months.checked_neg(),
+            self.days.checked_neg(),
+            self.micros.checked_neg(),
+        ) {
+            (Some(months), Some(days), Some(micros)) => Ok(Interval::new(months, days, micros)),
+            _ => Err("Interval out of range"),
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+
+        bytes[0..4].copy_from_slice(&self.months.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.days.to_be_bytes());
+        bytes[8..16].copy_from_slice(&self.micros.to_be_bytes());
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Interval {
+        Interval {
+            months: i32::from_be_bytes(bytes[0..4].try_into().unwrap()),
+            days: i32::from_be_bytes(bytes[4..8].try_into().unwrap()),
+            micros: i64::from_be_bytes(bytes[8..16].try_into().unwrap()),
+        }
+    }
+
+    /// Length in microseconds with 30-day months, intervals are compared by it.
+    pub fn span(&self) -> i128 {
+        (self.months as i128 * DAYS_PER_MONTH as i128 + self.days as i128) * MICROS_PER_DAY as i128
+            + self.micros as i128
+    }
+}
+
+impl PartialEq for Interval {
+    fn eq(&self, other: &Self) -> bool {
+        self.span() == other.span()
+    }
+}
+
+impl Eq for Interval {}
+
+impl PartialOrd for Interval {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Interval {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.span().cmp(&other.span())
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+
+        if year < 0 {
+            write!(f, "-{:04}-{:02}-{:02}", -year, month, day)
+        } else {
+            write!(f, "{:04}-{:02}-{:02}", year, month, day)
+        }
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hours = self.0 / MICROS_PER_HOUR;
+        let minutes = self.0 % MICROS_PER_HOUR / MICROS_PER_MINUTE;
+
+        write!(
+            f,
+            "{:02}:{:02}:{}",
+            hours,
+            minutes,
+            format_seconds(self.0 % MICROS_PER_MINUTE, 2)
+        )
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}T{}", self.date(), self.time())
+    }
+}
+
+impl fmt::Display for TimestampTz {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}Z", self.to_utc())
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.months == 0 && self.days == 0 && self.micros == 0 {
+            return write!(f, "PT0S");
+        }
+
+        write!(f, "P")?;
+
+        if self.months / 12 != 0 {
+            write!(f, "{}Y", self.months / 12)?;
+        }
+        if self.months % 12 != 0 {
+            write!(f, "{}M", self.months % 12)?;
+        }
+        if self.days != 0 {
+            write!(f, "{}D", self.days)?;
+        }
+
+        if self.micros != 0 {
+            write!(f, "T")?;
+
+            if self.micros / MICROS_PER_HOUR != 0 {
+                write!(f, "{}H", self.micros / MICROS_PER_HOUR)?;
+            }
+            if self.micros % MICROS_PER_HOUR / MICROS_PER_MINUTE != 0 {
+                write!(f, "{}M", self.micros % MICROS_PER_HOUR / MICROS_PER_MINUTE)?;
+            }
+            if self.micros % MICROS_PER_MINUTE != 0 {
+                let seconds = self.micros % MICROS_PER_MINUTE;
+                let sign = if seconds < 0 { "-" } else { "" };
+
+                write!(f, "{}{}S", sign, format_seconds(seconds.abs(), 1))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Days since 1970-01-01 of a date, see http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = year as i64 - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month as i64 + 9) % 12) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    (year as i32, month as u32, day as u32)
+}
+
+fn is_leap_year(year: i32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn add_months(year: i32, month: u32, months: i32) -> (i32, u32) {
+    let total = year as i64 * 12 + month as i64 - 1 + months as i64;
+
+    (total.div_euclid(12) as i32, total.rem_euclid(12) as u32 + 1)
+}
+
+// Timestamp of microseconds since midnight of a day since 1970-01-01, if it
+// is in range.
+fn from_days(days: i64, micros: i64) -> Option<Timestamp> {
+    days.checked_mul(MICROS_PER_DAY)
+        .and_then(|day| day.checked_add(micros))
+        .map(Timestamp)
+}
+
+fn split_date_time(s: &str) -> (&str, Option<&str>) {
+    // The first character may be the sign of the year.
+    let separator = s
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| matches!(c, 'T' | 't' | ' '));
+
+    match separator {
+        Some((i, _)) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    }
+}
+
+fn parse_number(s: &str, min_digits: usize, max_digits: usize) -> Result<i64, ParseTemporalError> {
+    if s.len() < min_digits || s.len() > max_digits || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("Invalid number");
+    }
+
+    s.parse().or(Err("Invalid number"))
+}
+
+// `total + number * factor`, if it fits.
+fn add_scaled(total: i32, number: i64, factor: i64) -> Result<i32, ParseTemporalError> {
+    number
+        .checked_mul(factor)
+        .and_then(|number| i32::try_from(number).ok())
+        .and_then(|number| total.checked_add(number))
+        .ok_or("Interval out of range")
+}
+
+fn parse_signed(s: &str) -> Result<i64, ParseTemporalError> {
+    match s.strip_prefix('-') {
+        Some(s) => Ok(-parse_number(s, 1, 18)?),
+        None => parse_number(s.strip_prefix('+').unwrap_or(s), 1, 18),
+    }
+}
+
+// `SS` or `SS.ffffff`, digits after the sixth one are truncated.
+fn parse_seconds(s: &str) -> Result<(i64, i64), ParseTemporalError> {
+    match s.split_once(['.', ',']) {
+        Some((seconds, fraction)) => {
+            if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+                return Err("Invalid number");
+            }
+            let fraction = &fraction[..fraction.len().min(6)];
+            let micros = parse_number(fraction, 1, 6)? * 10_i64.pow(6 - fraction.len() as u32);
+
+            Ok((parse_number(seconds, 1, 18)?, micros))
+        }
+        None => Ok((parse_number(s, 1, 18)?, 0)),
+    }
+}
+
+fn parse_offset(s: &str) -> Result<i64, ParseTemporalError> {
+    let (negative, s) = match s.split_at_checked(1) {
+        Some(("+", s)) => (false, s),
+        Some(("-", s)) => (true, s),
+        _ => return Err("Invalid offset"),
+    };
+
+    let (hours, minutes) = match (s.len(), s.split_once(':')) {
+        (_, Some((hours, minutes))) => (hours, minutes),
+        (4, None) => s.split_at_checked(2).ok_or("Invalid offset")?,
+        (_, None) => (s, "00"),
+    };
+
+    let hours = parse_number(hours, 2, 2)?;
+    let minutes = parse_number(minutes, 2, 2)?;
+    if hours > 23 || minutes > 59 {
+        return Err("Offset out of range");
+    }
+
+    let offset = hours * MICROS_PER_HOUR + minutes * MICROS_PER_MINUTE;
+
+    Ok(if negative { -offset } else { offset })
+}
+
+// Splits `1Y2M` into (number, unit) pairs, numbers may have a sign or a fraction.
+fn duration_components(s: &str) -> Result<Vec<(&str, char)>, ParseTemporalError> {
+    let mut components = vec![];
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        if c.is_ascii_alphabetic() {
+            if i == start {
+                return Err("Invalid interval");
+            }
+
+            components.push((&s[start..i], c.to_ascii_uppercase()));
+            start = i + 1;
+        }
+    }
+
+    if start != s.len() {
+        return Err("Invalid interval");
+    }
+
+    Ok(components)
+}
+
+// Non negative microseconds of a minute as `SS` or `SS.ffffff` without trailing zeros.
+fn format_seconds(micros: i64, min_digits: usize) -> String {
+    let seconds = format!("{:0width$}", micros / MICROS_PER_SECOND, width = min_digits);
+
+    match micros % MICROS_PER_SECOND {
+        0 => seconds,
+        fraction => {
+            let fraction = format!("{:06}", fraction);
+
+            format!("{}.{}", seconds, fraction.trim_end_matches('0'))
+        }
+    }
+}
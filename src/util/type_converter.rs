@@ -2,6 +2,52 @@ pub fn int_to_bytes(value: &i32) -> [u8; 4] {
     value.to_be_bytes()
 }
 
+pub fn small_int_to_bytes(value: &i16) -> [u8; 2] {
+    value.to_be_bytes()
+}
+
+pub fn big_int_to_bytes(value: &i64) -> [u8; 8] {
+    value.to_be_bytes()
+}
+
+pub fn real_to_bytes(value: &f32) -> [u8; 4] {
+    value.to_be_bytes()
+}
+
+pub fn double_to_bytes(value: &f64) -> [u8; 8] {
+    value.to_be_bytes()
+}
+
+pub fn bool_to_bytes(value: &bool) -> [u8; 1] {
+    [*value as u8]
+}
+
 pub fn string_to_bytes(value: &str) -> &[u8] {
     value.as_bytes()
 }
+
+// Readers take the bytes starting at the value and panic if there are too few of them.
+
+pub fn bytes_to_int(bytes: &[u8]) -> i32 {
+    i32::from_be_bytes(bytes[..4].try_into().unwrap())
+}
+
+pub fn bytes_to_small_int(bytes: &[u8]) -> i16 {
+    i16::from_be_bytes(bytes[..2].try_into().unwrap())
+}
+
+pub fn bytes_to_big_int(bytes: &[u8]) -> i64 {
+    i64::from_be_bytes(bytes[..8].try_into().unwrap())
+}
+
+pub fn bytes_to_real(bytes: &[u8]) -> f32 {
+    f32::from_be_bytes(bytes[..4].try_into().unwrap())
+}
+
+pub fn bytes_to_double(bytes: &[u8]) -> f64 {
+    f64::from_be_bytes(bytes[..8].try_into().unwrap())
+}
+
+pub fn bytes_to_bool(bytes: &[u8]) -> bool {
+    bytes[0] != 0
+}
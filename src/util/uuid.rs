@@ -0,0 +1,61 @@
+use std::fmt;
+
+pub type ParseUuidError = &'static str;
+
+/// 128-bit identifier, stored as its 16 bytes in network order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid(pub [u8; 16]);
+
+impl Uuid {
+    /// Parses 32 hex digits, optionally in the hyphenated 8-4-4-4-12 form or in braces.
+    pub fn parse(s: &str) -> Result<Uuid, ParseUuidError> {
+        let s = s
+            .strip_prefix('{')
+            .and_then(|s| s.strip_suffix('}'))
+            .unwrap_or(s);
+
+        let digits = match s.len() {
+            32 => s.to_owned(),
+            36 => {
+                if [8, 13, 18, 23].iter().any(|i| s.as_bytes()[*i] != b'-') {
+                    return Err("Misplaced hyphen");
+                }
+                s.replace('-', "")
+            }
+            _ => return Err("Expected 32 hex digits"),
+        };
+
+        if digits.len() != 32 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err("Invalid hex digit");
+        }
+
+        let mut bytes = [0; 16];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).unwrap();
+        }
+
+        Ok(Uuid(bytes))
+    }
+
+    pub fn to_bytes(self) -> [u8; 16] {
+        self.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Uuid {
+        Uuid(bytes[..16].try_into().unwrap())
+    }
+}
+
+/// Lowercase hyphenated form.
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                write!(f, "-")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+
+        Ok(())
+    }
+}
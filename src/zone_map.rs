@@ -0,0 +1,310 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+
+use crate::page::PageId;
+use crate::schema::Schema;
+use crate::tuple::{KeyColumn, Tuple, TupleValue, decode_key, encode_key};
+use crate::util::side_file::{self, Reader};
+
+const FILE_EXTENSION: &str = "zm";
+
+const HAS_VALUES: u8 = 1;
+const HAS_NULLS: u8 = 2;
+
+/// Smallest and largest non-null value of one column in a range of pages.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Zone {
+    pub bounds: Option<(TupleValue, TupleValue)>,
+    pub has_nulls: bool,
+}
+
+impl Zone {
+    /// Whether a value between `start` and `end` may be in the range. Values
+    /// are compared as in SQL, a value of another type never matches.
+    pub fn may_contain(&self, start: Bound<&TupleValue>, end: Bound<&TupleValue>) -> bool {
+        let Some((min, max)) = &self.bounds else {
+            return false;
+        };
+
+        above(max, start) && below(min, end)
+    }
+
+    fn add(&mut self, value: &TupleValue) {
+        if value.is_null() {
+            self.has_nulls = true;
+            return;
+        }
+
+        self.bounds = match self.bounds.take() {
+            None => Some((value.clone(), value.clone())),
+            Some((min, max)) if value < &min => Some((value.clone(), max)),
+            Some((min, max)) if value > &max => Some((min, value.clone())),
+            bounds => bounds,
+        };
+    }
+}
+
+/// Min/max summaries of chosen columns of a heap file, one per range of
+/// `pages_per_range` pages.
+///
+/// Summaries only widen while rows are written, a deleted or updated value
+/// stays inside them until the pages are summarized again. A range that may
+/// hold a value is never skipped, so a scan still checks every row it reads.
+#[derive(Debug)]
+pub struct ZoneMap {
+    path: PathBuf,
+    columns: Vec<usize>,
+    key_columns: Vec<KeyColumn>,
+    pages_per_range: u64,
+    page_count: u64,
+    // Zones of range `i` are `zones[i * columns.len()..(i + 1) * columns.len()]`.
+    zones: Vec<Zone>,
+    // Changed since the last flush, the stored map is removed then.
+    dirty: bool,
+}
+
+impl ZoneMap {
+    /// Opens the map stored next to the heap file `filename`. The map is empty
+    /// if there is none yet or if it summarizes other columns or ranges.
+    pub fn open(
+        path: &str,
+        filename: &str,
+        schema: &Schema,
+        columns: &[usize],
+        pages_per_range: u64,
+    ) -> Result<ZoneMap, Error> {
+        assert!(pages_per_range > 0, "A range has at least one page");
+
+        let path = Path::new(path).join(format!("{}.{}", filename, FILE_EXTENSION));
+
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err),
+        };
+
+        let mut map = ZoneMap {
+            path,
+            columns: columns.to_vec(),
+            key_columns: columns
+                .iter()
+                .map(|&column| KeyColumn::new(schema.columns()[column].data_type))
+                .collect(),
+            pages_per_range,
+            page_count: 0,
+            zones: vec![],
+            dirty: false,
+        };
+
+        if !data.is_empty() {
+            let mut reader = Reader::new(&data, "Zone map");
+
+            if reader.u64()? == pages_per_range
+                && reader.u16()? as usize == columns.len()
+                && columns
+                    .iter()
+                    .all(|&column| matches!(reader.u16(), Ok(c) if c as usize == column))
+            {
+                map.page_count = reader.u64()?;
+
+                for _ in 0..map.range_count() {
+                    for column in 0..columns.len() {
+                        let zone = map.read_zone(&mut reader, column)?;
+                        map.zones.push(zone);
+                    }
+                }
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Number of pages the map covers, pages after them are not summarized.
+    pub fn page_count(&self) -> u64 {
+        self.page_count
+    }
+
+    pub fn pages_per_range(&self) -> u64 {
+        self.pages_per_range
+    }
+
+    /// Summary of a column of the heap file for the range holding `page_id`.
+    pub fn zone(&self, page_id: PageId, column: usize) -> Option<&Zone> {
+        let index = self.columns.iter().position(|&c| c == column)?;
+
+        self.zones
+            .get(self.range_of(page_id) * self.columns.len() + index)
+    }
+
+    /// Widens the summaries of the range of `page_id` by a row whose values of
+    /// the summarized columns are `values`, in the order of `columns`.
+    pub fn update(&mut self, page_id: PageId, values: &[TupleValue]) {
+        let start = self.add_page(page_id) * self.columns.len();
+
+        for (zone, value) in self.zones[start..].iter_mut().zip(values) {
+            zone.add(value);
+        }
+    }
+
+    pub fn update_tuple(&mut self, page_id: PageId, tuple: &Tuple) {
+        let start = self.add_page(page_id) * self.columns.len();
+
+        for (zone, &column) in self.zones[start..].iter_mut().zip(&self.columns) {
+            zone.add(&tuple.values[column]);
+        }
+    }
+
+    /// Covers `page_id` without adding a row to it, ranges of empty pages
+    /// never match.
+    pub fn cover(&mut self, page_id: PageId) {
+        self.add_page(page_id);
+    }
+
+    /// Forgets the summaries of the range of `page_id`, which is summarized
+    /// again by the rows passed to `update` afterwards.
+    pub fn reset_range(&mut self, page_id: PageId) {
+        let start = self.add_page(page_id) * self.columns.len();
+
+        self.zones[start..start + self.columns.len()].fill(Zone::default());
+    }
+
+    /// Whether a page may hold a row whose `column` is between `start` and
+    /// `end`. Pages not covered and columns not summarized always may.
+    pub fn may_match(
+        &self,
+        page_id: PageId,
+        column: usize,
+        start: Bound<&TupleValue>,
+        end: Bound<&TupleValue>,
+    ) -> bool {
+        if page_id >= self.page_count {
+            return true;
+        }
+
+        match self.zone(page_id, column) {
+            Some(zone) => zone.may_contain(start, end),
+            None => true,
+        }
+    }
+
+    /// Removes the stored map before the first change after an open or a
+    /// flush. Rows are durable as soon as their page is written, a map that
+    /// misses some is built again on open instead of being read.
+    pub fn invalidate(&mut self) -> Result<(), Error> {
+        if !self.dirty {
+            side_file::remove(&self.path)?;
+            self.dirty = true;
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let mut data = vec![];
+        data.extend_from_slice(&self.pages_per_range.to_be_bytes());
+        data.extend_from_slice(&(self.columns.len() as u16).to_be_bytes());
+        for &column in &self.columns {
+            data.extend_from_slice(&(column as u16).to_be_bytes());
+        }
+        data.extend_from_slice(&self.page_count.to_be_bytes());
+
+        for (i, zone) in self.zones.iter().enumerate() {
+            let key_column = &self.key_columns[i % self.columns.len()];
+
+            let flags = if zone.bounds.is_some() { HAS_VALUES } else { 0 }
+                | if zone.has_nulls { HAS_NULLS } else { 0 };
+            data.push(flags);
+
+            if let Some((min, max)) = &zone.bounds {
+                for value in [min, max] {
+                    let key = encode_key(
+                        std::slice::from_ref(key_column),
+                        std::slice::from_ref(value),
+                    )
+                    .map_err(|err| Error::new(ErrorKind::InvalidInput, format!("{:?}", err)))?;
+
+                    data.extend_from_slice(&(key.len() as u16).to_be_bytes());
+                    data.extend_from_slice(&key);
+                }
+            }
+        }
+
+        side_file::write(&self.path, &data)?;
+        self.dirty = false;
+
+        Ok(())
+    }
+
+    fn range_of(&self, page_id: PageId) -> usize {
+        (page_id / self.pages_per_range) as usize
+    }
+
+    fn range_count(&self) -> usize {
+        self.page_count.div_ceil(self.pages_per_range) as usize
+    }
+
+    // Index of the range of the page, the map grows to cover it.
+    fn add_page(&mut self, page_id: PageId) -> usize {
+        self.page_count = self.page_count.max(page_id + 1);
+        self.zones
+            .resize(self.range_count() * self.columns.len(), Zone::default());
+
+        self.range_of(page_id)
+    }
+
+    fn read_zone(&self, reader: &mut Reader, column: usize) -> Result<Zone, Error> {
+        let flags = reader.u8()?;
+        let mut zone = Zone {
+            bounds: None,
+            has_nulls: flags & HAS_NULLS != 0,
+        };
+
+        if flags & HAS_VALUES != 0 {
+            let min = self.read_value(reader, column)?;
+            let max = self.read_value(reader, column)?;
+            zone.bounds = Some((min, max));
+        }
+
+        Ok(zone)
+    }
+
+    fn read_value(&self, reader: &mut Reader, column: usize) -> Result<TupleValue, Error> {
+        let len = reader.u16()? as usize;
+        let key = reader.bytes(len)?;
+
+        match decode_key(&self.key_columns[column..column + 1], key) {
+            Ok(mut values) if values.len() == 1 => Ok(values.remove(0)),
+            Ok(_) => Err(reader.corrupted()),
+            Err(err) => Err(Error::new(ErrorKind::InvalidData, format!("{:?}", err))),
+        }
+    }
+}
+
+/// Whether `value` is between `start` and `end`, compared as in SQL.
+pub fn in_bounds(value: &TupleValue, start: Bound<&TupleValue>, end: Bound<&TupleValue>) -> bool {
+    above(value, start) && below(value, end)
+}
+
+fn above(value: &TupleValue, start: Bound<&TupleValue>) -> bool {
+    match start {
+        Bound::Included(start) => matches!(
+            value.sql_cmp(start),
+            Some(Ordering::Greater | Ordering::Equal)
+        ),
+        Bound::Excluded(start) => value.sql_cmp(start) == Some(Ordering::Greater),
+        Bound::Unbounded => !value.is_null(),
+    }
+}
+
+fn below(value: &TupleValue, end: Bound<&TupleValue>) -> bool {
+    match end {
+        Bound::Included(end) => {
+            matches!(value.sql_cmp(end), Some(Ordering::Less | Ordering::Equal))
+        }
+        Bound::Excluded(end) => value.sql_cmp(end) == Some(Ordering::Less),
+        Bound::Unbounded => !value.is_null(),
+    }
+}
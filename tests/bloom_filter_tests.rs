@@ -0,0 +1,238 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    include!("../src/util/mod.rs");
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+mod page {
+    include!("../src/page.rs");
+}
+
+mod persist {
+    include!("../src/persist/mod.rs");
+}
+
+mod buffer_pool {
+    include!("../src/buffer_pool/mod.rs");
+}
+
+mod free_space_map {
+    include!("../src/free_space_map.rs");
+}
+
+mod zone_map {
+    include!("../src/zone_map.rs");
+}
+
+mod bloom_filter {
+    include!("../src/bloom_filter.rs");
+}
+
+mod heap_file {
+    include!("../src/heap_file.rs");
+}
+
+use std::fs;
+use std::sync::LazyLock;
+
+use bloom_filter::BloomFilters;
+use buffer_pool::buffer_pool::BufferPool;
+use heap_file::HeapFile;
+use schema::Schema;
+use tuple::{Tuple, TupleValue};
+
+static SCHEMA: LazyLock<Schema> =
+    LazyLock::new(|| Schema::parse(&[("id", "integer"), ("name", "varchar")]).unwrap());
+
+fn row(id: i32) -> Tuple<'static> {
+    Tuple {
+        schema: &SCHEMA,
+        values: vec![
+            TupleValue::Integer(id),
+            TupleValue::Varchar(format!("name {}", id % 1000)),
+        ],
+    }
+}
+
+fn remove_files(filename: &str) {
+    let _ = fs::remove_file(format!("./{}", filename));
+    let _ = fs::remove_file(format!("./{}.fsm", filename));
+    let _ = fs::remove_file(format!("./{}.bloom", filename));
+}
+
+fn name(i: i32) -> TupleValue {
+    TupleValue::Varchar(format!("name {}", i))
+}
+
+#[test]
+fn test_filters() {
+    let _ = fs::remove_file("./01_bloom_filters.bloom");
+
+    let mut filters = BloomFilters::open(".", "01_bloom_filters", &[0, 1], 2, 0.01).unwrap();
+    assert_eq!(filters.hash_count(), 7);
+
+    // Pages not covered yet may hold anything.
+    assert!(filters.may_contain(0, 1, &name(1)));
+
+    filters.reset_range(0, 1000);
+    assert_eq!(filters.filter_bits(0, 1), Some(9600));
+    assert_eq!(filters.filter_bits(0, 2), None);
+    for i in 0..1000 {
+        filters.insert(i as u64 % 2, &[TupleValue::Integer(i), name(i)]);
+    }
+    filters.insert(1, &[TupleValue::Null, TupleValue::Null]);
+
+    for i in 0..1000 {
+        assert!(filters.may_contain(0, 0, &TupleValue::Integer(i)));
+        assert!(filters.may_contain(1, 1, &name(i)));
+    }
+    let false_positives = (1000..11000)
+        .filter(|&i| filters.may_contain(0, 1, &name(i)))
+        .count();
+    assert!(false_positives < 200, "{} false positives", false_positives);
+
+    // Values equal in SQL are found whatever their type, nulls never are.
+    assert!(filters.may_contain(0, 0, &TupleValue::BigInt(5)));
+    assert!(!filters.may_contain(0, 0, &TupleValue::Null));
+    // A column without a filter and pages after the filters may hold anything.
+    assert!(filters.may_contain(0, 2, &name(5000)));
+    assert!(filters.may_contain(2, 1, &name(5000)));
+
+    filters.cover(3);
+    assert!(!filters.may_contain(3, 1, &name(1)));
+    filters.flush().unwrap();
+
+    let reopened = BloomFilters::open(".", "01_bloom_filters", &[0, 1], 2, 0.01).unwrap();
+    assert_eq!(reopened.page_count(), 4);
+    assert_eq!(reopened.filter_bits(0, 1), Some(9600));
+    assert!(
+        (0..11000)
+            .all(|i| reopened.may_contain(0, 1, &name(i)) == filters.may_contain(0, 1, &name(i)))
+    );
+
+    // Filters of another rate start empty.
+    let other = BloomFilters::open(".", "01_bloom_filters", &[0, 1], 2, 0.05).unwrap();
+    assert_eq!(other.page_count(), 0);
+
+    // A filter without words is corrupted, bits are taken modulo its size.
+    let mut data = vec![];
+    data.extend_from_slice(&2u64.to_be_bytes());
+    data.extend_from_slice(&0.01f64.to_bits().to_be_bytes());
+    data.extend_from_slice(&2u16.to_be_bytes());
+    data.extend_from_slice(&0u16.to_be_bytes());
+    data.extend_from_slice(&1u16.to_be_bytes());
+    data.extend_from_slice(&1u64.to_be_bytes());
+    data.extend_from_slice(&0u32.to_be_bytes());
+    fs::write("./01_bloom_filters.bloom", data).unwrap();
+    assert!(BloomFilters::open(".", "01_bloom_filters", &[0, 1], 2, 0.01).is_err());
+
+    fs::remove_file("./01_bloom_filters.bloom").unwrap();
+}
+
+#[test]
+fn test_scan_equal_and_vacuum() {
+    remove_files("02_bloom_scan");
+
+    {
+        let pool = BufferPool::new(64, 1024 * 4);
+        let heap = HeapFile::open(&pool, 0, ".", "02_bloom_scan", &SCHEMA)
+            .unwrap()
+            .with_bloom_filters(".", "02_bloom_scan", &[1], 1, 0.01)
+            .unwrap();
+
+        for id in 0..5000 {
+            heap.insert(&row(id)).unwrap();
+        }
+
+        let ids = |value: &TupleValue| {
+            let mut ids = heap
+                .scan_equal(1, value)
+                .map(|row| row.unwrap().1.values[0].clone())
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+        assert_eq!(
+            ids(&name(42)),
+            (0..5)
+                .map(|i| TupleValue::Integer(i * 1000 + 42))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(ids(&name(1000)), vec![]);
+        assert_eq!(ids(&TupleValue::Null), vec![]);
+
+        // Deleted values stay in the filters until a vacuum, which builds them
+        // again sized for the rows left.
+        let rows = heap.scan().map(|row| row.unwrap()).collect::<Vec<_>>();
+        for (row_id, tuple) in rows {
+            if tuple.values[0] != TupleValue::Integer(42) {
+                heap.delete(row_id).unwrap();
+            }
+        }
+        heap.flush().unwrap();
+        let filters = BloomFilters::open(".", "02_bloom_scan", &[1], 1, 0.01).unwrap();
+        assert!(filters.filter_bits(0, 1).unwrap() > 64);
+
+        heap.vacuum().unwrap();
+        heap.flush().unwrap();
+        assert_eq!(ids(&name(42)), vec![TupleValue::Integer(42)]);
+    }
+
+    let filters = BloomFilters::open(".", "02_bloom_scan", &[1], 1, 0.01).unwrap();
+    assert_eq!(filters.filter_bits(0, 1), Some(64));
+    assert!(filters.may_contain(0, 1, &name(42)));
+
+    // Filters are built on open if they don't cover every page.
+    fs::remove_file("./02_bloom_scan.bloom").unwrap();
+    let pool = BufferPool::new(64, 1024 * 4);
+    let heap = HeapFile::open(&pool, 0, ".", "02_bloom_scan", &SCHEMA)
+        .unwrap()
+        .with_bloom_filters(".", "02_bloom_scan", &[1], 1, 0.01)
+        .unwrap();
+    assert_eq!(heap.scan_equal(1, &name(42)).count(), 1);
+    heap.flush().unwrap();
+
+    fs::remove_file("./02_bloom_scan").unwrap();
+    fs::remove_file("./02_bloom_scan.fsm").unwrap();
+    fs::remove_file("./02_bloom_scan.bloom").unwrap();
+}
+
+#[test]
+fn test_reopen_without_flush() {
+    remove_files("03_bloom_no_flush");
+
+    {
+        let pool = BufferPool::new(64, 1024 * 4);
+        let heap = HeapFile::open(&pool, 0, ".", "03_bloom_no_flush", &SCHEMA)
+            .unwrap()
+            .with_bloom_filters(".", "03_bloom_no_flush", &[1], 1, 0.01)
+            .unwrap();
+
+        for id in 0..5 {
+            heap.insert(&row(id)).unwrap();
+        }
+        heap.flush().unwrap();
+
+        // The row lands on a page the stored filters cover already.
+        heap.insert(&row(999)).unwrap();
+        assert_eq!(heap.page_count(), 1);
+    }
+
+    let pool = BufferPool::new(64, 1024 * 4);
+    let heap = HeapFile::open(&pool, 0, ".", "03_bloom_no_flush", &SCHEMA)
+        .unwrap()
+        .with_bloom_filters(".", "03_bloom_no_flush", &[1], 1, 0.01)
+        .unwrap();
+    assert_eq!(heap.scan_equal(1, &name(999)).count(), 1);
+    heap.flush().unwrap();
+
+    remove_files("03_bloom_no_flush");
+}
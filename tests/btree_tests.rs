@@ -0,0 +1,329 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    include!("../src/util/mod.rs");
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+mod page {
+    include!("../src/page.rs");
+}
+
+mod persist {
+    include!("../src/persist/mod.rs");
+}
+
+mod buffer_pool {
+    include!("../src/buffer_pool/mod.rs");
+}
+
+mod free_space_map {
+    include!("../src/free_space_map.rs");
+}
+
+mod zone_map {
+    include!("../src/zone_map.rs");
+}
+
+mod bloom_filter {
+    include!("../src/bloom_filter.rs");
+}
+
+mod heap_file {
+    include!("../src/heap_file.rs");
+}
+
+mod index {
+    include!("../src/index/mod.rs");
+}
+
+use std::fs;
+use std::ops::Bound;
+
+use buffer_pool::buffer_pool::BufferPool;
+use heap_file::RowId;
+use index::btree::{BTree, BTreeError};
+
+const PAGE_SIZE: usize = 1024 * 4;
+
+// Long keys make a tree of three levels from a few thousand entries.
+fn key(i: u32) -> Vec<u8> {
+    format!("{:0100}", i).into_bytes()
+}
+
+fn row_id(i: u32) -> RowId {
+    RowId::new(i as u64 / 100, (i % 100) as u16)
+}
+
+// Every number below `count` once, not in order.
+fn shuffled(count: u32) -> impl Iterator<Item = u32> {
+    (0..count).map(move |i| (i as u64 * 7919 % count as u64) as u32)
+}
+
+fn keys(tree: &BTree, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Vec<Vec<u8>> {
+    tree.range(start, end)
+        .unwrap()
+        .map(|entry| entry.unwrap().0)
+        .collect()
+}
+
+#[test]
+fn test_insert_lookup_and_range() {
+    let _ = fs::remove_file("./01_btree_insert");
+
+    let pool = BufferPool::new(1024, PAGE_SIZE);
+    let tree = BTree::open(&pool, 0, ".", "01_btree_insert").unwrap();
+    assert_eq!(
+        keys(&tree, Bound::Unbounded, Bound::Unbounded),
+        Vec::<Vec<u8>>::new()
+    );
+
+    for i in shuffled(5000) {
+        tree.insert(&key(i), row_id(i)).unwrap();
+    }
+    assert!(tree.page_count() > 100);
+
+    assert_eq!(tree.get(&key(1234)).unwrap(), vec![row_id(1234)]);
+    assert_eq!(tree.get(&key(5000)).unwrap(), vec![]);
+    assert_eq!(
+        keys(&tree, Bound::Unbounded, Bound::Unbounded),
+        (0..5000).map(key).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        keys(
+            &tree,
+            Bound::Excluded(&key(100)),
+            Bound::Included(&key(200))
+        ),
+        (101..=200).map(key).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        keys(&tree, Bound::Included(&key(4990)), Bound::Unbounded),
+        (4990..5000).map(key).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        keys(&tree, Bound::Included(&key(7)), Bound::Excluded(&key(7))),
+        Vec::<Vec<u8>>::new()
+    );
+
+    // A key may point to many rows, each entry is stored once.
+    for i in 0..300 {
+        tree.insert(&key(42), RowId::new(1000, i)).unwrap();
+    }
+    let rows = tree.get(&key(42)).unwrap();
+    assert_eq!(rows.len(), 301);
+    assert!(rows.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(matches!(
+        tree.insert(&key(42), row_id(42)),
+        Err(BTreeError::DuplicateEntry)
+    ));
+
+    let long = vec![0; tree.max_key_length() + 1];
+    assert!(matches!(
+        tree.insert(&long, row_id(0)),
+        Err(BTreeError::KeyTooLong(_))
+    ));
+    tree.insert(&long[1..], row_id(0)).unwrap();
+    assert_eq!(tree.get(&long[1..]).unwrap(), vec![row_id(0)]);
+
+    fs::remove_file("./01_btree_insert").unwrap();
+}
+
+#[test]
+fn test_delete_merges_nodes_and_reuses_pages() {
+    let _ = fs::remove_file("./02_btree_delete");
+
+    let pool = BufferPool::new(1024, PAGE_SIZE);
+    let tree = BTree::open(&pool, 0, ".", "02_btree_delete").unwrap();
+
+    for i in shuffled(3000) {
+        tree.insert(&key(i), row_id(i)).unwrap();
+    }
+    let page_count = tree.page_count();
+
+    assert!(!tree.delete(&key(1), row_id(2)).unwrap());
+    for i in shuffled(3000).filter(|i| i % 2 == 1) {
+        assert!(tree.delete(&key(i), row_id(i)).unwrap());
+    }
+    assert!(!tree.delete(&key(1), row_id(1)).unwrap());
+    assert_eq!(
+        keys(&tree, Bound::Unbounded, Bound::Unbounded),
+        (0..3000).step_by(2).map(key).collect::<Vec<_>>()
+    );
+
+    for i in shuffled(3000).filter(|i| i % 2 == 0) {
+        assert!(tree.delete(&key(i), row_id(i)).unwrap());
+    }
+    assert_eq!(
+        keys(&tree, Bound::Unbounded, Bound::Unbounded),
+        Vec::<Vec<u8>>::new()
+    );
+
+    // Pages of merged nodes are taken by splits before the file grows.
+    for i in shuffled(3000) {
+        tree.insert(&key(i), row_id(i)).unwrap();
+    }
+    assert_eq!(tree.page_count(), page_count);
+    assert_eq!(
+        keys(&tree, Bound::Unbounded, Bound::Unbounded),
+        (0..3000).map(key).collect::<Vec<_>>()
+    );
+
+    fs::remove_file("./02_btree_delete").unwrap();
+}
+
+#[test]
+fn test_reopen() {
+    let _ = fs::remove_file("./03_btree_reopen");
+
+    let page_count = {
+        let pool = BufferPool::new(1024, PAGE_SIZE);
+        let tree = BTree::open(&pool, 0, ".", "03_btree_reopen").unwrap();
+
+        for i in shuffled(2000) {
+            tree.insert(&key(i), row_id(i)).unwrap();
+        }
+        for i in 0..1000 {
+            tree.delete(&key(i), row_id(i)).unwrap();
+        }
+
+        tree.page_count()
+    };
+
+    let pool = BufferPool::new(1024, PAGE_SIZE);
+    let tree = BTree::open(&pool, 7, ".", "03_btree_reopen").unwrap();
+    assert_eq!(tree.relation_id(), 7);
+    assert_eq!(
+        keys(&tree, Bound::Unbounded, Bound::Unbounded),
+        (1000..2000).map(key).collect::<Vec<_>>()
+    );
+
+    // Free pages are found on open, a tree built again fits into the same pages.
+    for i in 1000..2000 {
+        tree.delete(&key(i), row_id(i)).unwrap();
+    }
+    for i in shuffled(2000) {
+        tree.insert(&key(i), row_id(i)).unwrap();
+    }
+    assert_eq!(tree.page_count(), page_count);
+    assert_eq!(tree.get(&key(500)).unwrap(), vec![row_id(500)]);
+
+    fs::remove_file("./03_btree_reopen").unwrap();
+}
+
+#[test]
+fn test_concurrent_writers_and_scans() {
+    let _ = fs::remove_file("./04_btree_concurrent");
+
+    let pool = BufferPool::new(1024, PAGE_SIZE);
+    let tree = BTree::open(&pool, 0, ".", "04_btree_concurrent").unwrap();
+
+    for i in (0..4000).filter(|i| i % 4 == 3) {
+        tree.insert(&key(i), row_id(i)).unwrap();
+    }
+
+    std::thread::scope(|s| {
+        for thread in 0..3 {
+            let tree = &tree;
+            s.spawn(move || {
+                for i in shuffled(4000).filter(|i| i % 4 == thread) {
+                    tree.insert(&key(i), row_id(i)).unwrap();
+                }
+            });
+        }
+
+        let tree = &tree;
+        s.spawn(move || {
+            for i in shuffled(4000).filter(|i| i % 4 == 3) {
+                assert!(tree.delete(&key(i), row_id(i)).unwrap());
+            }
+        });
+
+        // Scans see keys in order, whatever the writers do.
+        s.spawn(move || {
+            for _ in 0..20 {
+                let keys = keys(tree, Bound::Unbounded, Bound::Unbounded);
+                assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+            }
+        });
+    });
+
+    assert_eq!(
+        keys(&tree, Bound::Unbounded, Bound::Unbounded),
+        (0..4000)
+            .filter(|i| i % 4 != 3)
+            .map(key)
+            .collect::<Vec<_>>()
+    );
+
+    fs::remove_file("./04_btree_concurrent").unwrap();
+}
+
+#[test]
+fn test_build() {
+    let _ = fs::remove_file("./05_btree_build");
+
+    let pool = BufferPool::new(1024, PAGE_SIZE);
+    let tree = BTree::open(&pool, 0, ".", "05_btree_build").unwrap();
+
+    let duplicate = vec![(key(1), row_id(1)), (key(1), row_id(1))];
+    assert!(matches!(
+        tree.build(duplicate),
+        Err(BTreeError::DuplicateEntry)
+    ));
+
+    tree.build(shuffled(5000).map(|i| (key(i), row_id(i))).collect())
+        .unwrap();
+    assert_eq!(
+        keys(&tree, Bound::Unbounded, Bound::Unbounded),
+        (0..5000).map(key).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        keys(
+            &tree,
+            Bound::Included(&key(2500)),
+            Bound::Excluded(&key(2600))
+        ),
+        (2500..2600).map(key).collect::<Vec<_>>()
+    );
+    assert_eq!(tree.get(&key(4321)).unwrap(), vec![row_id(4321)]);
+    assert!(matches!(
+        tree.build(vec![(key(1), row_id(1))]),
+        Err(BTreeError::NotEmpty)
+    ));
+
+    // A built tree takes fewer pages than one built by inserts, and works as one.
+    let page_count = tree.page_count();
+    {
+        let _ = fs::remove_file("./05_btree_build_inserts");
+        let inserted = BTree::open(&pool, 1, ".", "05_btree_build_inserts").unwrap();
+        for i in shuffled(5000) {
+            inserted.insert(&key(i), row_id(i)).unwrap();
+        }
+        assert!(page_count < inserted.page_count());
+        fs::remove_file("./05_btree_build_inserts").unwrap();
+    }
+
+    for i in (0..5000).filter(|i| i % 3 == 0) {
+        assert!(tree.delete(&key(i), row_id(i)).unwrap());
+    }
+    for i in 5000..6000 {
+        tree.insert(&key(i), row_id(i)).unwrap();
+    }
+    assert_eq!(
+        keys(&tree, Bound::Unbounded, Bound::Unbounded),
+        (0..6000)
+            .filter(|i| *i >= 5000 || i % 3 != 0)
+            .map(key)
+            .collect::<Vec<_>>()
+    );
+
+    fs::remove_file("./05_btree_build").unwrap();
+}
@@ -1,3 +1,5 @@
+#![cfg_attr(test, allow(dead_code))]
+
 use crate::buffer_pool::clock::Clock;
 
 mod util {
@@ -8,12 +10,16 @@ mod tuple {
     include!("../src/tuple.rs");
 }
 
+mod schema {
+    include!("../src/schema.rs");
+}
+
 mod page {
     include!("../src/page.rs");
 }
 
 mod persist {
-    include!("../src/persist.rs");
+    include!("../src/persist/mod.rs");
 }
 
 mod buffer_pool {
@@ -93,5 +99,5 @@ fn test_concurrent_track_delete() {
         }
     });
 
-    assert_eq!(true, c.find_victim_key().is_err());
+    assert!(c.find_victim_key().is_err());
 }
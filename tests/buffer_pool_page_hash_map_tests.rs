@@ -1,8 +1,12 @@
+#![cfg_attr(test, allow(dead_code))]
+
 use crate::{
     buffer_pool::page_hash_map::{
         BufferPoolPageHashMap,
         InsertPageResult::{ExistingPage, NewPage},
     },
+    page::PageKey,
+    schema::{DataType, Schema},
     tuple::{Tuple, TupleValue},
 };
 
@@ -14,12 +18,16 @@ mod tuple {
     include!("../src/tuple.rs");
 }
 
+mod schema {
+    include!("../src/schema.rs");
+}
+
 mod page {
     include!("../src/page.rs");
 }
 
 mod persist {
-    include!("../src/persist.rs");
+    include!("../src/persist/mod.rs");
 }
 
 mod buffer_pool {
@@ -29,21 +37,22 @@ mod buffer_pool {
 #[test]
 fn test_simple() {
     let m = BufferPoolPageHashMap::new(100);
+    let schema = Schema::from_types(&[DataType::Integer]);
 
     {
-        let Ok(NewPage(mut page)) = m.insert_page(&1) else {
+        let Ok(NewPage(mut page)) = m.insert_page(&PageKey::new(0, 1)) else {
             panic!("Cannot insert page");
         };
         let _ = page.write(&Tuple {
-            types: &["integer"],
+            schema: &schema,
             values: vec![TupleValue::Integer(15)],
         });
         page.id = 1;
     }
 
     {
-        let page = m.read_page(&1).unwrap();
-        let tuple = page.read(0, &["integer"]).unwrap();
+        let page = m.read_page(&PageKey::new(0, 1)).unwrap();
+        let tuple = page.read(0, &schema).unwrap();
 
         assert_eq!(tuple.values[0], TupleValue::Integer(15));
     }
@@ -57,7 +66,7 @@ fn test_insert_multithread_simple() {
         for _ in 0..10 {
             s.spawn(move || {
                 for id in 0..50 {
-                    match m.insert_page(&id) {
+                    match m.insert_page(&PageKey::new(0, id)) {
                         Ok(NewPage(mut guard)) => {
                             println!("New page {}", id);
 
@@ -78,6 +87,6 @@ fn test_insert_multithread_simple() {
     });
 
     for id in 0..50 {
-        assert_eq!(id, m.read_page(&id).unwrap().id);
+        assert_eq!(id, m.read_page(&PageKey::new(0, id)).unwrap().id);
     }
 }
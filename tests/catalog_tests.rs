@@ -0,0 +1,316 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    include!("../src/util/mod.rs");
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+mod page {
+    include!("../src/page.rs");
+}
+
+mod persist {
+    include!("../src/persist/mod.rs");
+}
+
+mod buffer_pool {
+    include!("../src/buffer_pool/mod.rs");
+}
+
+mod free_space_map {
+    include!("../src/free_space_map.rs");
+}
+
+mod zone_map {
+    include!("../src/zone_map.rs");
+}
+
+mod bloom_filter {
+    include!("../src/bloom_filter.rs");
+}
+
+mod heap_file {
+    include!("../src/heap_file.rs");
+}
+
+mod catalog {
+    include!("../src/catalog.rs");
+}
+
+use std::fs;
+
+use buffer_pool::buffer_pool::BufferPool;
+use catalog::{
+    COLUMNS_RELATION_ID, Catalog, CatalogError, Engine, FIRST_USER_RELATION_ID, TABLES_RELATION_ID,
+};
+use heap_file::HeapFile;
+use schema::{DataType, Schema};
+use tuple::{Tuple, TupleValue};
+
+#[test]
+fn test_bootstrap() {
+    let path = "./01_catalog_bootstrap";
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+
+    let pool = BufferPool::new(16, 1024 * 4);
+    let catalog = Catalog::open(&pool, path).unwrap();
+
+    let tables = catalog.tables().unwrap();
+    let names = tables.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec!["catalog_tables", "catalog_columns", "catalog_indexes"]
+    );
+
+    let columns = catalog.table("catalog_columns").unwrap().unwrap();
+    assert_eq!(
+        columns.schema,
+        Schema::parse(&[
+            ("table_id", "integer not null"),
+            ("name", "varchar not null"),
+            ("type", "varchar not null"),
+            ("ordinal", "integer not null"),
+            ("nullable", "integer not null"),
+        ])
+        .unwrap()
+    );
+
+    fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_create_table_and_index() {
+    let path = "./02_catalog_create";
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+
+    {
+        let pool = BufferPool::new(16, 1024 * 4);
+        let catalog = Catalog::open(&pool, path).unwrap();
+
+        let schema = Schema::parse(&[("id", "integer"), ("name", "varchar")]).unwrap();
+        let users = catalog.create_table("users", &schema).unwrap();
+        assert_eq!(users.id, FIRST_USER_RELATION_ID);
+
+        assert!(matches!(
+            catalog.create_table("users", &Schema::from_types(&[DataType::Integer])),
+            Err(CatalogError::TableExists(_))
+        ));
+
+        let index = catalog
+            .create_index("users_name", "users", &["name"], true)
+            .unwrap();
+        assert_eq!(index.id, FIRST_USER_RELATION_ID + 1);
+        assert_eq!(index.columns, vec![1]);
+
+        assert!(matches!(
+            catalog.create_index("users_email", "users", &["email"], false),
+            Err(CatalogError::ColumnNotFound(_))
+        ));
+
+        let heap = HeapFile::open(&pool, users.id, path, &users.filename, &users.schema).unwrap();
+        heap.insert(&Tuple {
+            schema: &users.schema,
+            values: vec![
+                TupleValue::Integer(1),
+                TupleValue::Varchar("first".to_owned()),
+            ],
+        })
+        .unwrap();
+
+        catalog.flush().unwrap();
+        heap.flush().unwrap();
+    }
+
+    {
+        let pool = BufferPool::new(16, 1024 * 4);
+        let catalog = Catalog::open(&pool, path).unwrap();
+
+        let users = catalog.table("users").unwrap().unwrap();
+        assert_eq!(users.schema.index_of("name"), Some(1));
+
+        let indexes = catalog.indexes(users.id).unwrap();
+        assert_eq!(indexes.len(), 1);
+        assert_eq!(indexes[0].name, "users_name");
+        assert!(indexes[0].unique);
+
+        let orders = catalog
+            .create_table("orders", &Schema::from_types(&[DataType::Integer]))
+            .unwrap();
+        assert_eq!(orders.id, FIRST_USER_RELATION_ID + 2);
+
+        let heap = HeapFile::open(&pool, users.id, path, &users.filename, &users.schema).unwrap();
+        let rows = heap.scan().map(|r| r.unwrap().1).collect::<Vec<_>>();
+        assert_eq!(rows[0].values[1], TupleValue::Varchar("first".to_owned()));
+    }
+
+    fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_lsm_engine_reopen() {
+    let path = "./03_catalog_lsm";
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+
+    let schema = Schema::parse(&[("id", "integer not null"), ("name", "varchar")]).unwrap();
+
+    {
+        let pool = BufferPool::new(16, 1024 * 4);
+        let catalog = Catalog::open(&pool, path).unwrap();
+
+        assert!(matches!(
+            Engine::lsm(&schema, &[]),
+            Err(CatalogError::EmptyKey)
+        ));
+        assert!(matches!(
+            catalog.create_table_with_engine("events", &schema, Engine::Lsm { key: vec![] }),
+            Err(CatalogError::EmptyKey)
+        ));
+
+        let engine = Engine::lsm(&schema, &["name", "id"]).unwrap();
+        catalog
+            .create_table_with_engine("events", &schema, engine)
+            .unwrap();
+        catalog.flush().unwrap();
+    }
+
+    let pool = BufferPool::new(16, 1024 * 4);
+    let catalog = Catalog::open(&pool, path).unwrap();
+
+    let events = catalog.table("events").unwrap().unwrap();
+    assert_eq!(events.engine, Engine::Lsm { key: vec![1, 0] });
+
+    fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_upgrade_legacy_catalog() {
+    let path = "./04_catalog_legacy";
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+
+    // A catalog as written before tables had an engine column.
+    let legacy_tables = Schema::parse(&[
+        ("id", "integer not null"),
+        ("name", "varchar not null"),
+        ("filename", "varchar not null"),
+    ])
+    .unwrap();
+    let columns_schema = Schema::parse(&[
+        ("table_id", "integer not null"),
+        ("name", "varchar not null"),
+        ("type", "varchar not null"),
+        ("ordinal", "integer not null"),
+        ("nullable", "integer not null"),
+    ])
+    .unwrap();
+    let users_schema = Schema::parse(&[("id", "integer"), ("name", "varchar")]).unwrap();
+
+    {
+        let pool = BufferPool::new(16, 1024 * 4);
+        let tables = HeapFile::open(
+            &pool,
+            TABLES_RELATION_ID,
+            path,
+            "catalog_tables",
+            &legacy_tables,
+        )
+        .unwrap();
+        let columns = HeapFile::open(
+            &pool,
+            COLUMNS_RELATION_ID,
+            path,
+            "catalog_columns",
+            &columns_schema,
+        )
+        .unwrap();
+
+        for (id, name, filename, schema) in [
+            (
+                TABLES_RELATION_ID,
+                "catalog_tables",
+                "catalog_tables",
+                &legacy_tables,
+            ),
+            (
+                COLUMNS_RELATION_ID,
+                "catalog_columns",
+                "catalog_columns",
+                &columns_schema,
+            ),
+            (
+                FIRST_USER_RELATION_ID,
+                "users",
+                "table_16.data",
+                &users_schema,
+            ),
+        ] {
+            for (ordinal, column) in schema.columns().iter().enumerate() {
+                columns
+                    .insert(&Tuple {
+                        schema: &columns_schema,
+                        values: vec![
+                            TupleValue::Integer(id as i32),
+                            TupleValue::Varchar(column.name.clone()),
+                            TupleValue::Varchar(column.data_type.name()),
+                            TupleValue::Integer(ordinal as i32),
+                            TupleValue::Integer(column.nullable as i32),
+                        ],
+                    })
+                    .unwrap();
+            }
+            tables
+                .insert(&Tuple {
+                    schema: &legacy_tables,
+                    values: vec![
+                        TupleValue::Integer(id as i32),
+                        TupleValue::Varchar(name.to_owned()),
+                        TupleValue::Varchar(filename.to_owned()),
+                    ],
+                })
+                .unwrap();
+        }
+
+        tables.flush().unwrap();
+        columns.flush().unwrap();
+    }
+
+    {
+        let pool = BufferPool::new(16, 1024 * 4);
+        let catalog = Catalog::open(&pool, path).unwrap();
+
+        let users = catalog.table("users").unwrap().unwrap();
+        assert_eq!(users.schema, users_schema);
+        assert_eq!(users.engine, Engine::Heap);
+
+        let tables = catalog.table("catalog_tables").unwrap().unwrap();
+        assert_eq!(tables.schema.index_of("engine"), Some(3));
+
+        catalog.create_table("orders", &users_schema).unwrap();
+        catalog.flush().unwrap();
+    }
+
+    let pool = BufferPool::new(16, 1024 * 4);
+    let catalog = Catalog::open(&pool, path).unwrap();
+    let names = catalog
+        .tables()
+        .unwrap()
+        .into_iter()
+        .map(|t| t.name)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec!["catalog_tables", "catalog_columns", "users", "orders"]
+    );
+
+    fs::remove_dir_all(path).unwrap();
+}
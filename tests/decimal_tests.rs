@@ -0,0 +1,283 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    pub mod type_converter {
+        include!("../src/util/type_converter.rs");
+    }
+
+    pub mod temporal {
+        include!("../src/util/temporal.rs");
+    }
+
+    pub mod decimal {
+        include!("../src/util/decimal.rs");
+    }
+
+    pub mod uuid {
+        include!("../src/util/uuid.rs");
+    }
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+use schema::{DataType, Schema, SchemaError};
+use tuple::{Tuple, TupleToDataError, TupleValue};
+use util::decimal::{Decimal, DecimalError, RoundingMode};
+
+fn d(s: &str) -> Decimal {
+    Decimal::parse(s).unwrap()
+}
+
+#[test]
+fn test_parse_and_format() {
+    for s in [
+        "0",
+        "-1",
+        "123.45",
+        "-0.05",
+        "0.000",
+        "99999999999999999999999999999999999999",
+    ] {
+        assert_eq!(d(s).to_string(), s);
+    }
+    assert_eq!(d("+.5").to_string(), "0.5");
+    assert_eq!(d("7.").to_string(), "7");
+    assert_eq!(d("1.50"), d("1.5"));
+    assert!(d("-2") < d("1.999"));
+
+    assert_eq!(Decimal::parse(""), Err(DecimalError::InvalidFormat));
+    assert_eq!(Decimal::parse("1e5"), Err(DecimalError::InvalidFormat));
+    assert_eq!(Decimal::parse("-"), Err(DecimalError::InvalidFormat));
+    assert_eq!(
+        Decimal::parse("999999999999999999999999999999999999999999"),
+        Err(DecimalError::Overflow)
+    );
+}
+
+#[test]
+fn test_arithmetic() {
+    assert_eq!(d("0.1").add(&d("0.2")).unwrap().to_string(), "0.3");
+    assert_eq!(d("1.05").sub(&d("2")).unwrap().to_string(), "-0.95");
+    assert_eq!(d("1.5").mul(&d("-0.25")).unwrap().to_string(), "-0.375");
+    assert_eq!(
+        d("1")
+            .div(&d("3"), 5, RoundingMode::HalfUp)
+            .unwrap()
+            .to_string(),
+        "0.33333"
+    );
+    assert_eq!(
+        d("2")
+            .div(&d("3"), 2, RoundingMode::HalfUp)
+            .unwrap()
+            .to_string(),
+        "0.67"
+    );
+    assert_eq!(
+        d("1000")
+            .div(&d("0.001"), 0, RoundingMode::Down)
+            .unwrap()
+            .to_string(),
+        "1000000"
+    );
+    assert_eq!(
+        d("1").div(&d("0"), 2, RoundingMode::HalfUp),
+        Err(DecimalError::DivisionByZero)
+    );
+
+    let max = d("99999999999999999999999999999999999999");
+    assert_eq!(max.add(&max), Err(DecimalError::Overflow));
+    assert_eq!(max.sub(&max).unwrap().to_string(), "0");
+    assert_eq!(max.mul(&max), Err(DecimalError::Overflow));
+    assert_eq!(max.add(&d("0.1")), Err(DecimalError::Overflow));
+
+    // Scales above the maximum precision are rejected, not wrapped.
+    assert_eq!(
+        d("1").div(&d("3"), 39, RoundingMode::HalfUp),
+        Err(DecimalError::Overflow)
+    );
+    assert_eq!(
+        d("1").rescale(39, RoundingMode::HalfUp),
+        Err(DecimalError::Overflow)
+    );
+    let tiny = d("0.00000000000000000000000000000000000001");
+    assert_eq!(
+        d("1").div(&tiny, 38, RoundingMode::HalfUp),
+        Err(DecimalError::Overflow)
+    );
+    assert_eq!(tiny.div(&d("1"), 38, RoundingMode::HalfUp).unwrap(), tiny);
+}
+
+#[test]
+fn test_compare_with_doubles() {
+    use std::cmp::Ordering;
+
+    assert_eq!(d("2.50").to_f64_exact(), Some(2.5));
+    assert_eq!(d("-0.125").to_f64_exact(), Some(-0.125));
+    assert_eq!(d("0.1").to_f64_exact(), None);
+    assert_eq!(d("9007199254740993").to_f64_exact(), None);
+    assert_eq!(
+        d("18014398509481984").to_f64_exact(),
+        Some((1u64 << 54) as f64)
+    );
+
+    assert_eq!(d("0.5").cmp_f64(0.5), Ordering::Equal);
+    assert_eq!(d("0.1").cmp_f64(0.1), Ordering::Less);
+    assert_eq!(d("-0.1").cmp_f64(-0.1), Ordering::Greater);
+    assert_eq!(
+        d("9007199254740993").cmp_f64(9007199254740992.0),
+        Ordering::Greater
+    );
+    assert_eq!(d("0").cmp_f64(-0.0), Ordering::Equal);
+    assert_eq!(
+        d("0.00000000000000000000000000000000000001").cmp_f64(1e-38),
+        Ordering::Greater
+    );
+    assert_eq!(
+        d("99999999999999999999999999999999999999").cmp_f64(1e38),
+        Ordering::Greater
+    );
+    assert_eq!(d("-1").cmp_f64(f64::MIN_POSITIVE), Ordering::Less);
+    assert_eq!(d("1").cmp_f64(f64::NEG_INFINITY), Ordering::Greater);
+    assert_eq!(d("1").cmp_f64(f64::MAX), Ordering::Less);
+}
+
+#[test]
+fn test_rounding_modes() {
+    let cases = [
+        (RoundingMode::Down, ["2", "2", "-2", "-2", "2"]),
+        (RoundingMode::Up, ["3", "3", "-3", "-3", "3"]),
+        (RoundingMode::Floor, ["2", "2", "-3", "-3", "2"]),
+        (RoundingMode::Ceiling, ["3", "3", "-2", "-2", "3"]),
+        (RoundingMode::HalfUp, ["3", "3", "-3", "-2", "2"]),
+        (RoundingMode::HalfDown, ["2", "3", "-2", "-2", "2"]),
+        (RoundingMode::HalfEven, ["2", "3", "-2", "-2", "2"]),
+    ];
+
+    for (mode, expected) in cases {
+        for (value, expected) in ["2.5", "2.51", "-2.5", "-2.1", "2.0001"]
+            .iter()
+            .zip(expected)
+        {
+            assert_eq!(
+                d(value).round(0, mode).unwrap().to_string(),
+                expected,
+                "{} {:?}",
+                value,
+                mode
+            );
+        }
+    }
+    assert_eq!(
+        d("3.5")
+            .round(0, RoundingMode::HalfEven)
+            .unwrap()
+            .to_string(),
+        "4"
+    );
+    assert_eq!(
+        d("1.25").round(4, RoundingMode::Down).unwrap().to_string(),
+        "1.25"
+    );
+    assert_eq!(
+        d("1.25")
+            .rescale(4, RoundingMode::Down)
+            .unwrap()
+            .to_string(),
+        "1.2500"
+    );
+}
+
+#[test]
+fn test_decimal_type() {
+    assert_eq!(
+        DataType::parse("NUMERIC(10, 2)"),
+        Ok(DataType::Decimal {
+            precision: 10,
+            scale: 2
+        })
+    );
+    assert_eq!(
+        DataType::parse("decimal(5)"),
+        Ok(DataType::Decimal {
+            precision: 5,
+            scale: 0
+        })
+    );
+    assert_eq!(
+        DataType::parse("decimal"),
+        Ok(DataType::Decimal {
+            precision: 38,
+            scale: 0
+        })
+    );
+    for name in [
+        "decimal(2,3)",
+        "decimal(0)",
+        "decimal(39)",
+        "decimal(1,2,3)",
+        "decimal(a)",
+    ] {
+        assert_eq!(
+            DataType::parse(name),
+            Err(SchemaError::InvalidTypeModifier(name.to_owned()))
+        );
+    }
+
+    let schema = Schema::parse(&[("price", "decimal(10, 2) not null")]).unwrap();
+    assert!(!schema.columns()[0].nullable);
+    assert_eq!(
+        DataType::parse(&schema.columns()[0].data_type.name()),
+        Ok(schema.columns()[0].data_type)
+    );
+}
+
+#[test]
+fn test_decimal_tuple() {
+    let schema = Schema::parse(&[
+        ("a", "decimal(4,2)"),
+        ("b", "decimal(9,0)"),
+        ("c", "decimal(18,6)"),
+        ("d", "decimal(38,10)"),
+    ])
+    .unwrap();
+
+    let tuple = Tuple {
+        schema: &schema,
+        values: vec![
+            TupleValue::Decimal(d("-99.99")),
+            TupleValue::Decimal(d("123456789")),
+            TupleValue::Decimal(d("0.000001")),
+            TupleValue::Decimal(d("-1234567890123456789012345678.0123456789")),
+        ],
+    };
+    let data = tuple.to_data().unwrap();
+    assert_eq!(data.len(), 1 + 2 + 4 + 8 + 16);
+    assert_eq!(Tuple::read(&schema, &data).unwrap(), tuple);
+
+    // Extra fractional digits are rounded on write, extra integer digits are rejected.
+    let schema = Schema::parse(&[("a", "decimal(4,2)")]).unwrap();
+    let tuple = Tuple {
+        schema: &schema,
+        values: vec![TupleValue::Decimal(d("1.005"))],
+    };
+    let read = Tuple::read(&schema, &tuple.to_data().unwrap()).unwrap();
+    assert!(matches!(&read.values[0], TupleValue::Decimal(v) if v.to_string() == "1.01"));
+
+    for value in ["100", "99.995"] {
+        let tuple = Tuple {
+            schema: &schema,
+            values: vec![TupleValue::Decimal(d(value))],
+        };
+        assert!(matches!(
+            tuple.to_data(),
+            Err(TupleToDataError::DecimalOutOfRange(0))
+        ));
+    }
+}
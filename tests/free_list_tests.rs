@@ -1,7 +1,13 @@
+#![cfg_attr(test, allow(dead_code))]
+
 pub mod tuple {
     include!("../src/tuple.rs");
 }
 
+mod schema {
+    include!("../src/schema.rs");
+}
+
 pub mod page {
     include!("../src/page.rs");
 }
@@ -11,6 +17,18 @@ mod util {
         include!("../src/util/type_converter.rs");
     }
 
+    pub mod temporal {
+        include!("../src/util/temporal.rs");
+    }
+
+    pub mod decimal {
+        include!("../src/util/decimal.rs");
+    }
+
+    pub mod uuid {
+        include!("../src/util/uuid.rs");
+    }
+
     pub mod free_list {
         include!("../src/util/free_list.rs");
     }
@@ -29,16 +47,16 @@ fn test_concurrent_free_list() {
         println!("{:?}", free_list.next.load(Ordering::Relaxed).is_null());
 
         let next = unsafe { Box::from_raw(free_list.next.load(Ordering::Relaxed)) };
-        println!("{:?}", (*next).value);
-        println!("{:?}", (*next).next.load(Ordering::Relaxed).is_null());
+        println!("{:?}", next.value);
+        println!("{:?}", next.next.load(Ordering::Relaxed).is_null());
 
-        let next = unsafe { Box::from_raw((*next).next.load(Ordering::Relaxed)) };
-        println!("{:?}", (*next).value);
-        println!("{:?}", (*next).next.load(Ordering::Relaxed).is_null());
+        let next = unsafe { Box::from_raw(next.next.load(Ordering::Relaxed)) };
+        println!("{:?}", next.value);
+        println!("{:?}", next.next.load(Ordering::Relaxed).is_null());
 
-        let next = unsafe { Box::from_raw((&*next).next.load(Ordering::Relaxed)) };
-        println!("{:?}", (*next).value);
-        println!("{:?}", (*next).next.load(Ordering::Relaxed).is_null());
+        let next = unsafe { Box::from_raw(next.next.load(Ordering::Relaxed)) };
+        println!("{:?}", next.value);
+        println!("{:?}", next.next.load(Ordering::Relaxed).is_null());
     }
 }
 
@@ -0,0 +1,133 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    pub mod type_converter {
+        include!("../src/util/type_converter.rs");
+    }
+
+    pub mod temporal {
+        include!("../src/util/temporal.rs");
+    }
+
+    pub mod decimal {
+        include!("../src/util/decimal.rs");
+    }
+
+    pub mod uuid {
+        include!("../src/util/uuid.rs");
+    }
+}
+
+mod page {
+    include!("../src/page.rs");
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+mod free_space_map {
+    include!("../src/free_space_map.rs");
+}
+
+use std::fs;
+use std::sync::LazyLock;
+
+use free_space_map::FreeSpaceMap;
+use page::Page;
+use schema::{DataType, Schema};
+use tuple::{Tuple, TupleValue};
+
+static VARCHAR_SCHEMA: LazyLock<Schema> =
+    LazyLock::new(|| Schema::from_types(&[DataType::Varchar]));
+
+fn varchar_tuple(length: usize) -> Tuple<'static> {
+    Tuple {
+        schema: &VARCHAR_SCHEMA,
+        values: vec![TupleValue::Varchar("x".repeat(length))],
+    }
+}
+
+#[test]
+fn test_find_page() {
+    let mut fsm = FreeSpaceMap::open(".", "01_fsm_find_page", 1024 * 8).unwrap();
+
+    assert_eq!(fsm.find_page(0), None);
+
+    fsm.update(0, 100);
+    fsm.update(1, 5000);
+    fsm.update(2, 8000);
+    fsm.update(10, 3000);
+
+    assert_eq!(fsm.page_count(), 11);
+    assert_eq!(fsm.find_page(50), Some(0));
+    assert_eq!(fsm.find_page(200), Some(1));
+    assert_eq!(fsm.find_page(5000), Some(2));
+    assert_eq!(fsm.find_page(8100), None);
+
+    fsm.update(1, 0);
+    fsm.update(2, 0);
+
+    assert_eq!(fsm.find_page(200), Some(10));
+    assert!(fsm.free_space(10) <= 3000);
+    assert!(fsm.free_space(10) > 3000 - 1024 * 8 / 256);
+    assert_eq!(fsm.free_space(100), 0);
+}
+
+#[test]
+fn test_tracks_write_delete_compact() {
+    let mut fsm = FreeSpaceMap::open(".", "02_fsm_track", 1024 * 8).unwrap();
+    let mut p = Page::new(0);
+
+    fsm.update_page(&p);
+    assert_eq!(fsm.find_page(8000), Some(0));
+
+    for _ in 0..4 {
+        p.write(&varchar_tuple(1500)).unwrap();
+    }
+    fsm.update_page(&p);
+    assert_eq!(fsm.find_page(8000), None);
+    assert_eq!(fsm.find_page(1000), Some(0));
+
+    p.delete(1).unwrap();
+    p.delete(2).unwrap();
+    assert!(p.delete(2).is_err());
+    assert!(p.read(1, &VARCHAR_SCHEMA).is_err());
+    assert_eq!(p.read_iterator(&VARCHAR_SCHEMA).count(), 2);
+
+    p.compact();
+    fsm.update_page(&p);
+    assert_eq!(fsm.find_page(5000), Some(0));
+    assert_eq!(p.read(0, &VARCHAR_SCHEMA).unwrap(), varchar_tuple(1500));
+    assert_eq!(p.read(3, &VARCHAR_SCHEMA).unwrap(), varchar_tuple(1500));
+    assert!(p.read(2, &VARCHAR_SCHEMA).is_err());
+
+    let slot = p.write(&varchar_tuple(10)).unwrap();
+    assert_eq!(slot.id, 4);
+    assert_eq!(p.read(4, &VARCHAR_SCHEMA).unwrap(), varchar_tuple(10));
+    assert_eq!(p.read_iterator_raw().count(), 3);
+}
+
+#[test]
+fn test_persist() {
+    let _ = fs::remove_file("./03_fsm_persist.fsm");
+
+    let mut fsm = FreeSpaceMap::open(".", "03_fsm_persist", 1024 * 4).unwrap();
+    for page_id in 0..1000 {
+        fsm.update(page_id, (page_id as usize * 4) % 4096);
+    }
+    fsm.flush().unwrap();
+
+    let reopened = FreeSpaceMap::open(".", "03_fsm_persist", 1024 * 4).unwrap();
+    assert_eq!(reopened.page_count(), 1000);
+    for page_id in 0..1000 {
+        assert_eq!(reopened.free_space(page_id), fsm.free_space(page_id));
+    }
+    assert_eq!(reopened.find_page(4000), fsm.find_page(4000));
+
+    fs::remove_file("./03_fsm_persist.fsm").unwrap();
+}
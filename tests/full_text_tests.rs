@@ -0,0 +1,186 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    include!("../src/util/mod.rs");
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+mod page {
+    include!("../src/page.rs");
+}
+
+mod persist {
+    include!("../src/persist/mod.rs");
+}
+
+mod buffer_pool {
+    include!("../src/buffer_pool/mod.rs");
+}
+
+mod free_space_map {
+    include!("../src/free_space_map.rs");
+}
+
+mod zone_map {
+    include!("../src/zone_map.rs");
+}
+
+mod bloom_filter {
+    include!("../src/bloom_filter.rs");
+}
+
+mod heap_file {
+    include!("../src/heap_file.rs");
+}
+
+mod index {
+    include!("../src/index/mod.rs");
+}
+
+use std::fs;
+
+use buffer_pool::buffer_pool::BufferPool;
+use heap_file::RowId;
+use index::full_text::{FullTextIndex, Query, tokenize};
+
+const PAGE_SIZE: usize = 1024 * 4;
+
+const FIRST_NAMES: [&str; 5] = ["Ann", "Bob", "Cleo", "Dan", "Eve"];
+const LAST_NAMES: [&str; 4] = ["Smith", "Jones", "Brown", "Miller"];
+
+// A name and an email, like the rows of the persist tests.
+fn text(i: u32) -> String {
+    let first = FIRST_NAMES[i as usize % FIRST_NAMES.len()];
+    let last = LAST_NAMES[i as usize / FIRST_NAMES.len() % LAST_NAMES.len()];
+
+    format!("{} {} {}.{}{}@mail.com", first, last, first, last, i)
+}
+
+fn row_id(i: u32) -> RowId {
+    RowId::new(i as u64 / 50, (i % 50) as u16)
+}
+
+fn search(index: &FullTextIndex, query: &str) -> Vec<RowId> {
+    index.search(&Query::parse(query)).unwrap()
+}
+
+fn rows(filter: impl Fn(u32) -> bool) -> Vec<RowId> {
+    (0..1000).filter(|&i| filter(i)).map(row_id).collect()
+}
+
+#[test]
+fn test_tokenize_and_parse() {
+    assert_eq!(
+        tokenize("Ann Smith, ann.SMITH7@mail.com").collect::<Vec<_>>(),
+        vec![
+            (0, "ann".to_owned()),
+            (1, "smith".to_owned()),
+            (2, "ann".to_owned()),
+            (3, "smith7".to_owned()),
+            (4, "mail".to_owned()),
+            (5, "com".to_owned()),
+        ]
+    );
+    assert_eq!(tokenize(" -- ").count(), 0);
+    assert_eq!(tokenize(&"x".repeat(100)).next().unwrap().1.len(), 64);
+
+    let term = |term: &str| Query::Term(term.to_owned());
+    assert_eq!(Query::parse("Ann"), term("ann"));
+    assert_eq!(
+        Query::parse("ann smith OR bob"),
+        Query::Or(vec![
+            Query::And(vec![term("ann"), term("smith")]),
+            term("bob")
+        ])
+    );
+    assert_eq!(
+        Query::parse("\"Ann Smith\" bob@mail.com"),
+        Query::And(vec![
+            Query::Phrase(vec!["ann".to_owned(), "smith".to_owned()]),
+            Query::Phrase(vec!["bob".to_owned(), "mail".to_owned(), "com".to_owned()]),
+        ])
+    );
+    assert_eq!(Query::parse("OR ann OR"), term("ann"));
+    assert_eq!(Query::parse(""), Query::And(vec![]));
+}
+
+#[test]
+fn test_queries_and_updates() {
+    let _ = fs::remove_file("./01_full_text");
+
+    let pool = BufferPool::new(1024, PAGE_SIZE);
+    let index = FullTextIndex::open(&pool, 0, ".", "01_full_text").unwrap();
+
+    for i in 0..1000 {
+        index.insert(row_id(i), &text(i)).unwrap();
+    }
+
+    // Names repeat every 20 rows, numbers of emails don't.
+    let ann_smith = |i: u32| i.is_multiple_of(20);
+    assert_eq!(search(&index, "ann"), rows(|i| i.is_multiple_of(5)));
+    assert_eq!(search(&index, "ANN smith"), rows(ann_smith));
+    assert_eq!(
+        search(&index, "ann smith OR bob"),
+        rows(|i| ann_smith(i) || i % 5 == 1)
+    );
+    assert_eq!(search(&index, "cleo.smith42@mail.com"), rows(|i| i == 42));
+    assert_eq!(search(&index, "cleo.smith43@mail.com"), vec![]);
+    assert_eq!(search(&index, "nobody OR"), vec![]);
+    assert_eq!(search(&index, ""), vec![]);
+
+    // A phrase needs its terms next to each other and in order.
+    assert_eq!(search(&index, "\"ann smith\""), rows(ann_smith));
+    assert_eq!(search(&index, "\"com mail\""), vec![]);
+    assert_eq!(search(&index, "\"ann mail\""), vec![]);
+
+    let changed = rows(ann_smith)[0];
+    index.delete(changed, &text(0)).unwrap();
+    index.insert(changed, "Zoe Smith").unwrap();
+    assert_eq!(
+        search(&index, "ann smith"),
+        rows(|i| i != 0 && ann_smith(i))
+    );
+    assert_eq!(search(&index, "\"zoe smith\""), vec![changed]);
+
+    for i in (0..1000u32).filter(|i| i % 2 == 1) {
+        index.delete(row_id(i), &text(i)).unwrap();
+    }
+    assert_eq!(
+        search(&index, "bob OR dan"),
+        rows(|i| i % 10 == 6 || i % 10 == 8)
+    );
+    assert_eq!(search(&index, "mail com").len(), 499);
+
+    fs::remove_file("./01_full_text").unwrap();
+}
+
+#[test]
+fn test_reopen() {
+    let _ = fs::remove_file("./02_full_text_reopen");
+
+    {
+        let pool = BufferPool::new(1024, PAGE_SIZE);
+        let index = FullTextIndex::open(&pool, 0, ".", "02_full_text_reopen").unwrap();
+        for i in 0..200 {
+            index.insert(row_id(i), &text(i)).unwrap();
+        }
+    }
+
+    let pool = BufferPool::new(1024, PAGE_SIZE);
+    let index = FullTextIndex::open(&pool, 3, ".", "02_full_text_reopen").unwrap();
+    assert_eq!(index.relation_id(), 3);
+    // Eve is every fifth row, Jones every second group of five names.
+    assert_eq!(
+        search(&index, "\"eve jones\""),
+        (9..200).step_by(20).map(row_id).collect::<Vec<_>>()
+    );
+
+    fs::remove_file("./02_full_text_reopen").unwrap();
+}
@@ -0,0 +1,182 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    include!("../src/util/mod.rs");
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+mod page {
+    include!("../src/page.rs");
+}
+
+mod persist {
+    include!("../src/persist/mod.rs");
+}
+
+mod buffer_pool {
+    include!("../src/buffer_pool/mod.rs");
+}
+
+mod free_space_map {
+    include!("../src/free_space_map.rs");
+}
+
+mod zone_map {
+    include!("../src/zone_map.rs");
+}
+
+mod bloom_filter {
+    include!("../src/bloom_filter.rs");
+}
+
+mod heap_file {
+    include!("../src/heap_file.rs");
+}
+
+mod index {
+    include!("../src/index/mod.rs");
+}
+
+use std::fs;
+
+use buffer_pool::buffer_pool::BufferPool;
+use heap_file::RowId;
+use index::hash_index::{HashIndex, HashIndexError};
+
+const PAGE_SIZE: usize = 1024 * 4;
+
+fn key(i: u32) -> Vec<u8> {
+    format!("user-{}", i).into_bytes()
+}
+
+fn row_id(i: u32) -> RowId {
+    RowId::new(i as u64 / 100, (i % 100) as u16)
+}
+
+#[test]
+fn test_insert_lookup_and_split() {
+    let _ = fs::remove_file("./01_hash_insert");
+
+    let pool = BufferPool::new(1024, PAGE_SIZE);
+    let index = HashIndex::open(&pool, 0, ".", "01_hash_insert").unwrap();
+    assert_eq!(index.global_depth().unwrap(), 0);
+    assert_eq!(index.get(&key(1)).unwrap(), vec![]);
+
+    for i in 0..5000 {
+        index.insert(&key(i), row_id(i)).unwrap();
+    }
+    assert!(index.global_depth().unwrap() >= 4);
+
+    for i in 0..5000 {
+        assert_eq!(index.get(&key(i)).unwrap(), vec![row_id(i)]);
+    }
+    assert_eq!(index.get(&key(5000)).unwrap(), vec![]);
+
+    assert!(matches!(
+        index.insert(&key(7), row_id(7)),
+        Err(HashIndexError::DuplicateEntry)
+    ));
+    let long = vec![b'a'; index.max_key_length() + 1];
+    assert!(matches!(
+        index.insert(&long, row_id(0)),
+        Err(HashIndexError::KeyTooLong(_))
+    ));
+
+    fs::remove_file("./01_hash_insert").unwrap();
+}
+
+#[test]
+fn test_overflow_pages() {
+    let _ = fs::remove_file("./02_hash_overflow");
+
+    let pool = BufferPool::new(1024, PAGE_SIZE);
+    let index = HashIndex::open(&pool, 0, ".", "02_hash_overflow").unwrap();
+
+    // Entries of one key cannot be split apart, they fill a chain of overflow pages.
+    for i in 0..2000 {
+        index.insert(b"same", row_id(i)).unwrap();
+        index.insert(&key(i), row_id(i)).unwrap();
+    }
+    let mut rows = index.get(b"same").unwrap();
+    rows.sort();
+    assert_eq!(rows, (0..2000).map(row_id).collect::<Vec<_>>());
+
+    for i in 0..2000 {
+        assert!(index.delete(b"same", row_id(i)).unwrap());
+    }
+    assert!(!index.delete(b"same", row_id(0)).unwrap());
+    assert_eq!(index.get(b"same").unwrap(), vec![]);
+    assert_eq!(index.get(&key(1999)).unwrap(), vec![row_id(1999)]);
+
+    // Emptied overflow pages are reused.
+    let page_count = index.page_count();
+    for i in 0..2000 {
+        index.insert(b"same", row_id(i)).unwrap();
+    }
+    assert_eq!(index.page_count(), page_count);
+
+    fs::remove_file("./02_hash_overflow").unwrap();
+}
+
+#[test]
+fn test_reopen() {
+    let _ = fs::remove_file("./03_hash_reopen");
+
+    let global_depth = {
+        let pool = BufferPool::new(1024, PAGE_SIZE);
+        let index = HashIndex::open(&pool, 0, ".", "03_hash_reopen").unwrap();
+
+        for i in 0..5000 {
+            index.insert(&key(i), row_id(i)).unwrap();
+        }
+        for i in (0..5000).step_by(2) {
+            assert!(index.delete(&key(i), row_id(i)).unwrap());
+        }
+
+        index.global_depth().unwrap()
+    };
+
+    let pool = BufferPool::new(1024, PAGE_SIZE);
+    let index = HashIndex::open(&pool, 3, ".", "03_hash_reopen").unwrap();
+    assert_eq!(index.global_depth().unwrap(), global_depth);
+
+    for i in 0..5000 {
+        let expected = if i % 2 == 0 { vec![] } else { vec![row_id(i)] };
+        assert_eq!(index.get(&key(i)).unwrap(), expected);
+    }
+
+    fs::remove_file("./03_hash_reopen").unwrap();
+}
+
+#[test]
+fn test_concurrent_inserts_and_lookups() {
+    let _ = fs::remove_file("./04_hash_concurrent");
+
+    let pool = BufferPool::new(1024, PAGE_SIZE);
+    let index = HashIndex::open(&pool, 0, ".", "04_hash_concurrent").unwrap();
+
+    std::thread::scope(|s| {
+        for thread in 0..4 {
+            let index = &index;
+            s.spawn(move || {
+                for i in (0..5000).filter(|i| i % 4 == thread) {
+                    index.insert(&key(i), row_id(i)).unwrap();
+                    assert_eq!(index.get(&key(i)).unwrap(), vec![row_id(i)]);
+                }
+            });
+        }
+    });
+
+    for i in 0..5000 {
+        assert_eq!(index.get(&key(i)).unwrap(), vec![row_id(i)]);
+    }
+
+    fs::remove_file("./04_hash_concurrent").unwrap();
+}
@@ -0,0 +1,290 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    include!("../src/util/mod.rs");
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+mod page {
+    include!("../src/page.rs");
+}
+
+mod persist {
+    include!("../src/persist/mod.rs");
+}
+
+mod buffer_pool {
+    include!("../src/buffer_pool/mod.rs");
+}
+
+mod free_space_map {
+    include!("../src/free_space_map.rs");
+}
+
+mod zone_map {
+    include!("../src/zone_map.rs");
+}
+
+mod bloom_filter {
+    include!("../src/bloom_filter.rs");
+}
+
+mod heap_file {
+    include!("../src/heap_file.rs");
+}
+
+use std::fs;
+use std::sync::LazyLock;
+
+use buffer_pool::buffer_pool::{BufferPool, GetPageError};
+use heap_file::{HeapFile, HeapFileError, RowId};
+use page::PageKey;
+use schema::Schema;
+use tuple::{Tuple, TupleValue};
+
+static SCHEMA: LazyLock<Schema> =
+    LazyLock::new(|| Schema::parse(&[("id", "integer"), ("name", "varchar")]).unwrap());
+
+fn row(id: i32, name: &str) -> Tuple<'static> {
+    Tuple {
+        schema: &SCHEMA,
+        values: vec![
+            TupleValue::Integer(id),
+            TupleValue::Varchar(name.to_owned()),
+        ],
+    }
+}
+
+fn remove_files(filename: &str) {
+    let _ = fs::remove_file(format!("./{}", filename));
+    let _ = fs::remove_file(format!("./{}.fsm", filename));
+}
+
+#[test]
+fn test_insert_get_update_delete() {
+    remove_files("01_heap_crud");
+
+    {
+        let pool = BufferPool::new(16, 1024 * 4);
+        let heap = HeapFile::open(&pool, 0, ".", "01_heap_crud", &SCHEMA).unwrap();
+
+        let first = heap.insert(&row(1, "first")).unwrap();
+        let second = heap.insert(&row(2, "second")).unwrap();
+        assert_eq!(first, RowId::new(0, 0));
+        assert_eq!(second, RowId::new(0, 1));
+
+        assert_eq!(heap.get(first).unwrap(), row(1, "first"));
+        assert_eq!(heap.get(second).unwrap(), row(2, "second"));
+
+        let updated = heap.update(first, &row(1, "first, but longer")).unwrap();
+        assert_eq!(updated, first);
+        assert_eq!(heap.get(first).unwrap(), row(1, "first, but longer"));
+        assert_eq!(heap.get(second).unwrap(), row(2, "second"));
+
+        heap.delete(second).unwrap();
+        assert!(matches!(
+            heap.get(second),
+            Err(HeapFileError::TupleNotFound(_))
+        ));
+        assert!(matches!(
+            heap.delete(second),
+            Err(HeapFileError::TupleNotFound(_))
+        ));
+        assert!(matches!(
+            heap.get(RowId::new(10, 0)),
+            Err(HeapFileError::TupleNotFound(_))
+        ));
+
+        let too_big = row(3, &"x".repeat(1024 * 4));
+        assert!(matches!(
+            heap.insert(&too_big),
+            Err(HeapFileError::TupleTooBig(_))
+        ));
+
+        heap.flush().unwrap();
+    }
+
+    {
+        let pool = BufferPool::new(16, 1024 * 4);
+        let heap = HeapFile::open(&pool, 0, ".", "01_heap_crud", &SCHEMA).unwrap();
+
+        let rows = heap.scan().map(|r| r.unwrap()).collect::<Vec<_>>();
+        assert_eq!(rows, vec![(RowId::new(0, 0), row(1, "first, but longer"))]);
+    }
+
+    remove_files("01_heap_crud");
+}
+
+#[test]
+fn test_update_moves_row() {
+    remove_files("02_heap_move");
+
+    let pool = BufferPool::new(16, 1024 * 4);
+    let heap = HeapFile::open(&pool, 0, ".", "02_heap_move", &SCHEMA).unwrap();
+
+    let small = heap.insert(&row(1, "small")).unwrap();
+    let filler = heap.insert(&row(2, &"f".repeat(3000))).unwrap();
+
+    let moved = heap.update(small, &row(1, &"m".repeat(2000))).unwrap();
+    assert_ne!(moved, small);
+    assert_eq!(moved.page_id, 1);
+    assert_eq!(heap.get(moved).unwrap(), row(1, &"m".repeat(2000)));
+    assert!(heap.get(small).is_err());
+    assert_eq!(heap.get(filler).unwrap(), row(2, &"f".repeat(3000)));
+
+    // Space of the moved row is reused by the next insert.
+    let reused = heap.insert(&row(3, &"r".repeat(500))).unwrap();
+    assert_eq!(reused.page_id, 0);
+
+    remove_files("02_heap_move");
+}
+
+#[test]
+fn test_concurrent_inserts_and_scan() {
+    remove_files("03_heap_concurrent");
+
+    {
+        let pool = BufferPool::new(64, 1024 * 8);
+        let heap = HeapFile::open(&pool, 0, ".", "03_heap_concurrent", &SCHEMA).unwrap();
+        let heap_ref = &heap;
+
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                s.spawn(move || {
+                    for i in 0..500 {
+                        let id = t * 500 + i;
+                        let row_id = heap_ref.insert(&row(id, &format!("name {}", id))).unwrap();
+
+                        assert_eq!(
+                            heap_ref.get(row_id).unwrap(),
+                            row(id, &format!("name {}", id))
+                        );
+                    }
+                });
+            }
+        });
+
+        let even_rows = heap
+            .scan()
+            .map(|r| r.unwrap())
+            .filter(|(_, tuple)| matches!(tuple.values[0], TupleValue::Integer(id) if id % 2 == 0))
+            .map(|(row_id, _)| row_id)
+            .collect::<Vec<_>>();
+
+        for row_id in even_rows {
+            heap.delete(row_id).unwrap();
+        }
+    }
+
+    {
+        // No flush: the free space map is rebuilt from the pages.
+        let pool = BufferPool::new(64, 1024 * 8);
+        let heap = HeapFile::open(&pool, 0, ".", "03_heap_concurrent", &SCHEMA).unwrap();
+
+        let mut ids = heap
+            .scan()
+            .map(|r| match r.unwrap().1.values[0] {
+                TupleValue::Integer(id) => id,
+                _ => panic!("Unexpected value"),
+            })
+            .collect::<Vec<_>>();
+        ids.sort();
+
+        assert_eq!(ids, (0..2000).filter(|id| id % 2 == 1).collect::<Vec<_>>());
+
+        let row_id = heap.insert(&row(-1, "after reopen")).unwrap();
+        assert_eq!(row_id.page_id, 0);
+    }
+
+    remove_files("03_heap_concurrent");
+}
+
+#[test]
+fn test_heap_files_share_pool() {
+    remove_files("04_heap_shared_a");
+    remove_files("04_heap_shared_b");
+
+    let pool = BufferPool::new(16, 1024 * 4);
+    let first = HeapFile::open(&pool, 1, ".", "04_heap_shared_a", &SCHEMA).unwrap();
+    let second = HeapFile::open(&pool, 2, ".", "04_heap_shared_b", &SCHEMA).unwrap();
+
+    assert!(matches!(
+        HeapFile::open(&pool, 1, ".", "04_heap_shared_b", &SCHEMA),
+        Err(HeapFileError::Relation(_))
+    ));
+
+    let a = first.insert(&row(1, "a")).unwrap();
+    let b = second.insert(&row(2, "b")).unwrap();
+    assert_eq!(a, b);
+
+    assert_eq!(first.get(a).unwrap(), row(1, "a"));
+    assert_eq!(second.get(b).unwrap(), row(2, "b"));
+
+    remove_files("04_heap_shared_a");
+    remove_files("04_heap_shared_b");
+}
+
+#[test]
+fn test_prefetch() {
+    remove_files("05_heap_prefetch");
+
+    {
+        let pool = BufferPool::new(16, 1024 * 4);
+        let heap = HeapFile::open(&pool, 0, ".", "05_heap_prefetch", &SCHEMA).unwrap();
+
+        for id in 0..10 {
+            heap.insert(&row(id, &"p".repeat(3000))).unwrap();
+        }
+        assert_eq!(heap.page_count(), 10);
+
+        heap.flush().unwrap();
+    }
+
+    {
+        let pool = BufferPool::new(16, 1024 * 4);
+        let heap = HeapFile::open(&pool, 0, ".", "05_heap_prefetch", &SCHEMA).unwrap();
+
+        // Keys may come in any order and more than once.
+        let mut keys = (0..10)
+            .rev()
+            .map(|page_id| PageKey::new(0, page_id))
+            .collect::<Vec<_>>();
+        keys.push(PageKey::new(0, 3));
+        pool.prefetch(&keys).unwrap();
+        pool.prefetch(&keys).unwrap();
+
+        assert!(matches!(
+            pool.prefetch(&[PageKey::new(7, 0)]),
+            Err(GetPageError::UnknownRelation(7))
+        ));
+
+        // A page past the end of the file fails the batch, its frame is not
+        // found under its key afterwards.
+        let keys = [PageKey::new(0, 2), PageKey::new(0, 20)];
+        assert!(matches!(
+            pool.prefetch(&keys),
+            Err(GetPageError::FailedToReadFromDisk)
+        ));
+        assert!(matches!(
+            pool.get(PageKey::new(0, 20)),
+            Err(GetPageError::FailedToReadFromDisk)
+        ));
+
+        let rows = heap.scan().map(|r| r.unwrap()).collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            (0..10)
+                .map(|id| (RowId::new(id as u64, 0), row(id, &"p".repeat(3000))))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    remove_files("05_heap_prefetch");
+}
@@ -0,0 +1,237 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    pub mod type_converter {
+        include!("../src/util/type_converter.rs");
+    }
+
+    pub mod temporal {
+        include!("../src/util/temporal.rs");
+    }
+
+    pub mod decimal {
+        include!("../src/util/decimal.rs");
+    }
+
+    pub mod uuid {
+        include!("../src/util/uuid.rs");
+    }
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+use schema::{DataType, Schema};
+use tuple::{KeyColumn, Tuple, TupleDecodeError, TupleValue, decode_key, encode_key};
+use util::decimal::Decimal;
+use util::temporal::{Date, Interval};
+
+// Keys of the values sorted bytewise are in the order of the values.
+fn assert_sorted(column: KeyColumn, values: Vec<TupleValue>) {
+    let keys = values
+        .iter()
+        .map(|v| encode_key(&[column], std::slice::from_ref(v)).unwrap())
+        .collect::<Vec<_>>();
+
+    for (i, pair) in keys.windows(2).enumerate() {
+        assert!(pair[0] < pair[1], "{:?} < {:?}", values[i], values[i + 1]);
+    }
+
+    for (key, value) in keys.iter().zip(&values) {
+        let decoded = decode_key(&[column], key).unwrap();
+        assert!(decoded[0] == *value || value.is_null() && decoded[0].is_null());
+    }
+}
+
+#[test]
+fn test_numbers_sort_bytewise() {
+    assert_sorted(
+        KeyColumn::new(DataType::Integer),
+        [i32::MIN, -1000, -1, 0, 1, 255, 256, i32::MAX]
+            .map(TupleValue::Integer)
+            .into(),
+    );
+    assert_sorted(
+        KeyColumn::new(DataType::SmallInt),
+        [i16::MIN, -1, 0, 1, i16::MAX]
+            .map(TupleValue::SmallInt)
+            .into(),
+    );
+    assert_sorted(
+        KeyColumn::new(DataType::BigInt),
+        [i64::MIN, -1, 0, i64::MAX].map(TupleValue::BigInt).into(),
+    );
+    assert_sorted(
+        KeyColumn::new(DataType::Double),
+        [
+            f64::NEG_INFINITY,
+            -1.5,
+            -f64::MIN_POSITIVE,
+            0.0,
+            1e-300,
+            2.0,
+            f64::INFINITY,
+            f64::NAN,
+        ]
+        .map(TupleValue::Double)
+        .into(),
+    );
+    assert_sorted(
+        KeyColumn::new(DataType::Real),
+        [f32::MIN, -0.5, 0.0, 0.5, f32::MAX, f32::NAN]
+            .map(TupleValue::Real)
+            .into(),
+    );
+    assert_sorted(
+        KeyColumn::new(DataType::Date),
+        [-1, 0, 1].map(|d| TupleValue::Date(Date(d))).into(),
+    );
+
+    let decimal = KeyColumn::new(DataType::Decimal {
+        precision: 10,
+        scale: 2,
+    });
+    assert_sorted(
+        decimal,
+        ["-100", "-0.01", "0", "0.5", "1.25", "99999999.99"]
+            .map(|d| TupleValue::Decimal(Decimal::parse(d).unwrap()))
+            .into(),
+    );
+    assert_eq!(
+        encode_key(
+            &[decimal],
+            &[TupleValue::Decimal(Decimal::parse("1.5").unwrap())]
+        )
+        .unwrap(),
+        encode_key(
+            &[decimal],
+            &[TupleValue::Decimal(Decimal::parse("1.50").unwrap())]
+        )
+        .unwrap()
+    );
+
+    let double = KeyColumn::new(DataType::Double);
+    assert_eq!(
+        encode_key(&[double], &[TupleValue::Double(-0.0)]).unwrap(),
+        encode_key(&[double], &[TupleValue::Double(0.0)]).unwrap()
+    );
+
+    assert_sorted(
+        KeyColumn::new(DataType::Interval),
+        ["-P1D", "PT1H", "P1D", "P1M", "P1Y"]
+            .map(|i| TupleValue::Interval(Interval::parse(i).unwrap()))
+            .into(),
+    );
+}
+
+#[test]
+fn test_strings_sort_bytewise() {
+    assert_sorted(
+        KeyColumn::new(DataType::Varchar),
+        ["", "\0", "\0\0", "a", "a\0", "a\0b", "ab", "b", "é"]
+            .map(|s| TupleValue::Varchar(s.to_owned()))
+            .into(),
+    );
+    assert_sorted(
+        KeyColumn::new(DataType::Bytea),
+        [vec![], vec![0], vec![0, 0xff], vec![1], vec![0xff, 0]]
+            .map(TupleValue::Bytea)
+            .into(),
+    );
+
+    // Padding of char values is insignificant.
+    let char = KeyColumn::new(DataType::Char { length: 3 });
+    let key = encode_key(&[char], &[TupleValue::Char("ab".to_owned())]).unwrap();
+    assert_eq!(
+        key,
+        encode_key(&[char], &[TupleValue::Char("ab ".to_owned())]).unwrap()
+    );
+    assert!(matches!(&decode_key(&[char], &key).unwrap()[0], TupleValue::Char(c) if c == "ab "));
+}
+
+#[test]
+fn test_nulls_and_descending_columns() {
+    let values = [TupleValue::Integer(-5), TupleValue::Integer(7)];
+
+    assert_sorted(
+        KeyColumn::new(DataType::Integer),
+        vec![values[0].clone(), values[1].clone(), TupleValue::Null],
+    );
+    assert_sorted(
+        KeyColumn::new(DataType::Integer).nulls_first(),
+        vec![TupleValue::Null, values[0].clone(), values[1].clone()],
+    );
+    assert_sorted(
+        KeyColumn::new(DataType::Integer).descending(),
+        vec![TupleValue::Null, values[1].clone(), values[0].clone()],
+    );
+    assert_sorted(
+        KeyColumn::new(DataType::Varchar).descending().nulls_last(),
+        vec![
+            TupleValue::Varchar("b".to_owned()),
+            TupleValue::Varchar("ab".to_owned()),
+            TupleValue::Varchar("a".to_owned()),
+            TupleValue::Null,
+        ],
+    );
+}
+
+#[test]
+fn test_composite_keys() {
+    let schema =
+        Schema::parse(&[("id", "integer"), ("name", "varchar"), ("score", "double")]).unwrap();
+    let columns = [
+        KeyColumn::new(DataType::Varchar),
+        KeyColumn::new(DataType::Double).descending(),
+    ];
+
+    let rows = [
+        ("a", 2.0),
+        ("a", 1.0),
+        ("a\0", 5.0),
+        ("ab", 9.0),
+        ("b", -1.0),
+    ];
+    let keys = rows
+        .iter()
+        .map(|(name, score)| {
+            Tuple {
+                schema: &schema,
+                values: vec![
+                    TupleValue::Integer(0),
+                    TupleValue::Varchar(name.to_string()),
+                    TupleValue::Double(*score),
+                ],
+            }
+            .key(&[1, 2], &columns)
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(
+        decode_key(&columns, &keys[2]).unwrap(),
+        vec![
+            TupleValue::Varchar("a\0".to_owned()),
+            TupleValue::Double(5.0)
+        ]
+    );
+
+    assert_eq!(
+        decode_key(&columns, &keys[0][..keys[0].len() - 1]),
+        Err(TupleDecodeError::TruncatedColumn(1))
+    );
+    assert_eq!(
+        decode_key(&columns, &[keys[0].as_slice(), &[0]].concat()),
+        Err(TupleDecodeError::BadLength(keys[0].len() + 1))
+    );
+    assert_eq!(
+        decode_key(&columns[..1], &[1, b'a', 0, 7]),
+        Err(TupleDecodeError::BadEncoding(0))
+    );
+}
@@ -0,0 +1,361 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    include!("../src/util/mod.rs");
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+mod page {
+    include!("../src/page.rs");
+}
+
+mod persist {
+    include!("../src/persist/mod.rs");
+}
+
+mod buffer_pool {
+    include!("../src/buffer_pool/mod.rs");
+}
+
+mod free_space_map {
+    include!("../src/free_space_map.rs");
+}
+
+mod zone_map {
+    include!("../src/zone_map.rs");
+}
+
+mod bloom_filter {
+    include!("../src/bloom_filter.rs");
+}
+
+mod heap_file {
+    include!("../src/heap_file.rs");
+}
+
+mod catalog {
+    include!("../src/catalog.rs");
+}
+
+mod index {
+    include!("../src/index/mod.rs");
+}
+
+mod table {
+    include!("../src/table.rs");
+}
+
+mod lsm {
+    include!("../src/lsm/mod.rs");
+}
+
+use std::fs;
+use std::ops::Bound;
+use std::sync::LazyLock;
+
+use buffer_pool::buffer_pool::BufferPool;
+use catalog::{Catalog, Engine};
+use lsm::{Compaction, LsmError, LsmOptions, LsmTree};
+use page::Page;
+use persist::{IoBackend, relation::RelationFile};
+use schema::Schema;
+use table::{Table, TableError};
+use tuple::{Tuple, TupleValue};
+
+static SCHEMA: LazyLock<Schema> =
+    LazyLock::new(|| Schema::parse(&[("id", "integer not null"), ("name", "varchar")]).unwrap());
+
+fn row(id: i32, name: &str) -> Tuple<'static> {
+    Tuple {
+        schema: &SCHEMA,
+        values: vec![
+            TupleValue::Integer(id),
+            TupleValue::Varchar(name.to_owned()),
+        ],
+    }
+}
+
+fn options(compaction: Compaction) -> LsmOptions {
+    LsmOptions {
+        page_size: 1024 * 4,
+        memtable_size: 16 * 1024,
+        false_positive_rate: 0.01,
+        compaction,
+    }
+}
+
+fn name(tree: &LsmTree, id: i32) -> Option<String> {
+    tree.get(&[TupleValue::Integer(id)])
+        .unwrap()
+        .map(|tuple| match &tuple.values[1] {
+            TupleValue::Varchar(name) => name.clone(),
+            value => panic!("Unexpected name {:?}", value),
+        })
+}
+
+fn ids(tree: &LsmTree, start: Bound<&[TupleValue]>, end: Bound<&[TupleValue]>) -> Vec<i32> {
+    tree.scan(start, end)
+        .unwrap()
+        .map(|tuple| match tuple.unwrap().values[0] {
+            TupleValue::Integer(id) => id,
+            ref value => panic!("Unexpected id {:?}", value),
+        })
+        .collect()
+}
+
+#[test]
+fn test_leveled_compaction() {
+    let path = "./01_lsm_leveled";
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+
+    let compaction = Compaction::Leveled {
+        level0_runs: 2,
+        size_ratio: 2,
+    };
+
+    {
+        let tree = LsmTree::open(path, "rows", &SCHEMA, &[0], options(compaction.clone())).unwrap();
+
+        // Ids are written out of order, runs are sorted anyway.
+        for i in 0..3000 {
+            let id = (i * 7) % 3000;
+            tree.put(&row(id, &format!("row {:05}", id))).unwrap();
+        }
+        for id in (0..3000).filter(|id| id % 3 == 0) {
+            tree.put(&row(id, "updated")).unwrap();
+        }
+        for id in (0..3000).filter(|id| id % 5 == 0) {
+            tree.delete(&[TupleValue::Integer(id)]).unwrap();
+        }
+
+        // Runs were compacted into levels past level 0 as they piled up.
+        let run_counts = tree.run_counts();
+        assert!(run_counts.len() > 2);
+        assert!(run_counts[0] < 2);
+        assert!(run_counts[1..].iter().all(|&count| count <= 1));
+
+        assert_eq!(name(&tree, 1), Some("row 00001".to_owned()));
+        assert_eq!(name(&tree, 3), Some("updated".to_owned()));
+        assert_eq!(name(&tree, 10), None);
+        assert_eq!(name(&tree, 5000), None);
+
+        tree.flush().unwrap();
+    }
+
+    let tree = LsmTree::open(path, "rows", &SCHEMA, &[0], options(compaction)).unwrap();
+
+    let expected = (0..3000).filter(|id| id % 5 != 0).collect::<Vec<_>>();
+    assert_eq!(ids(&tree, Bound::Unbounded, Bound::Unbounded), expected);
+    assert_eq!(name(&tree, 2997), Some("updated".to_owned()));
+    assert_eq!(name(&tree, 2998), Some("row 02998".to_owned()));
+
+    // A deleted key comes back with a new row.
+    tree.put(&row(10, "again")).unwrap();
+    assert_eq!(name(&tree, 10), Some("again".to_owned()));
+
+    // Only the files of live runs are kept.
+    let run_files = fs::read_dir(path)
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_str().unwrap().ends_with(".sst")
+        })
+        .count();
+    assert_eq!(run_files, tree.run_counts().iter().sum::<usize>());
+
+    fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_tiered_compaction_and_range_scans() {
+    let path = "./02_lsm_tiered";
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+
+    let tree = LsmTree::open(
+        path,
+        "rows",
+        &SCHEMA,
+        &[0],
+        options(Compaction::Tiered { runs_per_level: 3 }),
+    )
+    .unwrap();
+
+    for id in 0..4000 {
+        tree.put(&row(id, &format!("{:0100}", id))).unwrap();
+    }
+    for id in 1000..2000 {
+        tree.delete(&[TupleValue::Integer(id)]).unwrap();
+    }
+
+    let run_counts = tree.run_counts();
+    assert!(run_counts.len() > 1);
+    assert!(run_counts.iter().all(|&count| count < 3));
+
+    let (start, end) = ([TupleValue::Integer(900)], [TupleValue::Integer(2100)]);
+    assert_eq!(
+        ids(&tree, Bound::Included(&start), Bound::Excluded(&end)),
+        (900..1000).chain(2000..2100).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        ids(&tree, Bound::Excluded(&end), Bound::Unbounded),
+        (2101..4000).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        ids(&tree, Bound::Excluded(&start), Bound::Included(&start)),
+        Vec::<i32>::new()
+    );
+
+    // A scan keeps reading the runs it started with while they are compacted.
+    let scan = tree.scan(Bound::Unbounded, Bound::Unbounded).unwrap();
+    for id in 4000..8000 {
+        tree.put(&row(id, "new")).unwrap();
+    }
+    assert_eq!(scan.count(), 3000);
+
+    assert!(matches!(
+        tree.put(&row(1, &"x".repeat(5000))),
+        Err(LsmError::RecordTooBig(_))
+    ));
+    assert!(matches!(
+        tree.delete(&[TupleValue::Null]),
+        Err(LsmError::NullKey)
+    ));
+
+    fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_engine_per_table() {
+    let path = "./03_lsm_engine";
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+
+    {
+        let pool = BufferPool::new(64, 1024 * 4);
+        let catalog = Catalog::open(&pool, path).unwrap();
+
+        catalog.create_table("users", &SCHEMA).unwrap();
+        let engine = Engine::lsm(&SCHEMA, &["id"]).unwrap();
+        let events = catalog
+            .create_table_with_engine("events", &SCHEMA, engine)
+            .unwrap();
+        assert_eq!(events.engine, Engine::Lsm { key: vec![0] });
+
+        assert!(Engine::lsm(&SCHEMA, &["missing"]).is_err());
+        assert!(
+            catalog
+                .create_table_with_engine("bad", &SCHEMA, Engine::Lsm { key: vec![2] })
+                .is_err()
+        );
+
+        let tree = LsmTree::open_table(&catalog, &events, LsmOptions::default()).unwrap();
+        tree.put(&row(1, "first")).unwrap();
+        tree.flush().unwrap();
+        catalog.flush().unwrap();
+    }
+
+    let pool = BufferPool::new(64, 1024 * 4);
+    let catalog = Catalog::open(&pool, path).unwrap();
+    let users = catalog.table("users").unwrap().unwrap();
+    let events = catalog.table("events").unwrap().unwrap();
+    assert_eq!(users.engine, Engine::Heap);
+    assert_eq!(events.engine, Engine::Lsm { key: vec![0] });
+
+    assert!(matches!(
+        LsmTree::open_table(&catalog, &users, LsmOptions::default()),
+        Err(LsmError::WrongEngine(_))
+    ));
+    assert!(matches!(
+        Table::open(&pool, &catalog, &events),
+        Err(TableError::WrongEngine(_))
+    ));
+
+    let tree = LsmTree::open_table(&catalog, &events, LsmOptions::default()).unwrap();
+    assert_eq!(name(&tree, 1), Some("first".to_owned()));
+
+    fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_invalid_options_and_corrupted_run() {
+    let path = "./04_lsm_corrupted";
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+
+    let leveled = Compaction::Leveled {
+        level0_runs: 2,
+        size_ratio: 2,
+    };
+    for options in [
+        LsmOptions {
+            page_size: 1000,
+            ..options(leveled.clone())
+        },
+        LsmOptions {
+            false_positive_rate: 1.0,
+            ..options(leveled.clone())
+        },
+        options(Compaction::Leveled {
+            level0_runs: 2,
+            size_ratio: 1,
+        }),
+        options(Compaction::Tiered { runs_per_level: 1 }),
+    ] {
+        assert!(matches!(
+            LsmTree::open(path, "rows", &SCHEMA, &[0], options),
+            Err(LsmError::InvalidOptions(_))
+        ));
+    }
+
+    {
+        let tree = LsmTree::open(path, "rows", &SCHEMA, &[0], options(leveled.clone())).unwrap();
+        tree.put(&row(1, "first")).unwrap();
+        tree.flush().unwrap();
+    }
+
+    // A footer with a negative page count is corrupted, the counts don't overflow.
+    let run = fs::read_dir(path)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .find(|name| name.ends_with(".sst"))
+        .unwrap();
+    let file = RelationFile::open(path, &run, 1024 * 4, IoBackend::Pread).unwrap();
+    let footer_schema = Schema::parse(&[
+        ("data_pages", "bigint not null"),
+        ("index_pages", "bigint not null"),
+        ("bloom_pages", "bigint not null"),
+        ("record_count", "bigint not null"),
+        ("hash_count", "integer not null"),
+    ])
+    .unwrap();
+    let mut footer = Page::with_size(file.page_count() - 1, 1024 * 4);
+    footer
+        .write(&Tuple {
+            schema: &footer_schema,
+            values: vec![
+                TupleValue::BigInt(-1),
+                TupleValue::BigInt(1),
+                TupleValue::BigInt(1),
+                TupleValue::BigInt(1),
+                TupleValue::Integer(7),
+            ],
+        })
+        .unwrap();
+    file.write_page(&footer).unwrap();
+
+    assert!(matches!(
+        LsmTree::open(path, "rows", &SCHEMA, &[0], options(leveled)),
+        Err(LsmError::Corrupted(_))
+    ));
+
+    fs::remove_dir_all(path).unwrap();
+}
@@ -1,7 +1,21 @@
+#![cfg_attr(test, allow(dead_code))]
+
 mod util {
     pub mod type_converter {
         include!("../src/util/type_converter.rs");
     }
+
+    pub mod temporal {
+        include!("../src/util/temporal.rs");
+    }
+
+    pub mod decimal {
+        include!("../src/util/decimal.rs");
+    }
+
+    pub mod uuid {
+        include!("../src/util/uuid.rs");
+    }
 }
 
 mod page {
@@ -12,8 +26,13 @@ mod tuple {
     include!("../src/tuple.rs");
 }
 
-use page::Page;
-use tuple::{Tuple, TupleValue};
+mod schema {
+    include!("../src/schema.rs");
+}
+
+use page::{CorruptedPageError, HEADER_SIZE, Page, ReadTupleError, SLOT_SIZE};
+use schema::{Column, DataType, Schema, SchemaError};
+use tuple::{Tuple, TupleDecodeError, TupleRef, TupleRefError, TupleValue};
 
 #[test]
 fn test_create_page() {
@@ -24,40 +43,61 @@ fn test_create_page() {
 
     {
         /* Tuple 0 */
+        let schema = Schema::from_types(&[DataType::Integer, DataType::Varchar]);
         let tuple = Tuple {
-            types: &[ "integer", "varchar" ],
-            values: vec![ TupleValue::Integer(10), TupleValue::Varchar("Hello!".to_owned()) ],
+            schema: &schema,
+            values: vec![
+                TupleValue::Integer(10),
+                TupleValue::Varchar("Hello!".to_owned()),
+            ],
         };
-    
+
         let slot = p.write(&tuple).unwrap();
         assert_eq!(slot.id, 0);
-    
-        let tuple_read = p.read(slot.id, &["integer", "varchar"]).unwrap();
-        assert_eq!(tuple_read, Tuple {
-            types: &[ "integer", "varchar" ],
-            values: vec![TupleValue::Integer(10), TupleValue::Varchar("Hello!".to_owned())],
-        });
+
+        let tuple_read = p.read(slot.id, &schema).unwrap();
+        assert_eq!(
+            tuple_read,
+            Tuple {
+                schema: &schema,
+                values: vec![
+                    TupleValue::Integer(10),
+                    TupleValue::Varchar("Hello!".to_owned())
+                ],
+            }
+        );
     }
 
     {
+        let schema = Schema::from_types(&[DataType::Varchar, DataType::Varchar]);
         let tuple = Tuple {
-            types: &[ "varchar", "varchar" ],
-            values: vec![ TupleValue::Varchar("It's me again".to_owned()), TupleValue::Varchar("lalalala".to_owned()) ],
+            schema: &schema,
+            values: vec![
+                TupleValue::Varchar("It's me again".to_owned()),
+                TupleValue::Varchar("lalalala".to_owned()),
+            ],
         };
 
         let slot = p.write(&tuple).unwrap();
         assert_eq!(slot.id, 1);
 
-        let tuple_read = p.read(slot.id, &["varchar", "varchar"]).unwrap();
-        assert_eq!(tuple_read, Tuple {
-            types: &[ "varchar", "varchar" ],
-            values: vec![TupleValue::Varchar("It's me again".to_owned()), TupleValue::Varchar("lalalala".to_owned())],
-        });
+        let tuple_read = p.read(slot.id, &schema).unwrap();
+        assert_eq!(
+            tuple_read,
+            Tuple {
+                schema: &schema,
+                values: vec![
+                    TupleValue::Varchar("It's me again".to_owned()),
+                    TupleValue::Varchar("lalalala".to_owned())
+                ],
+            }
+        );
     }
 
     {
+        let schema = Schema::from_types(&[DataType::Varchar, DataType::Varchar, DataType::Integer]);
         let tuple = Tuple {
-            types: &[ "varchar", "varchar", "integer" ],
+            schema: &schema,
             values: vec![
                 TupleValue::Varchar("It's me again heeey".to_owned()),
                 TupleValue::Varchar("test test".to_owned()),
@@ -68,13 +108,20 @@ fn test_create_page() {
         let slot = p.write(&tuple).unwrap();
         assert_eq!(slot.id, 2);
 
-        assert_eq!(p.has_space(&tuple).unwrap(), true);
+        assert!(p.has_space(&tuple).unwrap());
 
-        let tuple_read = p.read(slot.id, &["varchar", "varchar", "integer"]).unwrap();
-        assert_eq!(tuple_read, Tuple {
-            types: &[ "varchar", "varchar", "integer" ],
-            values: vec![TupleValue::Varchar("It's me again heeey".to_owned()), TupleValue::Varchar("test test".to_owned()), TupleValue::Integer(25)],
-        });
+        let tuple_read = p.read(slot.id, &schema).unwrap();
+        assert_eq!(
+            tuple_read,
+            Tuple {
+                schema: &schema,
+                values: vec![
+                    TupleValue::Varchar("It's me again heeey".to_owned()),
+                    TupleValue::Varchar("test test".to_owned()),
+                    TupleValue::Integer(25)
+                ],
+            }
+        );
     }
 
     {
@@ -82,13 +129,11 @@ fn test_create_page() {
         let s = String::from_utf8(vec).expect("Invalid UTF-8 string");
 
         let tuple = Tuple {
-            types: &[ "varchar" ],
-            values: vec![
-                TupleValue::Varchar(s),
-            ],
+            schema: &Schema::from_types(&[DataType::Varchar]),
+            values: vec![TupleValue::Varchar(s)],
         };
 
-        assert_eq!(p.has_space(&tuple).unwrap(), false);
+        assert!(!p.has_space(&tuple).unwrap());
     }
 
     {
@@ -96,12 +141,268 @@ fn test_create_page() {
         let s = String::from_utf8(vec).expect("Invalid UTF-8 string");
 
         let tuple = Tuple {
-            types: &[ "varchar" ],
+            schema: &Schema::from_types(&[DataType::Varchar]),
+            values: vec![TupleValue::Varchar(s)],
+        };
+
+        assert!(p.has_space(&tuple).unwrap());
+    }
+}
+
+#[test]
+fn test_page_sizes() {
+    for size in page::SUPPORTED_SIZES {
+        let mut p = Page::with_size(1, size);
+
+        assert_eq!(p.size(), size);
+        assert_eq!(p.free_space, size - 4);
+
+        // No null bitmap, so the tuple fills the page exactly.
+        let schema = Schema::new(vec![Column::new("a", DataType::Varchar).not_null()]).unwrap();
+        let tuple = Tuple {
+            schema: &schema,
+            values: vec![TupleValue::Varchar("a".repeat(size - 4 - 5 - 2))],
+        };
+
+        assert!(p.has_space(&tuple).unwrap());
+        p.write(&tuple).unwrap();
+        assert_eq!(p.free_space, 0);
+
+        let reloaded = Page::from_data(1, p.data.clone()).unwrap();
+        assert_eq!(reloaded.free_space, 0);
+        assert_eq!(reloaded.read(0, &schema).unwrap(), tuple);
+    }
+}
+
+#[test]
+fn test_read_corrupted_page() {
+    let schema =
+        Schema::parse(&[("id", "integer not null"), ("name", "varchar not null")]).unwrap();
+    let mut p = Page::new(1);
+
+    for (id, name) in [(1, "one"), (2, "two"), (3, "three")] {
+        p.write(&Tuple {
+            schema: &schema,
             values: vec![
-                TupleValue::Varchar(s),
+                TupleValue::Integer(id),
+                TupleValue::Varchar(name.to_owned()),
             ],
-        };
+        })
+        .unwrap();
+    }
+
+    // The length of the name of tuple 1, tuple 0 is at the end of the page.
+    let size = p.size();
+    p.data[size - 9 - 9 + 5] = 0xff;
+    // Slot 2 points outside of the page.
+    p.data[HEADER_SIZE + 2 * SLOT_SIZE + 2..HEADER_SIZE + 2 * SLOT_SIZE + 4]
+        .copy_from_slice(&u16::MAX.to_be_bytes());
+
+    let tuples = p.read_iterator(&schema).collect::<Vec<_>>();
+    assert_eq!(tuples.len(), 3);
+    assert_eq!(
+        tuples[0].as_ref().unwrap().values[0],
+        TupleValue::Integer(1)
+    );
+    assert_eq!(
+        tuples[1].as_ref().err(),
+        Some(&TupleDecodeError::TruncatedColumn(1))
+    );
+    assert_eq!(
+        tuples[2].as_ref().err(),
+        Some(&TupleDecodeError::BadLength(u16::MAX as usize))
+    );
+
+    assert!(matches!(
+        p.read(1, &schema),
+        Err(ReadTupleError::TupleDecodeError(
+            TupleDecodeError::TruncatedColumn(1)
+        ))
+    ));
+    assert!(matches!(
+        p.read(5, &schema),
+        Err(ReadTupleError::TupleNotFound)
+    ));
+    assert_eq!(p.read_iterator_raw().count(), 2);
+
+    // Slot lengths or a slot count that don't fit into the page are an error.
+    assert_eq!(
+        Page::from_data(1, p.data.clone()).err(),
+        Some(CorruptedPageError)
+    );
+    p.data[HEADER_SIZE + 2 * SLOT_SIZE + 2..HEADER_SIZE + 2 * SLOT_SIZE + 4]
+        .copy_from_slice(&0u16.to_be_bytes());
+    assert!(Page::from_data(1, p.data.clone()).is_ok());
+
+    p.data[2..4].copy_from_slice(&u16::MAX.to_be_bytes());
+    assert_eq!(p.refresh_metadata(), Err(CorruptedPageError));
+    assert_eq!(p.slots, 0);
+    assert_eq!(p.read_iterator_raw().count(), 0);
+}
+
+#[test]
+fn test_tuple_ref() {
+    let schema = Schema::parse(&[
+        ("id", "integer not null"),
+        ("name", "varchar"),
+        ("code", "char(3)"),
+        ("flag", "boolean"),
+        ("amount", "bigint"),
+        ("blob", "bytea"),
+    ])
+    .unwrap();
+    let mut p = Page::new(1);
+
+    p.write(&Tuple {
+        schema: &schema,
+        values: vec![
+            TupleValue::Integer(7),
+            TupleValue::Varchar("seven".to_owned()),
+            TupleValue::Char("ab".to_owned()),
+            TupleValue::Null,
+            TupleValue::BigInt(-70),
+            TupleValue::Bytea(vec![1, 2]),
+        ],
+    })
+    .unwrap();
+    p.write(&Tuple {
+        schema: &schema,
+        values: vec![
+            TupleValue::Integer(8),
+            TupleValue::Null,
+            TupleValue::Null,
+            TupleValue::Boolean(true),
+            TupleValue::Null,
+            TupleValue::Null,
+        ],
+    })
+    .unwrap();
 
-        assert_eq!(p.has_space(&tuple).unwrap(), true);
+    let tuple = p.read_ref(0, &schema).unwrap();
+    assert_eq!(tuple.get_i32(0), Ok(Some(7)));
+    assert_eq!(tuple.get_str(1), Ok(Some("seven")));
+    assert_eq!(tuple.get_str(2), Ok(Some("ab ")));
+    assert_eq!(tuple.get_bool(3), Ok(None));
+    assert_eq!(tuple.get_i64(4), Ok(Some(-70)));
+    assert_eq!(tuple.get_bytes(5), Ok(Some([1, 2].as_slice())));
+    assert_eq!(tuple.get(4), Ok(TupleValue::BigInt(-70)));
+    assert_eq!(tuple.to_tuple().unwrap(), p.read(0, &schema).unwrap());
+
+    assert_eq!(tuple.get_i64(0), Err(TupleRefError::TypeMismatch(0)));
+    assert_eq!(tuple.get_i32(6), Err(TupleRefError::NoSuchColumn(6)));
+
+    let tuples = p.read_iterator_ref(&schema).collect::<Vec<_>>();
+    assert_eq!(tuples.len(), 2);
+    assert_eq!(tuples[1].get_i32(0), Ok(Some(8)));
+    assert_eq!(tuples[1].is_null(1), Ok(true));
+    assert_eq!(tuples[1].get_bool(3), Ok(Some(true)));
+
+    // Only the columns up to the requested one are decoded.
+    let truncated = TupleRef::new(&schema, &tuples[0].data[..10]);
+    assert_eq!(truncated.get_i32(0), Ok(Some(7)));
+    assert_eq!(
+        truncated.get_str(1),
+        Err(TupleRefError::TupleDecodeError(
+            TupleDecodeError::TruncatedColumn(1)
+        ))
+    );
+}
+
+#[test]
+fn test_read_projected() {
+    let schema = Schema::parse(&[
+        ("id", "integer not null"),
+        ("amount", "bigint not null"),
+        ("flag", "boolean"),
+        ("name", "varchar"),
+        ("score", "double"),
+    ])
+    .unwrap();
+    let mut p = Page::new(1);
+
+    p.write(&Tuple {
+        schema: &schema,
+        values: vec![
+            TupleValue::Integer(1),
+            TupleValue::BigInt(10),
+            TupleValue::Boolean(true),
+            TupleValue::Varchar("one".to_owned()),
+            TupleValue::Double(1.5),
+        ],
+    })
+    .unwrap();
+    p.write(&Tuple {
+        schema: &schema,
+        values: vec![
+            TupleValue::Integer(2),
+            TupleValue::BigInt(20),
+            TupleValue::Null,
+            TupleValue::Null,
+            TupleValue::Double(2.5),
+        ],
+    })
+    .unwrap();
+
+    // Fixed offsets: the columns in front of the projected ones are fixed size and not null.
+    let projected = p
+        .read_iterator_projected(&schema, &[2, 0])
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        projected,
+        vec![
+            vec![TupleValue::Boolean(true), TupleValue::Integer(1)],
+            vec![TupleValue::Null, TupleValue::Integer(2)],
+        ]
+    );
+
+    // Columns after a varchar and a nullable column are found by skipping them.
+    let projected = p
+        .read_iterator_projected(&schema, &[4, 3, 4])
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        projected,
+        vec![
+            vec![
+                TupleValue::Double(1.5),
+                TupleValue::Varchar("one".to_owned()),
+                TupleValue::Double(1.5)
+            ],
+            vec![
+                TupleValue::Double(2.5),
+                TupleValue::Null,
+                TupleValue::Double(2.5)
+            ],
+        ]
+    );
+
+    for columns in [vec![0, 1], vec![1, 3], vec![]] {
+        let projected = p
+            .read_iterator_projected(&schema, &columns)
+            .unwrap()
+            .map(|v| v.unwrap())
+            .collect::<Vec<_>>();
+        let full = p
+            .read_iterator(&schema)
+            .map(|t| t.unwrap())
+            .collect::<Vec<_>>();
+
+        for (values, tuple) in projected.iter().zip(&full) {
+            assert_eq!(
+                values,
+                &columns
+                    .iter()
+                    .map(|c| tuple.values[*c].clone())
+                    .collect::<Vec<_>>()
+            );
+        }
     }
+
+    assert!(matches!(
+        p.read_iterator_projected(&schema, &[0, 5]),
+        Err(SchemaError::ColumnNotFound(5))
+    ));
 }
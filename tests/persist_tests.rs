@@ -1,7 +1,21 @@
+#![cfg_attr(test, allow(dead_code))]
+
 mod util {
     pub mod type_converter {
         include!("../src/util/type_converter.rs");
     }
+
+    pub mod temporal {
+        include!("../src/util/temporal.rs");
+    }
+
+    pub mod decimal {
+        include!("../src/util/decimal.rs");
+    }
+
+    pub mod uuid {
+        include!("../src/util/uuid.rs");
+    }
 }
 
 mod page {
@@ -12,15 +26,20 @@ mod tuple {
     include!("../src/tuple.rs");
 }
 
+mod schema {
+    include!("../src/schema.rs");
+}
+
 mod persist {
-    include!("../src/persist.rs");
+    include!("../src/persist/mod.rs");
 }
 
 use std::fs;
 
 use fake::{Fake, faker::internet::en::FreeEmail, faker::name::en::Name, rand::random};
 use page::Page;
-use persist::{Reader, Writer};
+use persist::{IoBackend, MmapReader, Reader, Writer, relation::RelationFile};
+use schema::{DataType, Schema};
 use tuple::{Tuple, TupleValue};
 
 #[test]
@@ -35,7 +54,7 @@ fn test_persist_single_page() {
         email.truncate(i8::MAX as usize - 1);
 
         let tuple = Tuple {
-            types: &["integer", "varchar", "varchar"],
+            schema: &Schema::from_types(&[DataType::Integer, DataType::Varchar, DataType::Varchar]),
             values: vec![
                 TupleValue::Integer(random::<i32>()),
                 TupleValue::Varchar(name),
@@ -55,14 +74,12 @@ fn test_persist_single_page() {
         p.slots, p.free_space
     );
 
-    match fs::remove_file("./01_single_page") {
-        _ => {}
-    };
+    let _ = fs::remove_file("./01_single_page");
 
-    let writer = Writer::new(".", "01_single_page");
+    let writer = Writer::new(".", "01_single_page").unwrap();
     writer.insert_page(&p).unwrap();
 
-    let reader = Reader::new(".", "01_single_page");
+    let reader = Reader::new(".", "01_single_page").unwrap();
     let mut page = Page::new(0);
 
     reader.read_page(0, &mut page).unwrap();
@@ -71,3 +88,221 @@ fn test_persist_single_page() {
 
     fs::remove_file("./01_single_page").unwrap();
 }
+
+fn batch_pages() -> Vec<Page> {
+    (0..100)
+        .map(|page_id| {
+            let mut p = Page::new(page_id);
+            p.write(&Tuple {
+                schema: &Schema::from_types(&[DataType::Integer, DataType::Varchar]),
+                values: vec![
+                    TupleValue::Integer(page_id as i32),
+                    TupleValue::Varchar(Name().fake()),
+                ],
+            })
+            .unwrap();
+            p
+        })
+        .collect()
+}
+
+fn assert_batch_roundtrip(filename: &str, writer_backend: IoBackend, reader_backend: IoBackend) {
+    let _ = fs::remove_file(format!("./{}", filename));
+
+    let pages = batch_pages();
+    let writer = Writer::with_backend(".", filename, writer_backend).unwrap();
+    writer
+        .insert_pages(&pages.iter().collect::<Vec<_>>())
+        .unwrap();
+
+    let reader = Reader::with_backend(".", filename, reader_backend).unwrap();
+    assert_eq!(reader.page_count(), 100);
+
+    let mut read_pages = (0..100).rev().map(Page::new).collect::<Vec<_>>();
+    reader
+        .read_pages(&mut read_pages.iter_mut().collect::<Vec<_>>())
+        .unwrap();
+
+    for page in read_pages {
+        assert_eq!(page.data, pages[page.id as usize].data);
+    }
+
+    let mut missing_page = Page::new(100);
+    assert!(reader.read_pages(&mut [&mut missing_page]).is_err());
+
+    fs::remove_file(format!("./{}", filename)).unwrap();
+}
+
+#[test]
+fn test_persist_batch_pread() {
+    assert_batch_roundtrip("02_batch_pread", IoBackend::Pread, IoBackend::Pread);
+}
+
+#[test]
+fn test_persist_batch_io_uring() {
+    assert_batch_roundtrip(
+        "03_batch_io_uring",
+        IoBackend::io_uring_or_pread(),
+        IoBackend::io_uring_or_pread(),
+    );
+}
+
+#[test]
+fn test_persist_mmap_reader() {
+    let _ = fs::remove_file("./04_mmap");
+
+    let pages = batch_pages();
+    let writer = Writer::new(".", "04_mmap").unwrap();
+    writer
+        .insert_pages(&pages.iter().collect::<Vec<_>>())
+        .unwrap();
+
+    // Nothing writes to the file while it is mapped.
+    let reader = unsafe { MmapReader::new(".", "04_mmap") }.unwrap();
+    assert_eq!(reader.page_count(), 100);
+
+    let schema = Schema::from_types(&[DataType::Integer, DataType::Varchar]);
+
+    for page in pages.iter() {
+        let view = reader.page(page.id).unwrap();
+
+        assert_eq!(view.id, page.id);
+        assert_eq!(view.slots, page.slots);
+        assert_eq!(
+            view.read(0, &schema).unwrap(),
+            page.read(0, &schema).unwrap()
+        );
+        assert_eq!(
+            view.read_iterator_raw().collect::<Vec<_>>(),
+            page.read_iterator_raw().collect::<Vec<_>>()
+        );
+    }
+
+    assert!(reader.page(100).is_none());
+
+    fs::remove_file("./04_mmap").unwrap();
+}
+
+#[test]
+fn test_persist_page_size_in_header() {
+    for (i, page_size) in page::SUPPORTED_SIZES.iter().enumerate() {
+        let filename = format!("05_page_size_{}", i);
+        let _ = fs::remove_file(format!("./{}", filename));
+
+        let mut p = Page::with_size(0, *page_size);
+        p.write(&Tuple {
+            schema: &Schema::from_types(&[DataType::Integer]),
+            values: vec![TupleValue::Integer(*page_size as i32)],
+        })
+        .unwrap();
+
+        let writer = Writer::new(".", &filename).unwrap();
+        writer.insert_page(&p).unwrap();
+        writer.insert_page(&p).unwrap();
+
+        let other_size = page::SUPPORTED_SIZES[(i + 1) % page::SUPPORTED_SIZES.len()];
+        assert!(writer.insert_page(&Page::with_size(2, other_size)).is_err());
+
+        let reader = Reader::new(".", &filename).unwrap();
+        assert_eq!(reader.page_size(), *page_size);
+        assert_eq!(reader.page_count(), 2);
+        assert_eq!(
+            fs::metadata(format!("./{}", filename)).unwrap().len(),
+            3 * *page_size as u64
+        );
+
+        let mut page = Page::with_size(1, *page_size);
+        reader.read_page(1, &mut page).unwrap();
+        page.refresh_metadata().unwrap();
+        assert_eq!(page.data, p.data);
+        assert_eq!(page.free_space, p.free_space);
+
+        let mut wrong_size_page = Page::with_size(1, other_size);
+        assert!(reader.read_page(1, &mut wrong_size_page).is_err());
+
+        let mmap_reader = unsafe { MmapReader::new(".", &filename) }.unwrap();
+        assert_eq!(mmap_reader.page_size(), *page_size);
+        assert_eq!(mmap_reader.page_count(), 2);
+
+        fs::remove_file(format!("./{}", filename)).unwrap();
+    }
+}
+
+#[test]
+fn test_persist_relation_segments() {
+    let page_size = 1024 * 4;
+    let remove_segments = || {
+        for segment in ["06_relation", "06_relation.1", "06_relation.2"] {
+            let _ = fs::remove_file(format!("./{}", segment));
+        }
+    };
+    remove_segments();
+
+    // The header takes a page, so every segment holds two pages.
+    let segment_size = 3 * page_size as u64;
+    let schema = Schema::from_types(&[DataType::Integer]);
+
+    {
+        let relation = RelationFile::with_segment_size(
+            ".",
+            "06_relation",
+            page_size,
+            IoBackend::Pread,
+            segment_size,
+        )
+        .unwrap();
+
+        for id in [0, 1, 2, 4] {
+            let mut p = Page::with_size(id, page_size);
+            p.write(&Tuple {
+                schema: &schema,
+                values: vec![TupleValue::Integer(id as i32)],
+            })
+            .unwrap();
+
+            relation.write_page(&p).unwrap();
+        }
+
+        assert_eq!(relation.segment_count(), 3);
+        assert_eq!(relation.page_count(), 5);
+    }
+
+    assert_eq!(fs::metadata("./06_relation").unwrap().len(), segment_size);
+    assert_eq!(fs::metadata("./06_relation.1").unwrap().len(), segment_size);
+
+    let relation = RelationFile::with_segment_size(
+        ".",
+        "06_relation",
+        page_size,
+        IoBackend::Pread,
+        segment_size,
+    )
+    .unwrap();
+    assert_eq!(relation.segment_count(), 3);
+
+    let mut pages = [0, 2, 4].map(|id| Page::with_size(id, page_size));
+    relation
+        .read_pages(&mut pages.iter_mut().collect::<Vec<_>>())
+        .unwrap();
+
+    for page in pages.iter_mut() {
+        page.refresh_metadata().unwrap();
+        assert_eq!(
+            page.read(0, &schema).unwrap().values[0],
+            TupleValue::Integer(page.id as i32)
+        );
+    }
+
+    // A directory that doesn't exist is an error, not a panic.
+    assert!(
+        RelationFile::open(
+            "./06_missing_directory",
+            "06_relation",
+            page_size,
+            IoBackend::Pread
+        )
+        .is_err()
+    );
+
+    remove_segments();
+}
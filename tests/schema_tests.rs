@@ -0,0 +1,406 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    pub mod type_converter {
+        include!("../src/util/type_converter.rs");
+    }
+
+    pub mod temporal {
+        include!("../src/util/temporal.rs");
+    }
+
+    pub mod decimal {
+        include!("../src/util/decimal.rs");
+    }
+
+    pub mod uuid {
+        include!("../src/util/uuid.rs");
+    }
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+use schema::{Column, DataType, Schema, SchemaError};
+use tuple::{Tuple, TupleDecodeError, TupleToDataError, TupleValue};
+use util::uuid::Uuid;
+
+#[test]
+fn test_build_schema() {
+    let schema = Schema::parse(&[("id", "INTEGER"), ("name", "VarChar")]).unwrap();
+
+    assert_eq!(schema.len(), 2);
+    assert_eq!(
+        schema.columns(),
+        &[
+            Column::new("id", DataType::Integer),
+            Column::new("name", DataType::Varchar)
+        ]
+    );
+    assert_eq!(schema.index_of("name"), Some(1));
+    assert_eq!(schema.index_of("email"), None);
+
+    assert_eq!(
+        Schema::parse(&[("id", "float")]),
+        Err(SchemaError::UnknownType("float".to_owned()))
+    );
+    assert_eq!(
+        Schema::parse(&[("id", "integer"), ("id", "varchar")]),
+        Err(SchemaError::DuplicateColumn("id".to_owned()))
+    );
+}
+
+#[test]
+fn test_tuple_must_match_schema() {
+    let schema = Schema::from_types(&[DataType::Integer, DataType::Varchar]);
+
+    let tuple = Tuple {
+        schema: &schema,
+        values: vec![TupleValue::Integer(1), TupleValue::Integer(2)],
+    };
+    assert!(matches!(
+        tuple.to_data(),
+        Err(TupleToDataError::ValueTypeMismatch(1))
+    ));
+
+    let tuple = Tuple {
+        schema: &schema,
+        values: vec![TupleValue::Integer(1)],
+    };
+    assert!(matches!(
+        tuple.to_data(),
+        Err(TupleToDataError::ColumnCountMismatch(1))
+    ));
+
+    let tuple = Tuple {
+        schema: &schema,
+        values: vec![TupleValue::Integer(1), TupleValue::Varchar("a".to_owned())],
+    };
+    assert_eq!(
+        Tuple::read(&schema, &tuple.to_data().unwrap()).unwrap(),
+        tuple
+    );
+}
+
+#[test]
+fn test_null_values() {
+    let schema = Schema::parse(&[("id", "integer NOT NULL"), ("name", "varchar")]).unwrap();
+    assert!(!schema.columns()[0].nullable);
+    assert!(schema.columns()[1].nullable);
+    assert_eq!(schema.null_bitmap_size(), 1);
+
+    let tuple = Tuple {
+        schema: &schema,
+        values: vec![TupleValue::Integer(7), TupleValue::Null],
+    };
+    let data = tuple.to_data().unwrap();
+    assert_eq!(data, vec![0b10, 0, 0, 0, 7]);
+    assert_eq!(Tuple::read(&schema, &data).unwrap(), tuple);
+
+    let tuple = Tuple {
+        schema: &schema,
+        values: vec![TupleValue::Null, TupleValue::Varchar("a".to_owned())],
+    };
+    assert!(matches!(
+        tuple.to_data(),
+        Err(TupleToDataError::NullInNotNullColumn(0))
+    ));
+
+    let not_null = Schema::parse(&[("id", "integer not null")]).unwrap();
+    assert_eq!(not_null.null_bitmap_size(), 0);
+
+    let wide = Schema::from_types(&[DataType::Integer; 9]);
+    assert_eq!(wide.null_bitmap_size(), 2);
+
+    let mut values = (0..9).map(TupleValue::Integer).collect::<Vec<_>>();
+    values[8] = TupleValue::Null;
+    let tuple = Tuple {
+        schema: &wide,
+        values,
+    };
+    let data = tuple.to_data().unwrap();
+    assert_eq!(&data[..2], &[0, 1]);
+    assert_eq!(Tuple::read(&wide, &data).unwrap(), tuple);
+}
+
+#[test]
+fn test_numeric_types() {
+    let schema = Schema::parse(&[
+        ("a", "smallint"),
+        ("b", "bigint"),
+        ("c", "real"),
+        ("d", "double"),
+        ("e", "boolean"),
+        ("f", "integer"),
+    ])
+    .unwrap();
+
+    let tuple = Tuple {
+        schema: &schema,
+        values: vec![
+            TupleValue::SmallInt(-3),
+            TupleValue::BigInt(i64::MAX),
+            TupleValue::Real(1.5),
+            TupleValue::Double(f64::NAN),
+            TupleValue::Boolean(true),
+            TupleValue::Null,
+        ],
+    };
+    let data = tuple.to_data().unwrap();
+    assert_eq!(data.len(), 1 + 2 + 8 + 4 + 8 + 1);
+    assert_eq!(Tuple::read(&schema, &data).unwrap(), tuple);
+
+    for data_type in schema.data_types() {
+        assert_eq!(DataType::parse(&data_type.name()), Ok(data_type));
+    }
+}
+
+#[test]
+fn test_value_ordering() {
+    use std::cmp::Ordering;
+
+    let nan = TupleValue::Double(f64::NAN);
+    assert_eq!(nan, TupleValue::Double(f64::NAN));
+    assert!(nan > TupleValue::Double(f64::INFINITY));
+    assert!(TupleValue::Real(f32::NAN) > TupleValue::Real(1.0));
+    assert_eq!(TupleValue::Double(-0.0), TupleValue::Double(0.0));
+
+    assert!(TupleValue::BigInt(-1) < TupleValue::BigInt(1));
+    assert!(TupleValue::Boolean(false) < TupleValue::Boolean(true));
+    assert_eq!(
+        TupleValue::SmallInt(2).partial_cmp(&TupleValue::SmallInt(1)),
+        Some(Ordering::Greater)
+    );
+
+    assert_eq!(TupleValue::Null, TupleValue::Null);
+    assert_eq!(TupleValue::Null.sql_cmp(&TupleValue::Null), None);
+    assert!(TupleValue::Null > TupleValue::Double(f64::NAN));
+    assert_eq!(TupleValue::Integer(1), TupleValue::BigInt(1));
+    assert_eq!(
+        TupleValue::Integer(1).sql_cmp(&TupleValue::BigInt(1)),
+        Some(Ordering::Equal)
+    );
+}
+
+#[test]
+fn test_cross_type_comparison() {
+    use std::cmp::Ordering;
+    use std::collections::HashSet;
+    use tuple::{Binary, CaseInsensitive};
+    use util::decimal::Decimal;
+    use util::temporal::{Date, Time, Timestamp, TimestampTz};
+
+    let decimal = |s| TupleValue::Decimal(Decimal::parse(s).unwrap());
+    assert_eq!(decimal("1.50"), TupleValue::Real(1.5));
+    assert_eq!(decimal("2"), TupleValue::SmallInt(2));
+    assert!(decimal("2.5") < TupleValue::BigInt(3));
+    assert!(TupleValue::BigInt(i64::MAX) > TupleValue::BigInt(i64::MAX - 1));
+    assert!(TupleValue::Double(f64::NAN) > TupleValue::BigInt(i64::MAX));
+
+    // Integers beyond the precision of a double are compared exactly.
+    let (above, exact) = (
+        TupleValue::BigInt((1 << 53) + 1),
+        TupleValue::BigInt(1 << 53),
+    );
+    assert!(above > TupleValue::Double((1i64 << 53) as f64));
+    assert_eq!(exact, TupleValue::Double((1i64 << 53) as f64));
+    assert!(TupleValue::Double(0.1) > decimal("0.1"));
+    assert!(TupleValue::Double(0.1) < decimal("0.1000000000000000056"));
+    assert!(TupleValue::Real(f32::INFINITY) > TupleValue::BigInt(i64::MAX));
+
+    assert_eq!(
+        TupleValue::Char("ab  ".to_owned()),
+        TupleValue::Varchar("ab".to_owned())
+    );
+    assert!(TupleValue::Varchar("ab ".to_owned()) > TupleValue::Char("ab".to_owned()));
+
+    let midnight = Timestamp::new(Date(3), Time(0));
+    assert_eq!(TupleValue::Date(Date(3)), TupleValue::Timestamp(midnight));
+    assert_eq!(
+        TupleValue::Timestamp(midnight),
+        TupleValue::TimestampTz(TimestampTz(midnight.0))
+    );
+
+    // Values of different families are ordered but incomparable in SQL.
+    assert!(TupleValue::Boolean(true) < TupleValue::Integer(0));
+    assert!(TupleValue::Integer(0) < TupleValue::Varchar(String::new()));
+    assert_eq!(
+        TupleValue::Integer(0).sql_cmp(&TupleValue::Varchar("0".to_owned())),
+        None
+    );
+
+    let set = [
+        TupleValue::Integer(1),
+        TupleValue::BigInt(1),
+        TupleValue::Double(1.0),
+        decimal("1.000"),
+        TupleValue::Double(-0.0),
+        TupleValue::SmallInt(0),
+        TupleValue::Char("a ".to_owned()),
+        TupleValue::Varchar("a".to_owned()),
+        decimal("0.1"),
+        decimal("0.10"),
+        TupleValue::Double(0.1),
+        TupleValue::Double(i64::MAX as f64),
+        TupleValue::BigInt(i64::MAX),
+        decimal("9223372036854775808.0"),
+    ]
+    .into_iter()
+    .collect::<HashSet<_>>();
+    assert_eq!(set.len(), 7);
+
+    let upper = TupleValue::Varchar("ABC".to_owned());
+    let lower = TupleValue::Varchar("abd".to_owned());
+    assert_eq!(upper.cmp_collated(&lower, &Binary), Ordering::Less);
+    assert!(lower.cmp_collated(&upper, &CaseInsensitive).is_gt());
+    assert_eq!(
+        TupleValue::Varchar("Straße".to_owned())
+            .cmp_collated(&TupleValue::Varchar("STRAßE".to_owned()), &CaseInsensitive),
+        Ordering::Equal
+    );
+}
+
+#[test]
+fn test_tuple_ordering() {
+    use std::cmp::Ordering;
+    use tuple::CaseInsensitive;
+
+    let schema = Schema::parse(&[("id", "integer"), ("name", "varchar")]).unwrap();
+    let tuple = |id, name: &str| Tuple {
+        schema: &schema,
+        values: vec![
+            TupleValue::Integer(id),
+            TupleValue::Varchar(name.to_owned()),
+        ],
+    };
+
+    let mut tuples = vec![tuple(2, "a"), tuple(1, "b"), tuple(1, "B"), tuple(1, "a")];
+    tuples.sort();
+    assert_eq!(
+        tuples,
+        vec![tuple(1, "B"), tuple(1, "a"), tuple(1, "b"), tuple(2, "a")]
+    );
+
+    assert_eq!(
+        tuple(1, "b").compare_on(&tuple(2, "b"), &[1]),
+        Ordering::Equal
+    );
+    assert_eq!(
+        tuple(1, "b").compare_on(&tuple(2, "a"), &[1, 0]),
+        Ordering::Greater
+    );
+    assert_eq!(
+        tuple(1, "b").compare_on_collated(&tuple(1, "B"), &[1, 0], &CaseInsensitive),
+        Ordering::Equal
+    );
+
+    // Tuples of other schemas with equal values are equal.
+    let other = Schema::parse(&[("a", "bigint"), ("b", "char(3)")]).unwrap();
+    let tuple = Tuple {
+        schema: &other,
+        values: vec![TupleValue::BigInt(1), TupleValue::Char("a  ".to_owned())],
+    };
+    assert_eq!(tuple.cmp(&tuples[1]), Ordering::Equal);
+}
+
+#[test]
+fn test_binary_uuid_and_char_types() {
+    let schema = Schema::parse(&[
+        ("a", "bytea"),
+        ("b", "uuid not null"),
+        ("c", "char(4)"),
+        ("d", "varbinary"),
+        ("e", "varchar"),
+    ])
+    .unwrap();
+    assert_eq!(schema.columns()[2].data_type, DataType::Char { length: 4 });
+    assert_eq!(schema.columns()[3].data_type, DataType::Bytea);
+    assert_eq!(
+        DataType::parse("CHARACTER"),
+        Ok(DataType::Char { length: 1 })
+    );
+    assert_eq!(
+        DataType::parse("char(0)"),
+        Err(SchemaError::InvalidTypeModifier("char(0)".to_owned()))
+    );
+
+    let uuid = Uuid::parse("{0123ABCD-4567-89ab-cdef-0123456789AB}").unwrap();
+    assert_eq!(uuid.to_string(), "0123abcd-4567-89ab-cdef-0123456789ab");
+    assert_eq!(Uuid::parse("0123abcd456789abcdef0123456789ab"), Ok(uuid));
+    assert!(Uuid::parse("0123abcd-4567-89ab-cdef-0123456789a").is_err());
+    assert!(Uuid::parse("0123abcd-4567-89ab-cdef-0123456789ag").is_err());
+
+    let tuple = Tuple {
+        schema: &schema,
+        values: vec![
+            TupleValue::Bytea(vec![0, 0xff, 0xc3, 0x28]),
+            TupleValue::Uuid(uuid),
+            TupleValue::Char("ab".to_owned()),
+            TupleValue::Null,
+            TupleValue::Varchar("é".to_owned()),
+        ],
+    };
+    let data = tuple.to_data().unwrap();
+    assert_eq!(data.len(), 1 + (2 + 4) + 16 + (2 + 4) + (2 + 2));
+
+    let read = Tuple::read(&schema, &data).unwrap();
+    assert_eq!(read, tuple);
+    assert!(matches!(&read.values[2], TupleValue::Char(c) if c == "ab  "));
+
+    // Only trailing spaces may be cut from a char value.
+    let schema = Schema::parse(&[("c", "char(2)")]).unwrap();
+    for (value, fits) in [("ab   ", true), ("abc", false)] {
+        let tuple = Tuple {
+            schema: &schema,
+            values: vec![TupleValue::Char(value.to_owned())],
+        };
+        assert_eq!(
+            !matches!(tuple.to_data(), Err(TupleToDataError::CharTooLong(0))),
+            fits
+        );
+    }
+}
+
+#[test]
+fn test_decode_errors() {
+    let schema =
+        Schema::parse(&[("id", "integer not null"), ("name", "varchar not null")]).unwrap();
+
+    assert_eq!(
+        Tuple::read(&schema, &[0, 0, 0, 1, 0, 2, b'a', b'b'])
+            .unwrap()
+            .values,
+        vec![TupleValue::Integer(1), TupleValue::Varchar("ab".to_owned())]
+    );
+    assert_eq!(
+        Tuple::read(&schema, &[0, 0, 0]),
+        Err(TupleDecodeError::TruncatedColumn(0))
+    );
+    assert_eq!(
+        Tuple::read(&schema, &[0, 0, 0, 1, 0]),
+        Err(TupleDecodeError::TruncatedColumn(1))
+    );
+    assert_eq!(
+        Tuple::read(&schema, &[0, 0, 0, 1, 0, 3, b'a', b'b']),
+        Err(TupleDecodeError::TruncatedColumn(1))
+    );
+    assert_eq!(
+        Tuple::read(&schema, &[0, 0, 0, 1, 0, 1, b'a', b'b']),
+        Err(TupleDecodeError::BadLength(8))
+    );
+    assert_eq!(
+        Tuple::read(&schema, &[0, 0, 0, 1, 0, 2, 0xc3, 0x28]),
+        Err(TupleDecodeError::InvalidUtf8(1))
+    );
+
+    let nullable = Schema::from_types(&[DataType::Integer; 9]);
+    assert_eq!(
+        Tuple::read(&nullable, &[0]),
+        Err(TupleDecodeError::BadLength(1))
+    );
+}
@@ -0,0 +1,375 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    include!("../src/util/mod.rs");
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+mod page {
+    include!("../src/page.rs");
+}
+
+mod persist {
+    include!("../src/persist/mod.rs");
+}
+
+mod buffer_pool {
+    include!("../src/buffer_pool/mod.rs");
+}
+
+mod free_space_map {
+    include!("../src/free_space_map.rs");
+}
+
+mod zone_map {
+    include!("../src/zone_map.rs");
+}
+
+mod bloom_filter {
+    include!("../src/bloom_filter.rs");
+}
+
+mod heap_file {
+    include!("../src/heap_file.rs");
+}
+
+mod index {
+    include!("../src/index/mod.rs");
+}
+
+mod catalog {
+    include!("../src/catalog.rs");
+}
+
+mod table {
+    include!("../src/table.rs");
+}
+
+use std::fs;
+
+use buffer_pool::buffer_pool::BufferPool;
+use catalog::Catalog;
+use heap_file::RowId;
+use index::btree::{BTree, BTreeError};
+use schema::{DataType, Schema};
+use table::{Table, TableError};
+use tuple::{KeyColumn, Tuple, TupleValue, encode_key};
+
+fn schema() -> Schema {
+    Schema::parse(&[
+        ("id", "integer not null"),
+        ("email", "varchar"),
+        ("name", "varchar"),
+    ])
+    .unwrap()
+}
+
+fn user<'a>(schema: &'a Schema, id: i32, email: Option<&str>, name: &str) -> Tuple<'a> {
+    Tuple {
+        schema,
+        values: vec![
+            TupleValue::Integer(id),
+            email.map_or(TupleValue::Null, |email| {
+                TupleValue::Varchar(email.to_owned())
+            }),
+            TupleValue::Varchar(name.to_owned()),
+        ],
+    }
+}
+
+fn varchar(value: &str) -> TupleValue {
+    TupleValue::Varchar(value.to_owned())
+}
+
+fn row_ids(table: &Table, index: &str, value: TupleValue) -> Vec<RowId> {
+    let mut rows = table
+        .lookup(index, &[value])
+        .unwrap()
+        .into_iter()
+        .map(|(row_id, _)| row_id)
+        .collect::<Vec<_>>();
+    rows.sort();
+    rows
+}
+
+#[test]
+fn test_writes_keep_indexes_consistent() {
+    let path = "./01_table_writes";
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+
+    let pool = BufferPool::new(256, 1024 * 4);
+    let catalog = Catalog::open(&pool, path).unwrap();
+    let info = catalog.create_table("users", &schema()).unwrap();
+    let schema = &info.schema;
+
+    let mut table = Table::open(&pool, &catalog, &info).unwrap();
+    table
+        .create_index(&catalog, "users_email", &["email"], true)
+        .unwrap();
+    table
+        .create_index(&catalog, "users_name", &["name"], false)
+        .unwrap();
+
+    let first = table.insert(&user(schema, 1, Some("a@x"), "ann")).unwrap();
+    let second = table.insert(&user(schema, 2, Some("b@x"), "ann")).unwrap();
+    assert_eq!(row_ids(&table, "users_email", varchar("a@x")), vec![first]);
+    assert_eq!(
+        row_ids(&table, "users_name", varchar("ann")),
+        vec![first, second]
+    );
+
+    // A duplicate key leaves the heap file and every index as they were.
+    assert!(matches!(
+        table.insert(&user(schema, 3, Some("a@x"), "bob")),
+        Err(TableError::UniqueViolation(name)) if name == "users_email"
+    ));
+    assert_eq!(table.scan().count(), 2);
+    assert_eq!(row_ids(&table, "users_name", varchar("bob")), vec![]);
+
+    // Keys with a null are never duplicates.
+    table.insert(&user(schema, 4, None, "null")).unwrap();
+    table.insert(&user(schema, 5, None, "null")).unwrap();
+    assert_eq!(row_ids(&table, "users_name", varchar("null")).len(), 2);
+
+    assert!(matches!(
+        table.update(second, &user(schema, 2, Some("a@x"), "bob")),
+        Err(TableError::UniqueViolation(_))
+    ));
+    assert_eq!(
+        table.get(second).unwrap(),
+        user(schema, 2, Some("b@x"), "ann")
+    );
+
+    // A row keeps its own unique key, only changed keys move.
+    let updated = table
+        .update(second, &user(schema, 2, Some("b@x"), "bob"))
+        .unwrap();
+    assert_eq!(updated, second);
+    assert_eq!(row_ids(&table, "users_name", varchar("ann")), vec![first]);
+    assert_eq!(row_ids(&table, "users_name", varchar("bob")), vec![second]);
+    assert_eq!(row_ids(&table, "users_email", varchar("b@x")), vec![second]);
+
+    // A row that moves is found at its new id by every index.
+    for id in 10..40 {
+        let email = format!("{}@x", id);
+        table
+            .insert(&user(schema, id, Some(&email), &"x".repeat(100)))
+            .unwrap();
+    }
+    // Keys too long for an index are rejected before anything is written.
+    assert!(matches!(
+        table.update(first, &user(schema, 1, Some("c@x"), &"y".repeat(2000))),
+        Err(TableError::Index(_))
+    ));
+    assert_eq!(
+        table.get(first).unwrap(),
+        user(schema, 1, Some("a@x"), "ann")
+    );
+
+    let moved = table
+        .update(first, &user(schema, 1, Some("c@x"), &"y".repeat(900)))
+        .unwrap();
+    assert_ne!(moved, first);
+    assert_eq!(row_ids(&table, "users_email", varchar("a@x")), vec![]);
+    assert_eq!(row_ids(&table, "users_email", varchar("c@x")), vec![moved]);
+    assert_eq!(row_ids(&table, "users_name", varchar("ann")), vec![]);
+    assert_eq!(
+        row_ids(&table, "users_name", varchar(&"y".repeat(900))),
+        vec![moved]
+    );
+
+    table.delete(moved).unwrap();
+    assert_eq!(row_ids(&table, "users_email", varchar("c@x")), vec![]);
+    assert_eq!(
+        row_ids(&table, "users_name", varchar(&"y".repeat(900))),
+        vec![]
+    );
+    assert!(table.delete(moved).is_err());
+
+    // The email is free again.
+    table.insert(&user(schema, 6, Some("c@x"), "cat")).unwrap();
+
+    fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_create_index_on_populated_table() {
+    let path = "./02_table_create_index";
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+
+    {
+        let pool = BufferPool::new(256, 1024 * 4);
+        let catalog = Catalog::open(&pool, path).unwrap();
+        let info = catalog.create_table("users", &schema()).unwrap();
+        let schema = &info.schema;
+
+        let mut table = Table::open(&pool, &catalog, &info).unwrap();
+        for id in 0..3000 {
+            let email = format!("{}@x", id);
+            table
+                .insert(&user(
+                    schema,
+                    id,
+                    Some(&email),
+                    &format!("name {}", id % 100),
+                ))
+                .unwrap();
+        }
+
+        // Duplicates found in the rows keep the index out of the catalog.
+        assert!(matches!(
+            table.create_index(&catalog, "users_name", &["name"], true),
+            Err(TableError::UniqueViolation(_))
+        ));
+        assert_eq!(catalog.indexes(info.id).unwrap(), vec![]);
+
+        table
+            .create_index(&catalog, "users_name", &["name"], false)
+            .unwrap();
+        table
+            .create_index(&catalog, "users_email", &["email"], true)
+            .unwrap();
+        assert_eq!(row_ids(&table, "users_name", varchar("name 42")).len(), 30);
+
+        table
+            .insert(&user(schema, 3000, Some("new@x"), "name 42"))
+            .unwrap();
+        assert_eq!(row_ids(&table, "users_name", varchar("name 42")).len(), 31);
+
+        catalog.flush().unwrap();
+        table.flush().unwrap();
+    }
+
+    let pool = BufferPool::new(256, 1024 * 4);
+    let catalog = Catalog::open(&pool, path).unwrap();
+    let info = catalog.table("users").unwrap().unwrap();
+    let table = Table::open(&pool, &catalog, &info).unwrap();
+
+    assert_eq!(
+        table.indexes().map(|i| i.name.as_str()).collect::<Vec<_>>(),
+        vec!["users_name", "users_email"]
+    );
+    let rows = table.lookup("users_email", &[varchar("1234@x")]).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].1.values[0], TupleValue::Integer(1234));
+    assert!(matches!(
+        table.insert(&user(&info.schema, 1, Some("new@x"), "again")),
+        Err(TableError::UniqueViolation(_))
+    ));
+    assert!(matches!(
+        table.lookup("users_id", &[TupleValue::Integer(1)]),
+        Err(TableError::IndexNotFound(_))
+    ));
+
+    fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_failed_update_of_moved_row() {
+    let path = "./03_table_failed_update";
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+
+    let name = "y".repeat(900);
+
+    let first = {
+        let pool = BufferPool::new(256, 1024 * 4);
+        let catalog = Catalog::open(&pool, path).unwrap();
+        let info = catalog.create_table("users", &schema()).unwrap();
+
+        let mut table = Table::open(&pool, &catalog, &info).unwrap();
+        table
+            .create_index(&catalog, "users_name", &["name"], false)
+            .unwrap();
+
+        let first = table.insert(&user(&info.schema, 0, None, "ann")).unwrap();
+        for id in 1..30 {
+            table
+                .insert(&user(&info.schema, id, None, &"x".repeat(100)))
+                .unwrap();
+        }
+        assert!(table.scan().all(|row| row.unwrap().0.page_id == 0));
+
+        catalog.flush().unwrap();
+        table.flush().unwrap();
+        first
+    };
+
+    // An entry left in the index where the row is going to move makes the
+    // index change of the update fail.
+    {
+        let pool = BufferPool::new(256, 1024 * 4);
+        let catalog = Catalog::open(&pool, path).unwrap();
+        let info = catalog.table("users").unwrap().unwrap();
+        let index = &catalog.indexes(info.id).unwrap()[0];
+
+        let tree = BTree::open(&pool, index.id, path, &index.filename).unwrap();
+        let key = encode_key(&[KeyColumn::new(DataType::Varchar)], &[varchar(&name)]).unwrap();
+        tree.insert(&key, RowId::new(1, 0)).unwrap();
+    }
+
+    let pool = BufferPool::new(256, 1024 * 4);
+    let catalog = Catalog::open(&pool, path).unwrap();
+    let info = catalog.table("users").unwrap().unwrap();
+    let table = Table::open(&pool, &catalog, &info).unwrap();
+
+    assert!(matches!(
+        table.update(first, &user(&info.schema, 0, None, &name)),
+        Err(TableError::Index(BTreeError::DuplicateEntry))
+    ));
+
+    // The row is back, maybe at another id, and the index points at it.
+    assert_eq!(table.scan().count(), 30);
+    let rows = table.lookup("users_name", &[varchar("ann")]).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].1, user(&info.schema, 0, None, "ann"));
+
+    fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_failed_index_build() {
+    let path = "./04_table_failed_index";
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+
+    let pool = BufferPool::new(256, 1024 * 4);
+    let catalog = Catalog::open(&pool, path).unwrap();
+    let info = catalog.create_table("users", &schema()).unwrap();
+
+    let mut table = Table::open(&pool, &catalog, &info).unwrap();
+    table.insert(&user(&info.schema, 1, None, "ann")).unwrap();
+
+    // A tree left in the file of the next index makes its build fail.
+    let filename = format!("index_{}.data", info.id + 1);
+    {
+        let pool = BufferPool::new(16, 1024 * 4);
+        let tree = BTree::open(&pool, info.id + 1, path, &filename).unwrap();
+        tree.insert(b"stale", RowId::new(0, 0)).unwrap();
+    }
+
+    assert!(matches!(
+        table.create_index(&catalog, "users_name", &["name"], false),
+        Err(TableError::Index(BTreeError::NotEmpty))
+    ));
+    assert_eq!(catalog.indexes(info.id).unwrap(), vec![]);
+    assert_eq!(table.indexes().count(), 0);
+    assert!(!fs::exists(format!("{}/{}", path, filename)).unwrap());
+
+    let index = table
+        .create_index(&catalog, "users_name", &["name"], false)
+        .unwrap();
+    assert_eq!(catalog.indexes(info.id).unwrap(), vec![index]);
+    assert_eq!(row_ids(&table, "users_name", varchar("ann")).len(), 1);
+
+    fs::remove_dir_all(path).unwrap();
+}
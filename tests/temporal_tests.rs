@@ -0,0 +1,250 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    pub mod type_converter {
+        include!("../src/util/type_converter.rs");
+    }
+
+    pub mod temporal {
+        include!("../src/util/temporal.rs");
+    }
+
+    pub mod decimal {
+        include!("../src/util/decimal.rs");
+    }
+
+    pub mod uuid {
+        include!("../src/util/uuid.rs");
+    }
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+use schema::Schema;
+use tuple::{Tuple, TupleValue};
+use util::temporal::{Date, Interval, Time, Timestamp, TimestampTz};
+
+#[test]
+fn test_parse_and_format() {
+    for date in [
+        "1970-01-01",
+        "2000-02-29",
+        "1969-12-31",
+        "0001-01-01",
+        "9999-12-31",
+    ] {
+        assert_eq!(Date::parse(date).unwrap().to_string(), date);
+    }
+    assert_eq!(Date::parse("1970-01-02").unwrap(), Date(1));
+    assert_eq!(Date::parse("1969-12-31").unwrap(), Date(-1));
+    assert_eq!(Date::parse("-0044-03-15").unwrap().ymd(), (-44, 3, 15));
+    assert!(Date::parse("2023-02-29").is_err());
+    assert!(Date::parse("2023-13-01").is_err());
+    assert!(Date::parse("2023-1-01").is_err());
+
+    assert_eq!(Time::parse("13:45").unwrap().to_string(), "13:45:00");
+    assert_eq!(
+        Time::parse("23:59:59.5").unwrap(),
+        Time::from_hms_micro(23, 59, 59, 500_000).unwrap()
+    );
+    assert_eq!(
+        Time::parse("00:00:01.000001").unwrap().to_string(),
+        "00:00:01.000001"
+    );
+    assert!(Time::parse("24:00:00").is_err());
+
+    let timestamp = Timestamp::parse("2024-03-10T08:30:15.25").unwrap();
+    assert_eq!(timestamp.to_string(), "2024-03-10T08:30:15.25");
+    assert_eq!(
+        Timestamp::parse("2024-03-10 08:30:15.25").unwrap(),
+        timestamp
+    );
+    assert_eq!(
+        Timestamp::parse("1969-12-31T23:59:59").unwrap(),
+        Timestamp(-1_000_000)
+    );
+    assert_eq!(
+        Timestamp::parse("2024-03-10").unwrap().to_string(),
+        "2024-03-10T00:00:00"
+    );
+
+    let utc = TimestampTz::parse("2024-03-10T08:30:00Z").unwrap();
+    assert_eq!(utc.to_string(), "2024-03-10T08:30:00Z");
+    assert_eq!(
+        TimestampTz::parse("2024-03-10T10:30:00+02:00").unwrap(),
+        utc
+    );
+    assert_eq!(TimestampTz::parse("2024-03-10T03:00:00-0530").unwrap(), utc);
+    assert_eq!(TimestampTz::parse("2024-03-10T09:30:00+01").unwrap(), utc);
+    assert_eq!(
+        utc.to_offset(-3600).unwrap().to_string(),
+        "2024-03-10T07:30:00"
+    );
+    assert!(TimestampTz::parse("2024-03-10T08:30:00+25:00").is_err());
+}
+
+#[test]
+fn test_interval() {
+    let interval = Interval::parse("P1Y2M10DT2H30M1.5S").unwrap();
+    assert_eq!(interval.months, 14);
+    assert_eq!(interval.days, 10);
+    assert_eq!(
+        interval.micros,
+        (2 * 3600 + 30 * 60) * 1_000_000 + 1_500_000
+    );
+    assert_eq!(interval.to_string(), "P1Y2M10DT2H30M1.5S");
+
+    assert_eq!(Interval::parse("P2W").unwrap().days, 14);
+    assert_eq!(Interval::parse("PT0S").unwrap().to_string(), "PT0S");
+    assert_eq!(
+        Interval::parse("-P1DT1H").unwrap(),
+        Interval::new(0, -1, -3_600_000_000)
+    );
+    assert_eq!(Interval::parse("PT-1.5S").unwrap().to_string(), "PT-1.5S");
+    assert!(Interval::parse("P").is_err());
+    assert!(Interval::parse("P1DT").is_err());
+    assert!(Interval::parse("P1H").is_err());
+    assert!(Interval::parse("1D").is_err());
+
+    assert_eq!(Interval::new(1, 0, 0), Interval::new(0, 30, 0));
+    assert!(Interval::new(0, 1, 0) > Interval::new(0, 0, 86_399_999_999));
+}
+
+#[test]
+fn test_arithmetic() {
+    let timestamp = Timestamp::parse("2024-01-31T12:00:00").unwrap();
+
+    assert_eq!(
+        timestamp
+            .checked_add(Interval::parse("P1M").unwrap())
+            .unwrap()
+            .to_string(),
+        "2024-02-29T12:00:00"
+    );
+    assert_eq!(
+        timestamp
+            .checked_add(Interval::parse("P1Y1M").unwrap())
+            .unwrap()
+            .to_string(),
+        "2025-02-28T12:00:00"
+    );
+    assert_eq!(
+        timestamp
+            .checked_sub(Interval::parse("P2MT13H").unwrap())
+            .unwrap()
+            .to_string(),
+        "2023-11-29T23:00:00"
+    );
+    assert_eq!(
+        timestamp
+            .checked_add(Interval::parse("PT36H").unwrap())
+            .unwrap()
+            .to_string(),
+        "2024-02-02T00:00:00"
+    );
+
+    let later = Timestamp::parse("2024-02-02T13:00:00").unwrap();
+    let since = later.since(timestamp).unwrap();
+    assert_eq!(since, Interval::parse("P2DT1H").unwrap());
+    assert_eq!(timestamp.checked_add(since).unwrap(), later);
+
+    let utc = TimestampTz::parse("2024-03-30T23:00:00-02:00").unwrap();
+    assert_eq!(
+        utc.checked_add(Interval::parse("P1D").unwrap())
+            .unwrap()
+            .to_string(),
+        "2024-04-01T01:00:00Z"
+    );
+
+    // Results out of range are errors.
+    let max = Timestamp(i64::MAX);
+    assert!(max.checked_add(Interval::parse("PT1S").unwrap()).is_err());
+    assert!(max.checked_add(Interval::parse("P1D").unwrap()).is_err());
+    assert!(
+        Timestamp(i64::MIN)
+            .checked_sub(Interval::parse("P1M").unwrap())
+            .is_err()
+    );
+    assert!(max.since(Timestamp(-1)).is_err());
+    assert!(
+        TimestampTz(i64::MIN)
+            .checked_sub(Interval::parse("PT1H").unwrap())
+            .is_err()
+    );
+    assert!(TimestampTz(i64::MAX).to_offset(1).is_err());
+}
+
+#[test]
+fn test_invalid_input_is_an_error() {
+    for s in [
+        "é",
+        "2024-01-01Té",
+        "2024-01-01T00:00:00.12345é",
+        "2024-01-01T00:00:00+1é1",
+        "2024-01-01T00:00:00+é",
+        "999999-01-01",
+        "-999999-01-01T00:00:00",
+        "2024-01-01T00:00:4294967296",
+    ] {
+        assert!(Timestamp::parse(s).is_err(), "{}", s);
+        assert!(TimestampTz::parse(s).is_err(), "{}", s);
+    }
+    assert!(Time::parse("00:00:00.1234567é").is_err());
+
+    for s in [
+        "P999999999Y",
+        "P2147483648M",
+        "P-2147483648M-1M",
+        "-P-2147483648M",
+        "P999999999999999999W",
+        "PT999999999999999999H",
+        "PT999999999999999999M",
+        "PT999999999999999999S",
+        "PT9223372036854.999999S",
+        "PT9223372036854775807S",
+        "PT2562047789H",
+        "PT2562047788H2562047788H",
+        "Pé",
+    ] {
+        assert!(Interval::parse(s).is_err(), "{}", s);
+    }
+    assert_eq!(Interval::parse("P-2147483648M").unwrap().months, i32::MIN);
+}
+
+#[test]
+fn test_temporal_tuple() {
+    let schema = Schema::parse(&[
+        ("a", "date"),
+        ("b", "time"),
+        ("c", "timestamp"),
+        ("d", "timestamptz"),
+        ("e", "interval"),
+    ])
+    .unwrap();
+
+    let tuple = Tuple {
+        schema: &schema,
+        values: vec![
+            TupleValue::Date(Date::parse("2024-02-29").unwrap()),
+            TupleValue::Time(Time::parse("12:34:56.789").unwrap()),
+            TupleValue::Timestamp(Timestamp::parse("1900-01-01T00:00:00").unwrap()),
+            TupleValue::Null,
+            TupleValue::Interval(Interval::parse("P-1Y2DT3S").unwrap()),
+        ],
+    };
+
+    let data = tuple.to_data().unwrap();
+    assert_eq!(data.len(), 1 + 4 + 8 + 8 + 16);
+    assert_eq!(Tuple::read(&schema, &data).unwrap(), tuple);
+
+    assert!(
+        TupleValue::Date(Date::parse("2024-01-01").unwrap())
+            < TupleValue::Date(Date::parse("2024-01-02").unwrap())
+    );
+}
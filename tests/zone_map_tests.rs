@@ -0,0 +1,304 @@
+#![cfg_attr(test, allow(dead_code))]
+
+mod util {
+    include!("../src/util/mod.rs");
+}
+
+mod tuple {
+    include!("../src/tuple.rs");
+}
+
+mod schema {
+    include!("../src/schema.rs");
+}
+
+mod page {
+    include!("../src/page.rs");
+}
+
+mod persist {
+    include!("../src/persist/mod.rs");
+}
+
+mod buffer_pool {
+    include!("../src/buffer_pool/mod.rs");
+}
+
+mod free_space_map {
+    include!("../src/free_space_map.rs");
+}
+
+mod zone_map {
+    include!("../src/zone_map.rs");
+}
+
+mod bloom_filter {
+    include!("../src/bloom_filter.rs");
+}
+
+mod heap_file {
+    include!("../src/heap_file.rs");
+}
+
+use std::fs;
+use std::ops::Bound;
+use std::sync::LazyLock;
+
+use buffer_pool::buffer_pool::BufferPool;
+use heap_file::HeapFile;
+use schema::Schema;
+use tuple::{Tuple, TupleValue};
+use zone_map::{Zone, ZoneMap};
+
+static SCHEMA: LazyLock<Schema> =
+    LazyLock::new(|| Schema::parse(&[("id", "integer"), ("name", "varchar")]).unwrap());
+
+fn row(id: i32) -> Tuple<'static> {
+    Tuple {
+        schema: &SCHEMA,
+        values: vec![
+            TupleValue::Integer(id),
+            TupleValue::Varchar(format!("{:0100}", id)),
+        ],
+    }
+}
+
+fn remove_files(filename: &str) {
+    let _ = fs::remove_file(format!("./{}", filename));
+    let _ = fs::remove_file(format!("./{}.fsm", filename));
+    let _ = fs::remove_file(format!("./{}.zm", filename));
+}
+
+fn ids(heap: &HeapFile, start: Bound<&TupleValue>, end: Bound<&TupleValue>) -> Vec<i32> {
+    let mut ids = heap
+        .scan_range(0, start, end)
+        .map(|row| match row.unwrap().1.values[0] {
+            TupleValue::Integer(id) => id,
+            ref value => panic!("Unexpected id {:?}", value),
+        })
+        .collect::<Vec<_>>();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn test_summaries() {
+    let _ = fs::remove_file("./01_zone_map_summaries.zm");
+
+    let mut map = ZoneMap::open(".", "01_zone_map_summaries", &SCHEMA, &[0], 4).unwrap();
+    let ten = TupleValue::Integer(10);
+    let twenty = TupleValue::Integer(20);
+
+    // Pages not covered yet may hold anything.
+    assert!(map.may_match(0, 0, Bound::Unbounded, Bound::Unbounded));
+
+    map.update(1, &[TupleValue::Integer(15)]);
+    map.update(2, &[TupleValue::Integer(12)]);
+    map.update(3, &[TupleValue::Null]);
+    map.cover(7);
+    assert_eq!(map.page_count(), 8);
+    assert_eq!(
+        map.zone(0, 0),
+        Some(&Zone {
+            bounds: Some((TupleValue::Integer(12), TupleValue::Integer(15))),
+            has_nulls: true,
+        })
+    );
+    assert_eq!(map.zone(0, 1), None);
+
+    assert!(map.may_match(0, 0, Bound::Excluded(&ten), Bound::Excluded(&twenty)));
+    assert!(map.may_match(
+        3,
+        0,
+        Bound::Included(&TupleValue::Integer(15)),
+        Bound::Unbounded
+    ));
+    assert!(!map.may_match(
+        3,
+        0,
+        Bound::Excluded(&TupleValue::Integer(15)),
+        Bound::Unbounded
+    ));
+    assert!(!map.may_match(0, 0, Bound::Unbounded, Bound::Excluded(&ten)));
+    // Values of another type never match, a column without a summary always may.
+    assert!(!map.may_match(
+        0,
+        0,
+        Bound::Unbounded,
+        Bound::Included(&TupleValue::Varchar("a".into()))
+    ));
+    assert!(map.may_match(0, 1, Bound::Unbounded, Bound::Excluded(&ten)));
+    // The range of empty pages holds nothing, pages after the map anything.
+    assert!(!map.may_match(5, 0, Bound::Unbounded, Bound::Unbounded));
+    assert!(map.may_match(8, 0, Bound::Unbounded, Bound::Unbounded));
+
+    map.flush().unwrap();
+
+    let reopened = ZoneMap::open(".", "01_zone_map_summaries", &SCHEMA, &[0], 4).unwrap();
+    assert_eq!(reopened.page_count(), 8);
+    assert_eq!(reopened.zone(1, 0), map.zone(1, 0));
+    assert_eq!(reopened.zone(4, 0), Some(&Zone::default()));
+
+    // A map of other columns or ranges starts empty.
+    let other = ZoneMap::open(".", "01_zone_map_summaries", &SCHEMA, &[0, 1], 4).unwrap();
+    assert_eq!(other.page_count(), 0);
+    let other = ZoneMap::open(".", "01_zone_map_summaries", &SCHEMA, &[0], 2).unwrap();
+    assert_eq!(other.page_count(), 0);
+
+    fs::remove_file("./01_zone_map_summaries.zm").unwrap();
+}
+
+#[test]
+fn test_scan_range() {
+    remove_files("02_zone_map_scan");
+
+    let pool = BufferPool::new(64, 1024 * 4);
+    let heap = HeapFile::open(&pool, 0, ".", "02_zone_map_scan", &SCHEMA)
+        .unwrap()
+        .with_zone_map(".", "02_zone_map_scan", &[0], 2)
+        .unwrap();
+
+    for id in 0..2000 {
+        heap.insert(&row(id)).unwrap();
+    }
+    assert!(heap.page_count() > 20);
+
+    let (start, end) = (TupleValue::Integer(1000), TupleValue::Integer(1100));
+    assert_eq!(
+        ids(&heap, Bound::Excluded(&start), Bound::Excluded(&end)),
+        (1001..1100).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        ids(&heap, Bound::Included(&end), Bound::Unbounded),
+        (1100..2000).collect::<Vec<_>>()
+    );
+
+    // Updated and moved values are found wherever they end up.
+    let (row_id, _) = heap.scan().next().unwrap().unwrap();
+    heap.update(row_id, &row(5000)).unwrap();
+    let (row_id, _) = heap.scan().nth(1).unwrap().unwrap();
+    heap.update(
+        row_id,
+        &Tuple {
+            schema: &SCHEMA,
+            values: vec![TupleValue::Integer(6000), TupleValue::Null],
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        ids(
+            &heap,
+            Bound::Included(&TupleValue::Integer(2000)),
+            Bound::Unbounded
+        ),
+        vec![5000, 6000]
+    );
+
+    // Nulls are never in a range.
+    heap.insert(&Tuple {
+        schema: &SCHEMA,
+        values: vec![TupleValue::Null, TupleValue::Null],
+    })
+    .unwrap();
+    assert_eq!(ids(&heap, Bound::Unbounded, Bound::Unbounded).len(), 2000);
+
+    let rows = heap.scan().map(|row| row.unwrap()).collect::<Vec<_>>();
+    for (row_id, _) in rows {
+        heap.delete(row_id).unwrap();
+    }
+    heap.vacuum().unwrap();
+    assert_eq!(
+        ids(&heap, Bound::Unbounded, Bound::Unbounded),
+        Vec::<i32>::new()
+    );
+    heap.flush().unwrap();
+
+    fs::remove_file("./02_zone_map_scan").unwrap();
+    fs::remove_file("./02_zone_map_scan.fsm").unwrap();
+    fs::remove_file("./02_zone_map_scan.zm").unwrap();
+}
+
+#[test]
+fn test_reopen_summarizes_missing_pages() {
+    remove_files("03_zone_map_reopen");
+
+    {
+        let pool = BufferPool::new(64, 1024 * 4);
+        let heap = HeapFile::open(&pool, 0, ".", "03_zone_map_reopen", &SCHEMA).unwrap();
+
+        for id in 0..500 {
+            heap.insert(&row(id)).unwrap();
+        }
+        heap.flush().unwrap();
+    }
+
+    {
+        // Pages written without a zone map are summarized on open.
+        let pool = BufferPool::new(64, 1024 * 4);
+        let heap = HeapFile::open(&pool, 0, ".", "03_zone_map_reopen", &SCHEMA)
+            .unwrap()
+            .with_zone_map(".", "03_zone_map_reopen", &[0], 1)
+            .unwrap();
+        let value = TupleValue::Integer(250);
+        assert_eq!(
+            ids(&heap, Bound::Included(&value), Bound::Included(&value)),
+            vec![250]
+        );
+
+        for id in 500..1000 {
+            heap.insert(&row(id)).unwrap();
+        }
+        heap.flush().unwrap();
+    }
+
+    let pool = BufferPool::new(64, 1024 * 4);
+    let heap = HeapFile::open(&pool, 0, ".", "03_zone_map_reopen", &SCHEMA)
+        .unwrap()
+        .with_zone_map(".", "03_zone_map_reopen", &[0], 1)
+        .unwrap();
+    let (start, end) = (TupleValue::Integer(900), TupleValue::Integer(1000));
+    assert_eq!(
+        ids(&heap, Bound::Included(&start), Bound::Excluded(&end)),
+        (900..1000).collect::<Vec<_>>()
+    );
+
+    fs::remove_file("./03_zone_map_reopen").unwrap();
+    fs::remove_file("./03_zone_map_reopen.fsm").unwrap();
+    fs::remove_file("./03_zone_map_reopen.zm").unwrap();
+}
+
+#[test]
+fn test_reopen_without_flush() {
+    remove_files("04_zone_map_no_flush");
+
+    {
+        let pool = BufferPool::new(64, 1024 * 4);
+        let heap = HeapFile::open(&pool, 0, ".", "04_zone_map_no_flush", &SCHEMA)
+            .unwrap()
+            .with_zone_map(".", "04_zone_map_no_flush", &[0], 1)
+            .unwrap();
+
+        for id in 0..5 {
+            heap.insert(&row(id)).unwrap();
+        }
+        heap.flush().unwrap();
+
+        // The row lands on a page the stored map covers already.
+        heap.insert(&row(1000)).unwrap();
+        assert_eq!(heap.page_count(), 1);
+    }
+
+    let pool = BufferPool::new(64, 1024 * 4);
+    let heap = HeapFile::open(&pool, 0, ".", "04_zone_map_no_flush", &SCHEMA)
+        .unwrap()
+        .with_zone_map(".", "04_zone_map_no_flush", &[0], 1)
+        .unwrap();
+    let value = TupleValue::Integer(1000);
+    assert_eq!(
+        ids(&heap, Bound::Included(&value), Bound::Included(&value)),
+        vec![1000]
+    );
+    heap.flush().unwrap();
+
+    remove_files("04_zone_map_no_flush");
+}